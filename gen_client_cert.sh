@@ -0,0 +1,40 @@
+#!/bin/sh
+# Issue a client certificate signed by the CA of gen_keys.sh, e.g. for a lab machine.
+if [ "$#" -lt 1 ]
+then
+  echo "Error: No client name argument provided"
+  echo "Usage: Provide a client name and optionally a DNS name as arguments"
+  exit 1
+fi
+
+NAME=$1
+CERTS_PATH=certs
+
+cat > ${CERTS_PATH}/${NAME}.conf <<CONF
+[ req ]
+prompt = no
+distinguished_name = dn
+
+[ dn ]
+O = CERG
+CN = ${NAME}
+
+[ client_ext ]
+basicConstraints = CA:FALSE
+keyUsage = digitalSignature
+extendedKeyUsage = clientAuth
+CONF
+
+if [ -n "$2" ]
+then
+  echo "subjectAltName = DNS:$2" >> ${CERTS_PATH}/${NAME}.conf
+fi
+
+openssl genrsa -out ${CERTS_PATH}/${NAME}.key.pem 2048
+openssl req -new -key ${CERTS_PATH}/${NAME}.key.pem -out ${CERTS_PATH}/${NAME}.csr -config ${CERTS_PATH}/${NAME}.conf
+openssl x509 -req \
+    -in ${CERTS_PATH}/${NAME}.csr \
+    -CA ${CERTS_PATH}/rootCA.crt -CAkey ${CERTS_PATH}/rootCA.key \
+    -CAcreateserial -out ${CERTS_PATH}/${NAME}.cert.pem \
+    -days 365 \
+    -sha256 -extfile ${CERTS_PATH}/${NAME}.conf -extensions client_ext
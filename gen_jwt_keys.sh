@@ -0,0 +1,13 @@
+#!/bin/sh
+# Generate an Ed25519 key pair for signing access tokens (JWT_PRIVATE_KEY and JWT_PUBLIC_KEY).
+# Keep the public key of the previous pair in JWT_VERIFY_KEYS while rotating.
+KEYS_PATH=${1:-certs}
+
+mkdir -p ${KEYS_PATH}
+
+STAMP=$(date +%Y%m%d%H%M%S)
+openssl genpkey -algorithm ed25519 -out ${KEYS_PATH}/jwt-${STAMP}.key.pem
+openssl pkey -in ${KEYS_PATH}/jwt-${STAMP}.key.pem -pubout -out ${KEYS_PATH}/jwt-${STAMP}.pub.pem
+
+echo "JWT_PRIVATE_KEY=${KEYS_PATH}/jwt-${STAMP}.key.pem"
+echo "JWT_PUBLIC_KEY=${KEYS_PATH}/jwt-${STAMP}.pub.pem"
@@ -0,0 +1,13 @@
+-- Add up migration script here
+CREATE TABLE IF NOT EXISTS artifacts (
+    result_id    TEXT        NOT NULL REFERENCES results (id) ON DELETE CASCADE ON UPDATE CASCADE,
+    filename     TEXT        NOT NULL,
+    sha256       TEXT        NOT NULL,
+    mime_type    TEXT        NOT NULL,
+    size         BIGINT      NOT NULL,
+    uploader     TEXT        NOT NULL,
+    timestamp    TIMESTAMPTZ NOT NULL,
+    PRIMARY KEY (result_id, filename)
+);
+
+CREATE INDEX IF NOT EXISTS artifacts_sha256_idx ON artifacts (sha256);
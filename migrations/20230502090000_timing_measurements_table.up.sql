@@ -0,0 +1,15 @@
+-- Add up migration script here
+-- measurements follow their result on rename and delete through the foreign key
+CREATE TABLE IF NOT EXISTS timing_measurements (
+    id           BIGSERIAL   PRIMARY KEY,
+    result_id    TEXT        NOT NULL REFERENCES results (id) ON DELETE CASCADE ON UPDATE CASCADE,
+    msg_id       BIGINT,
+    operation    TEXT        NOT NULL,
+    ad_len       BIGINT      NOT NULL,
+    msg_len      BIGINT      NOT NULL,
+    cycles       BIGINT      NOT NULL,
+    extra        JSONB
+);
+
+CREATE INDEX IF NOT EXISTS timing_measurements_result_id_idx ON timing_measurements (result_id);
+CREATE INDEX IF NOT EXISTS timing_measurements_size_idx ON timing_measurements (operation, ad_len, msg_len);
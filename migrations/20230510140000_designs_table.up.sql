@@ -0,0 +1,17 @@
+-- Add up migration script here
+CREATE TABLE IF NOT EXISTS designs (
+    result_id    TEXT             NOT NULL PRIMARY KEY REFERENCES results (id) ON DELETE CASCADE ON UPDATE CASCADE,
+    name         TEXT             NOT NULL,
+    language     TEXT,
+    top          TEXT,
+    clock_port   TEXT,
+    clock_period DOUBLE PRECISION,
+    w            INTEGER,
+    sw           INTEGER,
+    ccw          INTEGER,
+    ccsw         INTEGER,
+    algorithm    TEXT,
+    sources      TEXT[]           NOT NULL,
+    parsed       JSONB            NOT NULL,
+    original     TEXT             NOT NULL
+);
@@ -0,0 +1,19 @@
+-- Add up migration script here
+CREATE TABLE IF NOT EXISTS synthesis_runs (
+    id                  BIGSERIAL        PRIMARY KEY,
+    result_id           TEXT             NOT NULL REFERENCES results (id) ON DELETE CASCADE ON UPDATE CASCADE,
+    device              TEXT             NOT NULL DEFAULT '',
+    tool                TEXT             NOT NULL DEFAULT '',
+    tool_version        TEXT             NOT NULL DEFAULT '',
+    strategy            TEXT             NOT NULL DEFAULT '',
+    target_clock_period DOUBLE PRECISION,
+    settings            JSONB,
+    results             JSONB            NOT NULL,
+    submitter           TEXT             NOT NULL,
+    timestamp           TIMESTAMPTZ      NOT NULL
+);
+
+-- a run is identified by its result and settings, resubmitting it replaces the previous results
+CREATE UNIQUE INDEX IF NOT EXISTS synthesis_runs_key_idx ON synthesis_runs (
+    result_id, device, tool, tool_version, strategy, COALESCE(target_clock_period, 0)
+);
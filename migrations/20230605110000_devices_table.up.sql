@@ -0,0 +1,46 @@
+-- Add up migration script here
+-- `part` is the device without package and speed grade, it is matched as a prefix of the
+-- device names reported by the tools
+CREATE TABLE IF NOT EXISTS devices (
+    part         TEXT             NOT NULL UNIQUE PRIMARY KEY,
+    vendor       TEXT             NOT NULL,
+    family       TEXT             NOT NULL,
+    speed_grade  TEXT,
+    lut          INTEGER,
+    ff           INTEGER,
+    slice        INTEGER,
+    dsp          INTEGER,
+    bram         DOUBLE PRECISION
+);
+
+-- BRAM capacities are 36Kb tiles for Xilinx, EBR blocks for ECP5 and RAM4K blocks for iCE40
+INSERT INTO devices (part, vendor, family, lut, ff, slice, dsp, bram) VALUES
+    ('xc7a12t',   'xilinx',  'artix7',    8000,  16000,  2000,  40,  20),
+    ('xc7a15t',   'xilinx',  'artix7',   10400,  20800,  2600,  45,  25),
+    ('xc7a25t',   'xilinx',  'artix7',   14600,  29200,  3650,  56,  45),
+    ('xc7a35t',   'xilinx',  'artix7',   20800,  41600,  5200,  90,  50),
+    ('xc7a50t',   'xilinx',  'artix7',   32600,  65200,  8150, 120,  75),
+    ('xc7a75t',   'xilinx',  'artix7',   47200,  94400, 11800, 180, 105),
+    ('xc7a100t',  'xilinx',  'artix7',   63400, 126800, 15850, 240, 135),
+    ('xc7a200t',  'xilinx',  'artix7',  134600, 269200, 33650, 740, 365),
+    ('xc7s6',     'xilinx',  'spartan7',  3750,   7500,   938,  10,   5),
+    ('xc7s15',    'xilinx',  'spartan7',  8000,  16000,  2000,  20,  10),
+    ('xc7s25',    'xilinx',  'spartan7', 14600,  29200,  3650,  80,  45),
+    ('xc7s50',    'xilinx',  'spartan7', 32600,  65200,  8150, 120,  75),
+    ('xc7s75',    'xilinx',  'spartan7', 48000,  96000, 12000, 140,  90),
+    ('xc7s100',   'xilinx',  'spartan7', 64000, 128000, 16000, 160, 120),
+    ('lfe5u-12f', 'lattice', 'ecp5',     12000,  12000,  6000,  28,  32),
+    ('lfe5u-25f', 'lattice', 'ecp5',     24000,  24000, 12000,  28,  56),
+    ('lfe5u-45f', 'lattice', 'ecp5',     44000,  44000, 22000,  72, 108),
+    ('lfe5u-85f', 'lattice', 'ecp5',     84000,  84000, 42000, 156, 208),
+    ('lfe5um-25f', 'lattice', 'ecp5',    24000,  24000, 12000,  28,  56),
+    ('lfe5um-45f', 'lattice', 'ecp5',    44000,  44000, 22000,  72, 108),
+    ('lfe5um-85f', 'lattice', 'ecp5',    84000,  84000, 42000, 156, 208),
+    ('ice40hx1k', 'lattice', 'ice40',     1280,   1280,  NULL,   0,  16),
+    ('ice40hx4k', 'lattice', 'ice40',     3520,   3520,  NULL,   0,  20),
+    ('ice40hx8k', 'lattice', 'ice40',     7680,   7680,  NULL,   0,  32),
+    ('ice40lp1k', 'lattice', 'ice40',     1280,   1280,  NULL,   0,  16),
+    ('ice40lp8k', 'lattice', 'ice40',     7680,   7680,  NULL,   0,  32),
+    ('ice40up3k', 'lattice', 'ice40',     2800,   2800,  NULL,   4,  20),
+    ('ice40up5k', 'lattice', 'ice40',     5280,   5280,  NULL,   8,  30)
+ON CONFLICT DO NOTHING;
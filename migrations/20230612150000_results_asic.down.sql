@@ -0,0 +1,2 @@
+-- Add down migration script here
+ALTER TABLE results DROP COLUMN IF EXISTS asic;
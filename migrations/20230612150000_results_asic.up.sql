@@ -0,0 +1,2 @@
+-- Add up migration script here
+ALTER TABLE results ADD COLUMN IF NOT EXISTS asic JSONB;
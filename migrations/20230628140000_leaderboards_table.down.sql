@@ -0,0 +1,3 @@
+-- Add down migration script here
+DROP TABLE IF EXISTS leaderboard_ranks;
+DROP TABLE IF EXISTS leaderboards;
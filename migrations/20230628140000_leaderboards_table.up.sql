@@ -0,0 +1,17 @@
+-- Add up migration script here
+CREATE TABLE IF NOT EXISTS leaderboards (
+    name        TEXT             NOT NULL UNIQUE PRIMARY KEY,
+    definition  JSONB            NOT NULL,
+    creator     TEXT             NOT NULL,
+    timestamp   TIMESTAMPTZ      NOT NULL
+);
+
+-- the last ranking served for each leaderboard, to report rank changes between submissions
+CREATE TABLE IF NOT EXISTS leaderboard_ranks (
+    leaderboard   TEXT             NOT NULL REFERENCES leaderboards (name) ON DELETE CASCADE ON UPDATE CASCADE,
+    result_id     TEXT             NOT NULL REFERENCES results (id) ON DELETE CASCADE ON UPDATE CASCADE,
+    rank          INTEGER          NOT NULL,
+    previous_rank INTEGER,
+    changed       TIMESTAMPTZ      NOT NULL,
+    PRIMARY KEY (leaderboard, result_id)
+);
@@ -0,0 +1,8 @@
+-- Add down migration script here
+ALTER TABLE users ADD COLUMN IF NOT EXISTS is_admin BOOLEAN NOT NULL DEFAULT FALSE;
+
+UPDATE users SET is_admin = EXISTS (
+    SELECT 1 FROM user_roles r WHERE r.user_email = users.email AND r.role = 'admin'
+);
+
+DROP TABLE IF EXISTS user_roles;
@@ -0,0 +1,13 @@
+-- Add up migration script here
+CREATE TABLE IF NOT EXISTS user_roles (
+    user_email  TEXT        NOT NULL REFERENCES users (email) ON DELETE CASCADE ON UPDATE CASCADE,
+    role        TEXT        NOT NULL CHECK (role IN ('reader', 'submitter', 'maintainer', 'admin')),
+    PRIMARY KEY (user_email, role)
+);
+
+-- every existing user could submit, admins could also register users
+INSERT INTO user_roles (user_email, role)
+SELECT email, CASE WHEN is_admin THEN 'admin' ELSE 'submitter' END FROM users
+ON CONFLICT DO NOTHING;
+
+ALTER TABLE users DROP COLUMN IF EXISTS is_admin;
@@ -0,0 +1,13 @@
+-- Add up migration script here
+CREATE TABLE IF NOT EXISTS api_tokens (
+    id           SERIAL      PRIMARY KEY,
+    user_email   TEXT        NOT NULL REFERENCES users (email) ON DELETE CASCADE ON UPDATE CASCADE,
+    name         TEXT        NOT NULL,
+    token_hash   TEXT        NOT NULL UNIQUE,
+    scopes       TEXT[]      NOT NULL,
+    created      TIMESTAMPTZ NOT NULL,
+    expires      TIMESTAMPTZ NOT NULL,
+    last_used    TIMESTAMPTZ
+);
+
+CREATE INDEX IF NOT EXISTS api_tokens_user_email_idx ON api_tokens (user_email);
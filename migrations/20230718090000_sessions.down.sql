@@ -0,0 +1,3 @@
+-- Add down migration script here
+DROP TABLE IF EXISTS refresh_tokens;
+DROP TABLE IF EXISTS revoked_tokens;
@@ -0,0 +1,17 @@
+-- Add up migration script here
+CREATE TABLE IF NOT EXISTS revoked_tokens (
+    jti         TEXT        NOT NULL PRIMARY KEY,
+    expires     TIMESTAMPTZ NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS refresh_tokens (
+    token_hash  TEXT        NOT NULL PRIMARY KEY,
+    session     TEXT        NOT NULL,
+    user_email  TEXT        NOT NULL REFERENCES users (email) ON DELETE CASCADE ON UPDATE CASCADE,
+    created     TIMESTAMPTZ NOT NULL,
+    expires     TIMESTAMPTZ NOT NULL,
+    used        BOOLEAN     NOT NULL DEFAULT FALSE
+);
+
+CREATE INDEX IF NOT EXISTS refresh_tokens_session_idx ON refresh_tokens (session);
+CREATE INDEX IF NOT EXISTS refresh_tokens_user_email_idx ON refresh_tokens (user_email);
@@ -0,0 +1,4 @@
+-- Add down migration script here
+ALTER TABLE users DROP COLUMN IF EXISTS last_login;
+ALTER TABLE users DROP COLUMN IF EXISTS created_at;
+ALTER TABLE users DROP COLUMN IF EXISTS disabled;
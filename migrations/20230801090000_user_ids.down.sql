@@ -0,0 +1,29 @@
+-- Add down migration script here
+ALTER TABLE user_roles ADD COLUMN user_email TEXT;
+UPDATE user_roles r SET user_email = u.email FROM users u WHERE u.id = r.user_id;
+ALTER TABLE user_roles DROP COLUMN user_id;
+
+ALTER TABLE api_tokens ADD COLUMN user_email TEXT;
+UPDATE api_tokens t SET user_email = u.email FROM users u WHERE u.id = t.user_id;
+ALTER TABLE api_tokens DROP COLUMN user_id;
+
+ALTER TABLE refresh_tokens ADD COLUMN user_email TEXT;
+UPDATE refresh_tokens t SET user_email = u.email FROM users u WHERE u.id = t.user_id;
+ALTER TABLE refresh_tokens DROP COLUMN user_id;
+
+DROP INDEX IF EXISTS users_email_idx;
+ALTER TABLE users DROP CONSTRAINT users_pkey;
+ALTER TABLE users ADD PRIMARY KEY (email);
+ALTER TABLE users DROP COLUMN id;
+
+ALTER TABLE user_roles ALTER COLUMN user_email SET NOT NULL;
+ALTER TABLE user_roles ADD FOREIGN KEY (user_email) REFERENCES users (email) ON DELETE CASCADE ON UPDATE CASCADE;
+ALTER TABLE user_roles ADD PRIMARY KEY (user_email, role);
+
+ALTER TABLE api_tokens ALTER COLUMN user_email SET NOT NULL;
+ALTER TABLE api_tokens ADD FOREIGN KEY (user_email) REFERENCES users (email) ON DELETE CASCADE ON UPDATE CASCADE;
+CREATE INDEX IF NOT EXISTS api_tokens_user_email_idx ON api_tokens (user_email);
+
+ALTER TABLE refresh_tokens ALTER COLUMN user_email SET NOT NULL;
+ALTER TABLE refresh_tokens ADD FOREIGN KEY (user_email) REFERENCES users (email) ON DELETE CASCADE ON UPDATE CASCADE;
+CREATE INDEX IF NOT EXISTS refresh_tokens_user_email_idx ON refresh_tokens (user_email);
@@ -0,0 +1,32 @@
+-- Add up migration script here
+ALTER TABLE users ADD COLUMN IF NOT EXISTS id UUID NOT NULL DEFAULT gen_random_uuid();
+
+-- reference users by id instead of email
+ALTER TABLE user_roles ADD COLUMN user_id UUID;
+UPDATE user_roles r SET user_id = u.id FROM users u WHERE u.email = r.user_email;
+ALTER TABLE user_roles DROP COLUMN user_email;
+
+ALTER TABLE api_tokens ADD COLUMN user_id UUID;
+UPDATE api_tokens t SET user_id = u.id FROM users u WHERE u.email = t.user_email;
+ALTER TABLE api_tokens DROP COLUMN user_email;
+
+ALTER TABLE refresh_tokens ADD COLUMN user_id UUID;
+UPDATE refresh_tokens t SET user_id = u.id FROM users u WHERE u.email = t.user_email;
+ALTER TABLE refresh_tokens DROP COLUMN user_email;
+
+-- the email stays unique, but is no longer the primary key
+ALTER TABLE users DROP CONSTRAINT users_pkey;
+ALTER TABLE users ADD PRIMARY KEY (id);
+CREATE UNIQUE INDEX IF NOT EXISTS users_email_idx ON users (email);
+
+ALTER TABLE user_roles ALTER COLUMN user_id SET NOT NULL;
+ALTER TABLE user_roles ADD FOREIGN KEY (user_id) REFERENCES users (id) ON DELETE CASCADE;
+ALTER TABLE user_roles ADD PRIMARY KEY (user_id, role);
+
+ALTER TABLE api_tokens ALTER COLUMN user_id SET NOT NULL;
+ALTER TABLE api_tokens ADD FOREIGN KEY (user_id) REFERENCES users (id) ON DELETE CASCADE;
+CREATE INDEX IF NOT EXISTS api_tokens_user_id_idx ON api_tokens (user_id);
+
+ALTER TABLE refresh_tokens ALTER COLUMN user_id SET NOT NULL;
+ALTER TABLE refresh_tokens ADD FOREIGN KEY (user_id) REFERENCES users (id) ON DELETE CASCADE;
+CREATE INDEX IF NOT EXISTS refresh_tokens_user_id_idx ON refresh_tokens (user_id);
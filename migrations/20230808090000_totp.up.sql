@@ -0,0 +1,26 @@
+-- Add up migration script here
+CREATE TABLE IF NOT EXISTS user_totp (
+    user_id     UUID        NOT NULL PRIMARY KEY REFERENCES users (id) ON DELETE CASCADE,
+    secret      TEXT        NOT NULL,
+    enabled     BOOLEAN     NOT NULL DEFAULT FALSE,
+    last_step   BIGINT,
+    created     TIMESTAMPTZ NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS totp_recovery_codes (
+    user_id     UUID        NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    code_hash   TEXT        NOT NULL,
+    used        TIMESTAMPTZ,
+    PRIMARY KEY (user_id, code_hash)
+);
+
+-- logins waiting for their TOTP code
+CREATE TABLE IF NOT EXISTS login_challenges (
+    token_hash  TEXT        NOT NULL PRIMARY KEY,
+    user_id     UUID        NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    expires     TIMESTAMPTZ NOT NULL,
+    attempts    INTEGER     NOT NULL DEFAULT 0
+);
+
+-- whether a session was started with a second factor
+ALTER TABLE refresh_tokens ADD COLUMN IF NOT EXISTS mfa BOOLEAN NOT NULL DEFAULT FALSE;
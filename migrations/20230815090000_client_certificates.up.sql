@@ -0,0 +1,12 @@
+-- Add up migration script here
+CREATE TABLE IF NOT EXISTS client_certificates (
+    id          SERIAL      PRIMARY KEY,
+    -- the subject or a subject alternative name of the certificate
+    identity    TEXT        NOT NULL UNIQUE,
+    user_id     UUID        NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    scopes      TEXT[]      NOT NULL,
+    created     TIMESTAMPTZ NOT NULL,
+    last_used   TIMESTAMPTZ
+);
+
+CREATE INDEX IF NOT EXISTS client_certificates_user_id_idx ON client_certificates (user_id);
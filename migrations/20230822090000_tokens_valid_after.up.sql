@@ -0,0 +1,3 @@
+-- Add up migration script here
+-- login tokens issued before this time are rejected, set when the sessions of a user are ended
+ALTER TABLE users ADD COLUMN IF NOT EXISTS tokens_valid_after TIMESTAMPTZ;
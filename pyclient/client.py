@@ -1,6 +1,5 @@
 #!/usr/bin/env python3
 
-import csv
 import json
 import sys
 from getpass import getpass
@@ -14,12 +13,6 @@ import urllib3
 from attrs import define
 from dotenv import load_dotenv
 
-try:
-    import tomllib  # type: ignore # pyright: reportMissingImports=none
-except ModuleNotFoundError:
-    # python_version < "3.11":
-    import tomli as tomllib  # type: ignore
-
 load_dotenv()
 
 
@@ -33,6 +26,8 @@ class Api:
     verify: bool = True
     username: Optional[str] = None
     password: Optional[str] = None
+    token: Optional[str] = None
+    totp_code: Optional[str] = None
 
     def __attrs_post_init__(self) -> None:
         self.headers: Dict[str, str] = {
@@ -49,6 +44,10 @@ class Api:
         return f"http{'s' if self.tls else ''}://{self.hostname}:{self.port}/{api_root}"
 
     def login(self, username=None, password=None):
+        if self.token is not None and username is None and password is None:
+            # API tokens are used as they are
+            self.headers["Authorization"] = "Bearer " + self.token
+            return
         if username is None:
             username = self.username
         if password is None:
@@ -64,8 +63,19 @@ class Api:
             "login",
             json={"email": username, "password": password},
         )
+        if success and resp_json and resp_json.get("totp_required"):
+            # two-factor authentication is enabled for the account
+            code = self.totp_code
+            if code is None:
+                code = input("Enter TOTP or recovery code: ")
+            success, resp_json = self.post(
+                "login/totp",
+                json={"challenge": resp_json["challenge"], "code": code.strip()},
+            )
         if not success or not resp_json or not "access_token" in resp_json or "error" in resp_json:
             raise Exception(f"authentication failed: {resp_json}")
+        if "warning" in resp_json:
+            print(f"warning: {resp_json['warning']}")
         access_token = resp_json["access_token"]
         self.headers["Authorization"] = "Bearer " + access_token
         print(f"Successfully logged in as {username}")
@@ -114,14 +124,24 @@ class Api:
     def get_user_profile(self):
         return self.get("user_profile")
 
-    def add_user(self, email, password):
-        return self.post(
-            "register",
-            json={"email": email, "password": password},
-        )
-
-    def submit(self, json):
-        return self.post("submit", json=json)
+    def add_user(self, email, password, roles=None):
+        data = {"email": email, "password": password}
+        if roles:
+            data["roles"] = list(roles)
+        return self.post("register", json=data)
+
+    def submit(
+        self, data, timing_csv: Optional[Path] = None, design_toml: Optional[Path] = None
+    ):
+        if timing_csv is None and design_toml is None:
+            return self.post("submit", json=data)
+        # let the server parse the raw timing CSV and design.toml
+        files = {"results": (None, json.dumps(data), "application/json")}
+        if timing_csv is not None:
+            files["timing"] = (timing_csv.name, timing_csv.read_bytes(), "text/csv")
+        if design_toml is not None:
+            files["design"] = (design_toml.name, design_toml.read_bytes(), "application/toml")
+        return self.post("submit", files=files)
 
     def delete(self, id):
         return self.post("delete", json={"id": id})
@@ -135,7 +155,9 @@ class Api:
 @click.option("--tls-verify/--no-tls-verify", default=True, help="Verify TLS certificate.")
 @click.option("--username", default=None)
 @click.option("--password", default=None)
-def cli(ctx, server, port, tls, tls_verify, username, password):
+@click.option("--token", envvar="CDB_TOKEN", default=None, help="API token to use instead of logging in.")
+@click.option("--totp-code", default=None, help="TOTP or recovery code, prompted for when required.")
+def cli(ctx, server, port, tls, tls_verify, username, password, token, totp_code):
     ctx.ensure_object(dict)
     ctx.obj["api"] = Api(
         hostname=server,
@@ -144,6 +166,8 @@ def cli(ctx, server, port, tls, tls_verify, username, password):
         verify=tls_verify,
         username=username,
         password=password,
+        token=token,
+        totp_code=totp_code,
     )
 
 
@@ -157,6 +181,8 @@ def cli(ctx, server, port, tls, tls_verify, username, password):
 @click.option("--synthesis-settings", type=Path)
 @click.option("--synthesis-results", type=Path)
 @click.option("--design-toml", default=None, type=Path)
+@click.option("--asic-results", default=None, type=Path)
+@click.option("--software-results", default=None, type=Path)
 @click.pass_context
 def submit(
     ctx,
@@ -167,17 +193,14 @@ def submit(
     synthesis_settings,
     synthesis_results,
     design_toml,
+    asic_results,
+    software_results,
 ):
     api: Api = ctx.obj["api"]
     api.login()
 
     metadata = {}
 
-    if design_toml:
-        with open(design_toml, "rb") as f:
-            design_description = tomllib.load(f)
-        metadata["design"] = design_description
-
     if synthesis_settings:
         with open(synthesis_settings) as f:
             synthesis_settings = json.load(f)
@@ -187,21 +210,25 @@ def submit(
         with open(synthesis_results) as f:
             synthesis_results = json.load(f)
 
-    if timing_results:
-        with open(timing_results, encoding="utf-8") as f:
-            csv_reader = csv.DictReader(f)
-            timing_results = list(csv_reader)
+    if asic_results:
+        with open(asic_results) as f:
+            asic_results = json.load(f)
+
+    if software_results:
+        with open(software_results) as f:
+            software_results = json.load(f)
 
     data = {
         "id": submission_id,  # must be unique, no spaces, use lower-case letters, numbers and and under-score,
         "name": submission_name,  #
         "category": submission_category,
         "metadata": metadata,
-        "timing": timing_results,
         "synthesis": synthesis_results,
+        "asic": asic_results,
+        "software": software_results,
     }
 
-    success, r = api.submit(data)
+    success, r = api.submit(data, timing_csv=timing_results, design_toml=design_toml)
 
     if success:
         print("results submitted: ", r)
@@ -290,15 +317,22 @@ def rename(ctx, current_id, new_id):
 @click.command("adduser")
 @click.argument("username")
 @click.option("--admin-password", prompt="Enter admin password", hide_input=True, required=True)
+@click.option(
+    "--role",
+    "roles",
+    multiple=True,
+    type=click.Choice(["reader", "submitter", "maintainer", "admin"]),
+    help="role of the new user, can be repeated (default: submitter)",
+)
 @click.pass_context
-def add_user(ctx, username, admin_password):
+def add_user(ctx, username, admin_password, roles):
     api: Api = ctx.obj["api"]
 
     api.login(username="admin", password=admin_password)
 
     user_pass = getpass(f"Enter password for new user {username}: ")
 
-    success, r = api.add_user(username, user_pass)
+    success, r = api.add_user(username, user_pass, roles)
 
     if success:
         print("user added", r)
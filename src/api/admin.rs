@@ -1,51 +1,92 @@
 use std::sync::Arc;
 
-use axum::{Json, extract::State};
-use secrecy::ExposeSecret;
-use serde_json::{json, Value};
-use sqlx::{PgPool, Postgres, Transaction};
 use crate::{
+    api::totp,
+    api::users::{end_sessions, find_user_info, set_password, update_user, ProfileUpdate},
     error::AppError,
     models::{
         self,
-        auth::{Claims, User},
-    }, AppState,
+        auth::{Claims, RequirePermission, User, UserInfo},
+        roles::{ManageUsers, Role},
+    },
+    AppState,
+};
+use axum::{
+    extract::{Path, State},
+    Json,
 };
+use serde::Deserialize;
+use serde_json::{json, Value};
+use sqlx::{PgPool, Postgres, Transaction};
+use uuid::Uuid;
+
+/// Parse role names, defaulting to `submitter` when none are given.
+fn parse_roles(names: &[String]) -> Result<Vec<Role>, AppError> {
+    if names.is_empty() {
+        return Ok(vec![Role::Submitter]);
+    }
+    let (roles, errors): (Vec<_>, Vec<_>) = names
+        .iter()
+        .map(|name| name.parse::<Role>())
+        .partition(Result::is_ok);
+    if !errors.is_empty() {
+        return Err(AppError::ValidationError(
+            "roles".into(),
+            errors.into_iter().filter_map(Result::err).collect(),
+        ));
+    }
+    Ok(roles.into_iter().filter_map(Result::ok).collect())
+}
+
+/// Replace the roles of a user.
+pub async fn set_user_roles(
+    transaction: &mut Transaction<'_, Postgres>,
+    id: Uuid,
+    roles: &[Role],
+) -> Result<(), AppError> {
+    sqlx::query(r#"DELETE FROM user_roles WHERE user_id = $1"#)
+        .bind(id)
+        .execute(&mut *transaction)
+        .await?;
+    let roles: Vec<&str> = roles.iter().map(|role| role.as_str()).collect();
+    sqlx::query(r#"INSERT INTO user_roles (user_id, role) SELECT $1, unnest($2::text[])"#)
+        .bind(id)
+        .bind(&roles)
+        .execute(&mut *transaction)
+        .await?;
+    Ok(())
+}
 
 pub async fn insert_new_user(
     state: &AppState,
     transaction: &mut Transaction<'_, Postgres>,
     new_user: &User,
-) -> Result<String, AppError> {
-    let argon2_config = argon2::Config::default();
-    let password_hash = argon2::hash_encoded(
-        new_user.password.as_bytes(),
-        state.secret.expose_secret().as_bytes(),
-        &argon2_config,
-    )
-    .unwrap();
+) -> Result<Uuid, AppError> {
+    let roles = parse_roles(&new_user.roles)?;
+    let password_hash = state.password_hasher.hash(&new_user.password)?;
 
-    match sqlx::query!(
+    match sqlx::query_scalar::<_, Uuid>(
         r#"
         INSERT INTO users (
             email,
             password,
-            name,
-            is_admin
+            name
         )
-        VALUES ($1, $2, $3, $4)
+        VALUES ($1, $2, $3)
         ON CONFLICT DO NOTHING
-        RETURNING email
+        RETURNING id
         "#,
-        new_user.email,
-        password_hash,
-        new_user.name,
-        new_user.is_admin,
     )
-    .fetch_optional(transaction)
+    .bind(&new_user.email)
+    .bind(password_hash)
+    .bind(&new_user.name)
+    .fetch_optional(&mut *transaction)
     .await
     {
-        Ok(Some(record)) => Ok(record.email),
+        Ok(Some(id)) => {
+            set_user_roles(transaction, id, &roles).await?;
+            Ok(id)
+        }
         Ok(None) => Err(AppError::UserAlreadyExits),
         Err(err) => Err(AppError::SqlxError(err)),
     }
@@ -53,15 +94,10 @@ pub async fn insert_new_user(
 
 pub async fn register(
     State(state): State<Arc<AppState>>,
-    claims: Claims,
+    RequirePermission(claims, _): RequirePermission<ManageUsers>,
     Json(new_user): Json<models::auth::User>,
 ) -> Result<Json<Value>, AppError> {
-    if !claims.is_admin {
-        return Err(AppError::AuthenticationError(format!(
-            "User {} does not have admin privileges",
-            claims.username
-        )));
-    }
+    log::info!("{} is registering {}", claims.username, new_user.email);
 
     // check if email or password is a blank string
     if new_user.email.is_empty() || new_user.password.is_empty() {
@@ -73,6 +109,242 @@ pub async fn register(
     transaction.commit().await?;
 
     Ok(Json(json!({
-        "success": format!("registered user: {new_user_id}")
+        "success": format!("registered user: {}", new_user.email),
+        "id": new_user_id,
+    })))
+}
+
+/// The id of the user given in a path, either by id or by email.
+pub async fn resolve_user(pool: &PgPool, user: &str) -> Result<Uuid, AppError> {
+    let id = match user.parse::<Uuid>() {
+        Ok(id) => {
+            sqlx::query_scalar(r#"SELECT id FROM users WHERE id = $1"#)
+                .bind(id)
+                .fetch_optional(pool)
+                .await?
+        }
+        Err(_) => {
+            sqlx::query_scalar(r#"SELECT id FROM users WHERE email = $1"#)
+                .bind(user)
+                .fetch_optional(pool)
+                .await?
+        }
+    };
+    id.ok_or(AppError::UserDoesNotExist)
+}
+
+pub async fn get_roles(
+    State(state): State<Arc<AppState>>,
+    RequirePermission(_claims, _): RequirePermission<ManageUsers>,
+    Path(user): Path<String>,
+) -> Result<Json<Value>, AppError> {
+    let id = resolve_user(&state.pool, &user).await?;
+    let info = find_user_info(&state.pool, id).await?;
+    Ok(Json(json!({
+        "id": info.id,
+        "email": info.email,
+        "roles": info.roles,
+    })))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RolesRequest {
+    roles: Vec<Role>,
+}
+
+/// Replace the roles of a user. Removing a role ends their sessions, since the roles of login
+/// tokens are fixed when they are issued.
+pub async fn put_roles(
+    State(state): State<Arc<AppState>>,
+    RequirePermission(claims, _): RequirePermission<ManageUsers>,
+    Path(user): Path<String>,
+    Json(request): Json<RolesRequest>,
+) -> Result<Json<Value>, AppError> {
+    let id = resolve_user(&state.pool, &user).await?;
+    if id == claims.sub && !request.roles.contains(&Role::Admin) {
+        return Err(AppError::ValidationError(
+            "roles".into(),
+            vec!["admins cannot remove their own admin role".to_owned()],
+        ));
+    }
+    let mut roles = request.roles;
+    roles.sort();
+    roles.dedup();
+
+    let mut transaction = state.pool.begin().await?;
+    let previous: Vec<String> =
+        sqlx::query_scalar(r#"SELECT role FROM user_roles WHERE user_id = $1 FOR UPDATE"#)
+            .bind(id)
+            .fetch_all(&mut transaction)
+            .await?;
+    set_user_roles(&mut transaction, id, &roles).await?;
+    if previous
+        .iter()
+        .any(|role| !roles.iter().any(|r| r.as_str() == role))
+    {
+        end_sessions(&mut transaction, id).await?;
+    }
+    transaction.commit().await?;
+
+    log::info!(
+        "{} set the roles of {} to {:?}",
+        claims.username,
+        user,
+        roles
+    );
+    Ok(Json(json!({
+        "id": id,
+        "roles": roles,
+    })))
+}
+
+pub async fn list_users(
+    State(state): State<Arc<AppState>>,
+    RequirePermission(_claims, _): RequirePermission<ManageUsers>,
+) -> Result<Json<Value>, AppError> {
+    let users: Vec<UserInfo> = sqlx::query_as(&format!(
+        "SELECT {} FROM users ORDER BY email ASC",
+        UserInfo::COLUMNS
+    ))
+    .fetch_all(&state.pool)
+    .await?;
+    Ok(Json(json!(users)))
+}
+
+fn not_self(claims: &Claims, id: Uuid, action: &str) -> Result<(), AppError> {
+    if claims.sub == id {
+        return Err(AppError::ValidationError(
+            "user".into(),
+            vec![format!("admins cannot {action} themselves")],
+        ));
+    }
+    Ok(())
+}
+
+async fn set_disabled(state: &AppState, id: Uuid, disabled: bool) -> Result<(), AppError> {
+    let mut transaction = state.pool.begin().await?;
+    let updated = sqlx::query(r#"UPDATE users SET disabled = $2 WHERE id = $1"#)
+        .bind(id)
+        .bind(disabled)
+        .execute(&mut transaction)
+        .await?
+        .rows_affected();
+    if updated == 0 {
+        return Err(AppError::UserDoesNotExist);
+    }
+    if disabled {
+        end_sessions(&mut transaction, id).await?;
+    }
+    transaction.commit().await?;
+    Ok(())
+}
+
+/// Disable a user: they can no longer log in and their tokens are rejected.
+pub async fn disable_user(
+    State(state): State<Arc<AppState>>,
+    RequirePermission(claims, _): RequirePermission<ManageUsers>,
+    Path(user): Path<String>,
+) -> Result<Json<Value>, AppError> {
+    let id = resolve_user(&state.pool, &user).await?;
+    not_self(&claims, id, "disable")?;
+    set_disabled(&state, id, true).await?;
+    log::info!("{} disabled user {}", claims.username, user);
+    Ok(Json(json!(find_user_info(&state.pool, id).await?)))
+}
+
+pub async fn enable_user(
+    State(state): State<Arc<AppState>>,
+    RequirePermission(claims, _): RequirePermission<ManageUsers>,
+    Path(user): Path<String>,
+) -> Result<Json<Value>, AppError> {
+    let id = resolve_user(&state.pool, &user).await?;
+    set_disabled(&state, id, false).await?;
+    log::info!("{} enabled user {}", claims.username, user);
+    Ok(Json(json!(find_user_info(&state.pool, id).await?)))
+}
+
+/// Change the email and/or name of a user.
+pub async fn update(
+    State(state): State<Arc<AppState>>,
+    RequirePermission(claims, _): RequirePermission<ManageUsers>,
+    Path(user): Path<String>,
+    Json(update): Json<ProfileUpdate>,
+) -> Result<Json<Value>, AppError> {
+    let id = resolve_user(&state.pool, &user).await?;
+    let info = update_user(&state.pool, id, &update).await?;
+    log::info!("{} updated user {} ({})", claims.username, user, info.email);
+    Ok(Json(json!(info)))
+}
+
+/// Delete a user with their roles, API tokens and sessions. Their results are kept.
+pub async fn delete_user(
+    State(state): State<Arc<AppState>>,
+    RequirePermission(claims, _): RequirePermission<ManageUsers>,
+    Path(user): Path<String>,
+) -> Result<Json<Value>, AppError> {
+    let id = resolve_user(&state.pool, &user).await?;
+    not_self(&claims, id, "delete")?;
+    let email: String = sqlx::query_scalar(r#"DELETE FROM users WHERE id = $1 RETURNING email"#)
+        .bind(id)
+        .fetch_optional(&state.pool)
+        .await?
+        .ok_or(AppError::UserDoesNotExist)?;
+    log::info!("{} deleted user {}", claims.username, email);
+    Ok(Json(json!({
+        "deleted": email,
+        "id": id,
+        "user": claims.username,
+    })))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PasswordReset {
+    password: String,
+}
+
+/// Set a new password for a user, ending their sessions.
+pub async fn reset_password(
+    State(state): State<Arc<AppState>>,
+    RequirePermission(claims, _): RequirePermission<ManageUsers>,
+    Path(user): Path<String>,
+    Json(reset): Json<PasswordReset>,
+) -> Result<Json<Value>, AppError> {
+    if reset.password.is_empty() {
+        return Err(AppError::MissingCredential);
+    }
+    let id = resolve_user(&state.pool, &user).await?;
+    let mut transaction = state.pool.begin().await?;
+    set_password(&state, &mut transaction, id, &reset.password).await?;
+    transaction.commit().await?;
+    log::info!("{} reset the password of {}", claims.username, user);
+    Ok(Json(json!({
+        "success": format!("reset the password of {user}"),
+    })))
+}
+
+/// Remove the two-factor authentication of a user who lost their authenticator and recovery
+/// codes, ending their sessions.
+pub async fn reset_totp(
+    State(state): State<Arc<AppState>>,
+    RequirePermission(claims, _): RequirePermission<ManageUsers>,
+    Path(user): Path<String>,
+) -> Result<Json<Value>, AppError> {
+    let id = resolve_user(&state.pool, &user).await?;
+    let mut transaction = state.pool.begin().await?;
+    if !totp::remove(&mut transaction, id).await? {
+        return Err(AppError::ValidationError(
+            "TOTP".into(),
+            vec![format!("{user} has no two-factor authentication")],
+        ));
+    }
+    end_sessions(&mut transaction, id).await?;
+    transaction.commit().await?;
+    log::info!(
+        "{} reset the two-factor authentication of {}",
+        claims.username,
+        user
+    );
+    Ok(Json(json!({
+        "success": format!("reset the two-factor authentication of {user}"),
     })))
 }
@@ -0,0 +1,130 @@
+use std::{collections::BTreeMap, sync::Arc};
+
+use axum::{
+    extract::{Path, Query, State},
+    Json,
+};
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+use crate::{
+    error::AppError,
+    models::{
+        auth::RequirePermission,
+        cycle_fit::{self, BlockSizes},
+        design::Design,
+        roles::ReadResults,
+        timing::TimingRow,
+    },
+    parsers::timing::rows_from_json,
+    AppState,
+};
+
+#[derive(Debug, Deserialize)]
+pub struct FitParams {
+    /// block size in bits of both the AD and the message blocks
+    block_bits: Option<i64>,
+    ad_block_bits: Option<i64>,
+    msg_block_bits: Option<i64>,
+    /// flag measurements deviating by more than this many robust standard deviations
+    threshold: Option<f64>,
+}
+
+const DEFAULT_THRESHOLD: f64 = 3.5;
+
+/// Fit `cycles = a + b·⌈AD/blk⌉ + c·⌈M/blk⌉` to the timing measurements of a result, for each
+/// operation.
+///
+/// Block sizes not given in the query are taken from `lwc.block_bits` of the result's design
+/// description (`AD` and `PT` for AEAD operations, `HM` for hashing).
+pub async fn fit_cycles(
+    State(state): State<Arc<AppState>>,
+    RequirePermission(_claims, _): RequirePermission<ReadResults>,
+    Path(id): Path<String>,
+    Query(params): Query<FitParams>,
+) -> Result<Json<Value>, AppError> {
+    let threshold = params.threshold.unwrap_or(DEFAULT_THRESHOLD);
+    if threshold.is_nan() || threshold <= 0.0 {
+        return Err(AppError::ValidationError(
+            "fit parameters".into(),
+            vec![format!("threshold must be positive, got {threshold}")],
+        ));
+    }
+
+    let timing: Option<Value> =
+        sqlx::query_scalar::<_, Option<Value>>(r#"SELECT timing FROM results WHERE id = $1"#)
+            .bind(&id)
+            .fetch_optional(&state.pool)
+            .await?
+            .ok_or_else(|| AppError::IdNotFound(id.clone()))?;
+    let rows = rows_from_json(&timing.unwrap_or_default());
+    if rows.is_empty() {
+        return Err(AppError::ValidationError(
+            "timing".into(),
+            vec![format!("{id} has no timing measurements")],
+        ));
+    }
+
+    let design: Option<Design> =
+        sqlx::query_scalar::<_, Value>(r#"SELECT parsed FROM designs WHERE result_id = $1"#)
+            .bind(&id)
+            .fetch_optional(&state.pool)
+            .await?
+            .and_then(|parsed| serde_json::from_value(parsed).ok());
+
+    let mut operations: BTreeMap<&str, Vec<&TimingRow>> = BTreeMap::new();
+    for row in &rows {
+        operations.entry(&row.operation).or_default().push(row);
+    }
+
+    let mut fits = Vec::new();
+    let mut errors = Vec::new();
+    for (operation, rows) in operations {
+        let (ad_segment, msg_segment) = match operation {
+            "hash" => ("HM", "HM"),
+            _ => ("AD", "PT"),
+        };
+        let block_bits = |explicit: Option<i64>, segment: &str| {
+            explicit
+                .or(params.block_bits)
+                .or_else(|| design.as_ref()?.block_bits(segment))
+        };
+        let (Some(ad_bits), Some(msg_bits)) = (
+            block_bits(params.ad_block_bits, ad_segment),
+            block_bits(params.msg_block_bits, msg_segment),
+        ) else {
+            errors.push(format!(
+                "{operation}: unknown block size, pass block_bits or submit a design.toml with lwc.block_bits"
+            ));
+            continue;
+        };
+        if let Some(bits) = [ad_bits, msg_bits]
+            .into_iter()
+            .find(|bits| *bits <= 0 || bits % 8 != 0)
+        {
+            errors.push(format!(
+                "{operation}: block size must be a positive multiple of 8 bits, got {bits}"
+            ));
+            continue;
+        }
+        let block_bytes = BlockSizes {
+            ad: ad_bits / 8,
+            msg: msg_bits / 8,
+        };
+        match cycle_fit::fit(operation, &rows, block_bytes, threshold) {
+            Ok(fit) => fits.push(fit),
+            Err(error) => errors.push(error),
+        }
+    }
+
+    if fits.is_empty() {
+        return Err(AppError::ValidationError("cycle fit".into(), errors));
+    }
+
+    Ok(Json(json!({
+        "id": id,
+        "threshold": threshold,
+        "fits": fits,
+        "errors": errors,
+    })))
+}
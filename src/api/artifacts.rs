@@ -0,0 +1,364 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::{Multipart, Path, State},
+    http::header,
+    response::IntoResponse,
+    Json,
+};
+use serde_json::json;
+use sqlx::{Postgres, Transaction};
+use time::OffsetDateTime;
+use tracing::info;
+
+use crate::{
+    error::AppError,
+    models::{
+        artifacts::Artifact,
+        auth::{Claims, RequirePermission},
+        roles::{DeleteResult, ReadResults, SubmitResult},
+    },
+    storage::ArtifactStore,
+    AppState,
+};
+
+/// Lock the blob `sha256` until the end of the transaction, so that it is not removed while an
+/// artifact referring to it is being stored.
+async fn lock_blob(
+    transaction: &mut Transaction<'_, Postgres>,
+    sha256: &str,
+) -> Result<(), AppError> {
+    sqlx::query(r#"SELECT pg_advisory_xact_lock(hashtext('artifact:' || $1))"#)
+        .bind(sha256)
+        .execute(&mut *transaction)
+        .await?;
+    Ok(())
+}
+
+/// Remove blobs that are no longer referenced by any artifact.
+pub async fn remove_orphaned_blobs(state: &AppState, shas: &[String]) -> Result<(), AppError> {
+    for sha256 in shas {
+        let mut transaction = state.pool.begin().await?;
+        lock_blob(&mut transaction, sha256).await?;
+        let referenced: bool =
+            sqlx::query_scalar(r#"SELECT EXISTS(SELECT 1 FROM artifacts WHERE sha256 = $1)"#)
+                .bind(sha256)
+                .fetch_one(&mut transaction)
+                .await?;
+        if !referenced {
+            info!("removing unreferenced artifact blob {}", sha256);
+            state.artifacts.remove(sha256).await?;
+        }
+        transaction.commit().await?;
+    }
+    Ok(())
+}
+
+/// Check that `filename` can be used as the name of an artifact and trim it.
+pub fn sanitize_filename(filename: &str) -> Result<String, AppError> {
+    let filename = filename.trim();
+    if filename.is_empty()
+        || filename == "."
+        || filename == ".."
+        || filename.contains(['/', '\\', '"'])
+        || filename.contains(char::is_control)
+    {
+        return Err(AppError::InvalidUpload(format!(
+            "invalid artifact filename: {filename:?}"
+        )));
+    }
+    Ok(filename.to_owned())
+}
+
+/// The `Content-Disposition` of a download: an ASCII fallback name and the exact name
+/// percent-encoded as `filename*` (RFC 6266).
+fn content_disposition(filename: &str) -> String {
+    let fallback: String = filename
+        .chars()
+        .map(|c| match c {
+            ' '..='~' if c != '"' && c != '\\' => c,
+            _ => '_',
+        })
+        .collect();
+    let encoded: String = filename
+        .bytes()
+        .map(|b| match b {
+            b'0'..=b'9' | b'A'..=b'Z' | b'a'..=b'z' => (b as char).to_string(),
+            b'!' | b'#' | b'$' | b'&' | b'+' | b'-' | b'.' | b'^' | b'_' | b'`' | b'|' | b'~' => {
+                (b as char).to_string()
+            }
+            _ => format!("%{b:02X}"),
+        })
+        .collect();
+    format!("attachment; filename=\"{fallback}\"; filename*=UTF-8''{encoded}")
+}
+
+pub async fn ensure_result_exists(state: &AppState, id: &str) -> Result<(), AppError> {
+    if sqlx::query!(r#"SELECT id from results WHERE id = $1;"#, id)
+        .fetch_optional(&state.pool)
+        .await?
+        .is_none()
+    {
+        return Err(AppError::IdNotFound(id.to_owned()));
+    }
+    Ok(())
+}
+
+/// Check that a result exists and that `claims` allow submitting to its category.
+pub async fn ensure_can_submit_to(
+    state: &AppState,
+    claims: &Claims,
+    id: &str,
+) -> Result<(), AppError> {
+    let category: Option<String> =
+        sqlx::query_scalar(r#"SELECT category FROM results WHERE id = $1"#)
+            .bind(id)
+            .fetch_optional(&state.pool)
+            .await?
+            .ok_or_else(|| AppError::IdNotFound(id.to_owned()))?;
+    claims.require_category(category.as_deref())
+}
+
+/// An artifact stored by `store_artifact` whose transaction is not committed yet.
+pub struct StoredArtifact {
+    pub artifact: Artifact,
+    /// content of the blob, written by `commit_artifacts`
+    data: Vec<u8>,
+    /// checksum of the blob of the replaced artifact of the same name
+    replaced: Option<String>,
+}
+
+/// Store `data` as the artifact `filename` of a result in `transaction`, replacing an artifact
+/// with the same name. The transaction has to be committed with `commit_artifacts`, which
+/// writes the blob.
+pub async fn store_artifact(
+    transaction: &mut Transaction<'_, Postgres>,
+    result_id: &str,
+    filename: &str,
+    content_type: Option<&str>,
+    data: &[u8],
+    uploader: &str,
+) -> Result<StoredArtifact, AppError> {
+    let filename = sanitize_filename(filename)?;
+    let mime_type = content_type
+        .filter(|ct| *ct != "application/octet-stream")
+        .map(str::to_owned)
+        .unwrap_or_else(|| {
+            mime_guess::from_path(&filename)
+                .first_or_octet_stream()
+                .to_string()
+        });
+
+    let sha256 = ArtifactStore::checksum(data);
+    lock_blob(transaction, &sha256).await?;
+
+    let previous: Option<String> = sqlx::query_scalar(
+        r#"SELECT sha256 FROM artifacts WHERE result_id = $1 AND filename = $2 FOR UPDATE"#,
+    )
+    .bind(result_id)
+    .bind(&filename)
+    .fetch_optional(&mut *transaction)
+    .await?;
+
+    let artifact: Artifact = sqlx::query_as(
+        r#"
+        INSERT INTO artifacts (
+            result_id,
+            filename,
+            sha256,
+            mime_type,
+            size,
+            uploader,
+            timestamp
+        )
+        VALUES ($1, $2, $3, $4, $5, $6, $7)
+        ON CONFLICT (result_id, filename) DO UPDATE
+        SET sha256    = EXCLUDED.sha256,
+            mime_type = EXCLUDED.mime_type,
+            size      = EXCLUDED.size,
+            uploader  = EXCLUDED.uploader,
+            timestamp = EXCLUDED.timestamp
+        RETURNING *
+        "#,
+    )
+    .bind(result_id)
+    .bind(&filename)
+    .bind(&sha256)
+    .bind(&mime_type)
+    .bind(data.len() as i64)
+    .bind(uploader)
+    .bind(OffsetDateTime::now_utc())
+    .fetch_one(&mut *transaction)
+    .await?;
+
+    info!(
+        "{} uploaded artifact {} ({} bytes) for {}",
+        uploader, filename, artifact.size, result_id
+    );
+    Ok(StoredArtifact {
+        artifact,
+        data: data.to_vec(),
+        replaced: previous.filter(|sha| *sha != sha256),
+    })
+}
+
+/// Write the blobs of `artifacts` and commit the transaction that stored them, then remove the
+/// blobs they replaced. The blobs are written while the transaction still holds their locks, and
+/// removed again if writing or committing fails, unless other artifacts refer to them.
+pub async fn commit_artifacts(
+    state: &AppState,
+    transaction: Transaction<'_, Postgres>,
+    artifacts: &[StoredArtifact],
+) -> Result<(), AppError> {
+    let mut written = Vec::new();
+    let mut result = Ok(());
+    for stored in artifacts {
+        match state.artifacts.put(&stored.data).await {
+            Ok(sha256) => written.push(sha256),
+            Err(e) => {
+                result = Err(AppError::from(e));
+                break;
+            }
+        }
+    }
+    let result = match result {
+        // the locks have to be released before the blobs can be removed again
+        Err(e) => Err(transaction.rollback().await.err().map_or(e, AppError::from)),
+        Ok(()) => transaction.commit().await.map_err(AppError::from),
+    };
+    if let Err(e) = result {
+        remove_orphaned_blobs(state, &written).await?;
+        return Err(e);
+    }
+    let replaced: Vec<String> = artifacts
+        .iter()
+        .filter_map(|stored| stored.replaced.clone())
+        .collect();
+    remove_orphaned_blobs(state, &replaced).await
+}
+
+pub async fn upload(
+    State(state): State<Arc<AppState>>,
+    RequirePermission(claims, _): RequirePermission<SubmitResult>,
+    Path(id): Path<String>,
+    mut multipart: Multipart,
+) -> Result<Json<serde_json::Value>, AppError> {
+    ensure_can_submit_to(&state, &claims, &id).await?;
+
+    let mut transaction = state.pool.begin().await?;
+    let mut uploaded = Vec::new();
+
+    while let Some(field) = multipart
+        .next_field()
+        .await
+        .map_err(|e| AppError::InvalidUpload(e.to_string()))?
+    {
+        let filename = field
+            .file_name()
+            .or_else(|| field.name())
+            .unwrap_or_default()
+            .to_owned();
+        let content_type = field.content_type().map(str::to_owned);
+        let data = field
+            .bytes()
+            .await
+            .map_err(|e| AppError::InvalidUpload(e.to_string()))?;
+
+        uploaded.push(
+            store_artifact(
+                &mut transaction,
+                &id,
+                &filename,
+                content_type.as_deref(),
+                &data,
+                &claims.username,
+            )
+            .await?,
+        );
+    }
+
+    if uploaded.is_empty() {
+        return Err(AppError::InvalidUpload("no files in request".into()));
+    }
+    commit_artifacts(&state, transaction, &uploaded).await?;
+
+    let uploaded: Vec<Artifact> = uploaded.into_iter().map(|stored| stored.artifact).collect();
+    Ok(Json(json!({
+        "id": id,
+        "artifacts": uploaded,
+        "submitter": claims.username,
+    })))
+}
+
+pub async fn list(
+    State(state): State<Arc<AppState>>,
+    RequirePermission(_claims, _): RequirePermission<ReadResults>,
+    Path(id): Path<String>,
+) -> Result<Json<serde_json::Value>, AppError> {
+    ensure_result_exists(&state, &id).await?;
+
+    let artifacts: Vec<Artifact> =
+        sqlx::query_as(r#"SELECT * FROM artifacts WHERE result_id = $1 ORDER BY filename ASC"#)
+            .bind(&id)
+            .fetch_all(&state.pool)
+            .await?;
+
+    Ok(Json(json!({
+        "id": id,
+        "artifacts": artifacts,
+    })))
+}
+
+async fn find_artifact(state: &AppState, id: &str, filename: &str) -> Result<Artifact, AppError> {
+    sqlx::query_as(r#"SELECT * FROM artifacts WHERE result_id = $1 AND filename = $2"#)
+        .bind(id)
+        .bind(filename)
+        .fetch_optional(&state.pool)
+        .await?
+        .ok_or_else(|| AppError::ArtifactNotFound(id.to_owned(), filename.to_owned()))
+}
+
+pub async fn download(
+    State(state): State<Arc<AppState>>,
+    RequirePermission(_claims, _): RequirePermission<ReadResults>,
+    Path((id, filename)): Path<(String, String)>,
+) -> Result<impl IntoResponse, AppError> {
+    let artifact = find_artifact(&state, &id, &filename).await?;
+    let data = state.artifacts.get(&artifact.sha256).await?;
+
+    Ok((
+        [
+            (header::CONTENT_TYPE, artifact.mime_type),
+            (
+                header::CONTENT_DISPOSITION,
+                content_disposition(&artifact.filename),
+            ),
+            (header::ETAG, format!("\"{}\"", artifact.sha256)),
+        ],
+        data,
+    ))
+}
+
+pub async fn delete(
+    State(state): State<Arc<AppState>>,
+    RequirePermission(claims, _): RequirePermission<DeleteResult>,
+    Path((id, filename)): Path<(String, String)>,
+) -> Result<Json<serde_json::Value>, AppError> {
+    let artifact: Artifact = sqlx::query_as(
+        r#"DELETE FROM artifacts WHERE result_id = $1 AND filename = $2 RETURNING *"#,
+    )
+    .bind(&id)
+    .bind(&filename)
+    .fetch_optional(&state.pool)
+    .await?
+    .ok_or_else(|| AppError::ArtifactNotFound(id.clone(), filename.clone()))?;
+
+    log::info!("Deleted artifact {} of id={}", filename, id);
+    remove_orphaned_blobs(&state, std::slice::from_ref(&artifact.sha256)).await?;
+
+    Ok(Json(json!({
+        "id": id,
+        "deleted": artifact,
+        "user": claims.username,
+    })))
+}
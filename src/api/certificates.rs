@@ -0,0 +1,123 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::{Path, State},
+    Json,
+};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use time::OffsetDateTime;
+
+use crate::{
+    api::admin::resolve_user,
+    error::AppError,
+    models::{
+        auth::RequirePermission,
+        roles::ManageUsers,
+        tokens::{CertificateMapping, Scope},
+    },
+    AppState,
+};
+
+/// All client certificate identities and the users they authenticate.
+pub async fn list(
+    State(state): State<Arc<AppState>>,
+    RequirePermission(_claims, _): RequirePermission<ManageUsers>,
+) -> Result<Json<Value>, AppError> {
+    let mappings: Vec<CertificateMapping> = sqlx::query_as(
+        r#"
+        SELECT id, identity, user_id, scopes, created, last_used
+        FROM client_certificates
+        ORDER BY identity ASC
+        "#,
+    )
+    .fetch_all(&state.pool)
+    .await?;
+    Ok(Json(json!(mappings)))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct NewMapping {
+    /// id or email of the user
+    user: String,
+    /// subject or subject alternative name of the certificate
+    identity: String,
+    scopes: Vec<Scope>,
+}
+
+/// Authenticate client certificates with the subject or subject alternative name `identity` as
+/// `user`, limited to `scopes`.
+pub async fn create(
+    State(state): State<Arc<AppState>>,
+    RequirePermission(claims, _): RequirePermission<ManageUsers>,
+    Json(mapping): Json<NewMapping>,
+) -> Result<Json<Value>, AppError> {
+    let mut errors = Vec::new();
+    if mapping.identity.trim().is_empty() {
+        errors.push("identity must not be empty".to_owned());
+    }
+    if mapping.scopes.is_empty() {
+        errors.push("at least one scope is required".to_owned());
+    }
+    if !errors.is_empty() {
+        return Err(AppError::ValidationError(
+            "client certificate".into(),
+            errors,
+        ));
+    }
+    let user_id = resolve_user(&state.pool, &mapping.user).await?;
+
+    let scopes: Vec<String> = mapping.scopes.iter().map(Scope::to_string).collect();
+    let created: CertificateMapping = sqlx::query_as(
+        r#"
+        INSERT INTO client_certificates (identity, user_id, scopes, created)
+        VALUES ($1, $2, $3, $4)
+        ON CONFLICT DO NOTHING
+        RETURNING id, identity, user_id, scopes, created, last_used
+        "#,
+    )
+    .bind(mapping.identity.trim())
+    .bind(user_id)
+    .bind(&scopes)
+    .bind(OffsetDateTime::now_utc())
+    .fetch_optional(&state.pool)
+    .await?
+    .ok_or_else(|| {
+        AppError::ValidationError(
+            "client certificate".into(),
+            vec![format!("{} is already mapped", mapping.identity.trim())],
+        )
+    })?;
+
+    log::info!(
+        "{} mapped client certificate {} to {} with scopes {:?}",
+        claims.username,
+        created.identity,
+        mapping.user,
+        created.scopes
+    );
+    Ok(Json(json!(created)))
+}
+
+/// Stop authenticating the client certificates of a mapping.
+pub async fn delete(
+    State(state): State<Arc<AppState>>,
+    RequirePermission(claims, _): RequirePermission<ManageUsers>,
+    Path(id): Path<i32>,
+) -> Result<Json<Value>, AppError> {
+    let identity: String =
+        sqlx::query_scalar(r#"DELETE FROM client_certificates WHERE id = $1 RETURNING identity"#)
+            .bind(id)
+            .fetch_optional(&state.pool)
+            .await?
+            .ok_or_else(|| AppError::IdNotFound(id.to_string()))?;
+    log::info!(
+        "{} removed client certificate {}",
+        claims.username,
+        identity
+    );
+    Ok(Json(json!({
+        "deleted": id,
+        "identity": identity,
+    })))
+}
@@ -0,0 +1,176 @@
+use std::{
+    cmp::Ordering,
+    collections::{BTreeSet, HashMap},
+    sync::Arc,
+};
+
+use axum::{
+    extract::{Query, State},
+    Json,
+};
+use flatten_json_object::Flattener;
+use serde::Deserialize;
+use serde_json::{json, Map, Value};
+
+use crate::{
+    error::AppError,
+    models::{
+        auth::RequirePermission, filters::compare_values, results::Results, roles::ReadResults,
+    },
+    parsers::timing::rows_from_json,
+    AppState,
+};
+
+#[derive(Debug, Deserialize)]
+pub struct CompareParams {
+    /// comma separated result ids, the first one is the reference for the deltas
+    ids: String,
+    /// only list the paths whose values are not the same for all results
+    #[serde(default)]
+    differences_only: bool,
+}
+
+/// Timing rows keyed by `operation.ad_len.msg_len`, so that measurements of different
+/// results line up regardless of their order.
+fn timing_by_lengths(timing: &Value) -> Value {
+    let mut by_lengths = json!({});
+    for row in rows_from_json(timing) {
+        let mut measurement = row.extra;
+        measurement.insert("cycles".to_owned(), json!(row.cycles));
+        by_lengths[&row.operation][row.ad_len.to_string()][row.msg_len.to_string()] =
+            Value::Object(measurement);
+    }
+    by_lengths
+}
+
+/// Numbers are compared by value, so `1` and `1.0` are equal.
+fn equal(a: Option<&Value>, b: Option<&Value>) -> bool {
+    match (a, b) {
+        (Some(a), Some(b)) => compare_values(a, b) == Some(Ordering::Equal),
+        (None, None) => true,
+        _ => false,
+    }
+}
+
+/// The leaves of the compared columns of a result, keyed by their path.
+fn leaves(row: &Results, flattener: &Flattener) -> Map<String, Value> {
+    let columns = [
+        ("metadata", row.metadata.clone()),
+        ("timing", timing_by_lengths(&row.timing)),
+        ("synthesis", row.synthesis.clone()),
+    ];
+    let mut leaves = Map::new();
+    for (column, value) in columns {
+        if value.is_null() {
+            continue;
+        }
+        if let Ok(Value::Object(flat)) = flattener.flatten(&value) {
+            for (path, leaf) in flat {
+                leaves.insert(format!("{column}.{path}"), leaf);
+            }
+        }
+    }
+    leaves
+}
+
+/// Compare `metadata`, `timing` and `synthesis` of two or more results path by path.
+///
+/// Numeric values of the other results get their absolute and percentage delta to the
+/// first result.
+pub async fn compare(
+    State(state): State<Arc<AppState>>,
+    RequirePermission(claims, _): RequirePermission<ReadResults>,
+    Query(params): Query<CompareParams>,
+) -> Result<Json<Value>, AppError> {
+    log::info!("compare user:{} params: {:?}", claims.username, params);
+
+    let mut ids: Vec<String> = Vec::new();
+    for id in params
+        .ids
+        .split(',')
+        .map(str::trim)
+        .filter(|id| !id.is_empty())
+    {
+        if !ids.iter().any(|existing| existing == id) {
+            ids.push(id.to_owned());
+        }
+    }
+    if ids.len() < 2 {
+        return Err(AppError::ValidationError(
+            "comparison".into(),
+            vec!["at least two distinct ids are required".to_owned()],
+        ));
+    }
+
+    let rows: Vec<Results> = sqlx::query_as(
+        r#"SELECT id, name, category, metadata, timing, synthesis FROM results WHERE id = ANY($1)"#,
+    )
+    .bind(&ids)
+    .fetch_all(&state.pool)
+    .await?;
+    let mut rows: HashMap<String, Results> =
+        rows.into_iter().map(|row| (row.id.clone(), row)).collect();
+
+    let flattener = Flattener::new();
+    let mut results = Vec::new();
+    let mut columns = Vec::new();
+    for id in &ids {
+        let row = rows
+            .remove(id)
+            .ok_or_else(|| AppError::IdNotFound(id.clone()))?;
+        columns.push(leaves(&row, &flattener));
+        results.push(json!({
+            "id": row.id,
+            "name": row.name,
+            "category": row.category,
+        }));
+    }
+
+    let paths: BTreeSet<&str> = columns
+        .iter()
+        .flat_map(|leaves| leaves.keys().map(String::as_str))
+        .collect();
+
+    let mut fields = Vec::new();
+    for path in paths {
+        let values: Vec<Option<&Value>> = columns.iter().map(|leaves| leaves.get(path)).collect();
+        let same = values.iter().all(|v| equal(*v, values[0]));
+        if params.differences_only && same {
+            continue;
+        }
+
+        let mut by_id = Map::new();
+        let mut deltas = Map::new();
+        let reference = values[0].and_then(Value::as_f64);
+        for (id, value) in ids.iter().zip(&values) {
+            by_id.insert(id.clone(), value.cloned().unwrap_or(Value::Null));
+            if id == &ids[0] {
+                continue;
+            }
+            if let (Some(reference), Some(value)) = (reference, value.and_then(Value::as_f64)) {
+                let absolute = value - reference;
+                let percent = (reference != 0.0).then(|| 100.0 * absolute / reference.abs());
+                deltas.insert(
+                    id.clone(),
+                    json!({ "absolute": absolute, "percent": percent }),
+                );
+            }
+        }
+
+        let mut field = json!({
+            "path": path,
+            "values": by_id,
+            "different": !same,
+        });
+        if !deltas.is_empty() {
+            field["deltas"] = Value::Object(deltas);
+        }
+        fields.push(field);
+    }
+
+    Ok(Json(json!({
+        "reference": ids[0],
+        "results": results,
+        "fields": fields,
+    })))
+}
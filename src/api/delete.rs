@@ -6,8 +6,9 @@ use serde_json::json;
 use sqlx::query_as;
 
 use crate::{
+    api::{artifacts::remove_orphaned_blobs, leaderboards::schedule_rank_update},
     error::AppError,
-    models::{auth::Claims, results::Results},
+    models::{auth::RequirePermission, results::Results, roles::DeleteResult},
     AppState,
 };
 
@@ -18,9 +19,15 @@ pub struct DeleteRequest {
 
 pub async fn delete(
     State(state): State<Arc<AppState>>,
-    claims: Claims,
+    RequirePermission(claims, _): RequirePermission<DeleteResult>,
     Json(request): Json<DeleteRequest>,
 ) -> Result<axum::Json<serde_json::Value>, AppError> {
+    // artifact rows are removed by the foreign key cascade, their blobs are cleaned up below
+    let blobs: Vec<String> =
+        sqlx::query_scalar(r#"SELECT DISTINCT sha256 FROM artifacts WHERE result_id = $1"#)
+            .bind(&request.id)
+            .fetch_all(&state.pool)
+            .await?;
     let record: Option<Results> = query_as(r#"DELETE FROM results WHERE id = $1 RETURNING *"#)
         .bind(&request.id)
         .fetch_optional(&state.pool)
@@ -29,6 +36,8 @@ pub async fn delete(
         Some(deleted) => {
             assert!(request.id == deleted.id);
             log::info!("Deleted record with id={}", request.id);
+            remove_orphaned_blobs(&state, &blobs).await?;
+            schedule_rank_update(&state);
             Ok(axum::Json(json!({
                 "id": request.id,
                 "deleted" : deleted,
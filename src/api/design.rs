@@ -0,0 +1,156 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::{Path, State},
+    Json,
+};
+use serde_json::json;
+use sqlx::{Postgres, Transaction};
+use tracing::info;
+
+use crate::{
+    api::artifacts::ensure_can_submit_to,
+    error::AppError,
+    models::{
+        auth::RequirePermission,
+        design::{Design, DesignRecord},
+        roles::{ReadResults, SubmitResult},
+    },
+    parsers::design::parse_design_toml,
+    AppState,
+};
+
+/// Parse and validate the text of a `design.toml`.
+pub fn parse_design(text: &str) -> Result<Design, AppError> {
+    let design = parse_design_toml(text)
+        .map_err(|errors| AppError::ParseError("design.toml".into(), errors))?;
+    let errors = design.validate();
+    if !errors.is_empty() {
+        return Err(AppError::ValidationError("design.toml".into(), errors));
+    }
+    Ok(design)
+}
+
+/// Store the typed fields and the original text of a design description, and mirror the
+/// parsed description into `metadata.design`.
+pub async fn store_design(
+    transaction: &mut Transaction<'_, Postgres>,
+    result_id: &str,
+    design: &Design,
+    original: &str,
+) -> Result<(), AppError> {
+    let parsed = json!(design);
+    sqlx::query(
+        r#"
+        INSERT INTO designs (
+            result_id,
+            name,
+            language,
+            top,
+            clock_port,
+            clock_period,
+            w,
+            sw,
+            ccw,
+            ccsw,
+            algorithm,
+            sources,
+            parsed,
+            original
+        )
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14)
+        ON CONFLICT (result_id) DO UPDATE
+        SET name         = EXCLUDED.name,
+            language     = EXCLUDED.language,
+            top          = EXCLUDED.top,
+            clock_port   = EXCLUDED.clock_port,
+            clock_period = EXCLUDED.clock_period,
+            w            = EXCLUDED.w,
+            sw           = EXCLUDED.sw,
+            ccw          = EXCLUDED.ccw,
+            ccsw         = EXCLUDED.ccsw,
+            algorithm    = EXCLUDED.algorithm,
+            sources      = EXCLUDED.sources,
+            parsed       = EXCLUDED.parsed,
+            original     = EXCLUDED.original
+        "#,
+    )
+    .bind(result_id)
+    .bind(&design.name)
+    .bind(design.language_name())
+    .bind(&design.rtl.top)
+    .bind(design.clock_port())
+    .bind(design.clock_period())
+    .bind(design.w().map(|w| w as i32))
+    .bind(design.sw().map(|w| w as i32))
+    .bind(design.ccw().map(|w| w as i32))
+    .bind(design.ccsw().map(|w| w as i32))
+    .bind(design.algorithm())
+    .bind(&design.rtl.sources)
+    .bind(&parsed)
+    .bind(original)
+    .execute(&mut *transaction)
+    .await?;
+
+    mirror_design(transaction, result_id).await
+}
+
+/// Copy the stored design description of a result, if any, into `metadata.design`, e.g. after
+/// its `metadata` was replaced.
+pub async fn mirror_design(
+    transaction: &mut Transaction<'_, Postgres>,
+    result_id: &str,
+) -> Result<(), AppError> {
+    sqlx::query(
+        r#"
+        UPDATE results
+        SET metadata = jsonb_set(COALESCE(metadata, '{}'), '{design}', designs.parsed)
+        FROM designs
+        WHERE results.id = $1 AND designs.result_id = results.id
+        "#,
+    )
+    .bind(result_id)
+    .execute(&mut *transaction)
+    .await?;
+    Ok(())
+}
+
+/// Attach a `design.toml` (sent as the request body) to an existing result.
+pub async fn upload(
+    State(state): State<Arc<AppState>>,
+    RequirePermission(claims, _): RequirePermission<SubmitResult>,
+    Path(id): Path<String>,
+    text: String,
+) -> Result<Json<serde_json::Value>, AppError> {
+    ensure_can_submit_to(&state, &claims, &id).await?;
+    let design = parse_design(&text)?;
+
+    let mut transaction = state.pool.begin().await?;
+    store_design(&mut transaction, &id, &design, &text).await?;
+    transaction.commit().await?;
+
+    info!(
+        "{} uploaded design {} for {}",
+        claims.username, design.name, id
+    );
+
+    Ok(Json(json!({
+        "id": id,
+        "design": design,
+        "submitter": claims.username,
+    })))
+}
+
+pub async fn get(
+    State(state): State<Arc<AppState>>,
+    RequirePermission(_claims, _): RequirePermission<ReadResults>,
+    Path(id): Path<String>,
+) -> Result<Json<serde_json::Value>, AppError> {
+    let design: DesignRecord = sqlx::query_as(r#"SELECT * FROM designs WHERE result_id = $1"#)
+        .bind(&id)
+        .fetch_optional(&state.pool)
+        .await?
+        .ok_or_else(|| AppError::IdNotFound(id.clone()))?;
+
+    Ok(Json(json!(design)))
+}
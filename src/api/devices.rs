@@ -0,0 +1,148 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::{Path, State},
+    Json,
+};
+use serde_json::{json, Value};
+use sqlx::PgPool;
+
+use crate::{
+    api::leaderboards::schedule_rank_update,
+    error::AppError,
+    models::{
+        auth::RequirePermission,
+        devices::Device,
+        results::Results,
+        roles::{ManageDevices, ReadResults},
+    },
+    AppState,
+};
+
+pub async fn all_devices(pool: &PgPool) -> Result<Vec<Device>, AppError> {
+    Ok(sqlx::query_as(r#"SELECT * FROM devices ORDER BY part ASC"#)
+        .fetch_all(pool)
+        .await?)
+}
+
+/// The device a result targets: the one of its best synthesis run, or `metadata.device`.
+fn target_device(row: &Results) -> Option<&str> {
+    let best = &row.synthesis["best"];
+    best["device"]
+        .as_str()
+        .filter(|d| !d.is_empty())
+        .or_else(|| best["results"]["device"].as_str())
+        .or_else(|| row.metadata["device"].as_str())
+}
+
+/// Utilization percentages of the best synthesis results of a result on the device it
+/// targets, or `Null` if the device is not in the catalog.
+pub fn utilization(catalog: &[Device], row: &Results) -> Value {
+    target_device(row)
+        .and_then(|device| Device::lookup(catalog, device))
+        .map_or(Value::Null, |device| {
+            device.utilization(&row.synthesis["best"]["results"])
+        })
+}
+
+pub async fn list(
+    State(state): State<Arc<AppState>>,
+    RequirePermission(_claims, _): RequirePermission<ReadResults>,
+) -> Result<Json<Value>, AppError> {
+    Ok(Json(json!(all_devices(&state.pool).await?)))
+}
+
+pub async fn get(
+    State(state): State<Arc<AppState>>,
+    RequirePermission(_claims, _): RequirePermission<ReadResults>,
+    Path(part): Path<String>,
+) -> Result<Json<Value>, AppError> {
+    let device: Device = sqlx::query_as(r#"SELECT * FROM devices WHERE part = $1"#)
+        .bind(&part)
+        .fetch_optional(&state.pool)
+        .await?
+        .ok_or_else(|| AppError::IdNotFound(part.clone()))?;
+    Ok(Json(json!(device)))
+}
+
+async fn upsert(pool: &PgPool, device: &Device) -> Result<Device, AppError> {
+    Ok(sqlx::query_as(
+        r#"
+        INSERT INTO devices (part, vendor, family, speed_grade, lut, ff, slice, dsp, bram)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
+        ON CONFLICT (part) DO UPDATE
+        SET vendor      = EXCLUDED.vendor,
+            family      = EXCLUDED.family,
+            speed_grade = EXCLUDED.speed_grade,
+            lut         = EXCLUDED.lut,
+            ff          = EXCLUDED.ff,
+            slice       = EXCLUDED.slice,
+            dsp         = EXCLUDED.dsp,
+            bram        = EXCLUDED.bram
+        RETURNING *
+        "#,
+    )
+    .bind(device.part.to_lowercase())
+    .bind(&device.vendor)
+    .bind(&device.family)
+    .bind(&device.speed_grade)
+    .bind(device.lut)
+    .bind(device.ff)
+    .bind(device.slice)
+    .bind(device.dsp)
+    .bind(device.bram)
+    .fetch_one(pool)
+    .await?)
+}
+
+pub async fn create(
+    State(state): State<Arc<AppState>>,
+    RequirePermission(claims, _): RequirePermission<ManageDevices>,
+    Json(device): Json<Device>,
+) -> Result<Json<Value>, AppError> {
+    if device.part.is_empty() {
+        return Err(AppError::InvalidQuery);
+    }
+    let device = upsert(&state.pool, &device).await?;
+    log::info!("{} added device {}", claims.username, device.part);
+    schedule_rank_update(&state);
+    Ok(Json(json!(device)))
+}
+
+pub async fn update(
+    State(state): State<Arc<AppState>>,
+    RequirePermission(claims, _): RequirePermission<ManageDevices>,
+    Path(part): Path<String>,
+    Json(mut device): Json<Device>,
+) -> Result<Json<Value>, AppError> {
+    if sqlx::query!(r#"SELECT part FROM devices WHERE part = $1"#, &part)
+        .fetch_optional(&state.pool)
+        .await?
+        .is_none()
+    {
+        return Err(AppError::IdNotFound(part));
+    }
+    device.part = part;
+    let device = upsert(&state.pool, &device).await?;
+    log::info!("{} updated device {}", claims.username, device.part);
+    schedule_rank_update(&state);
+    Ok(Json(json!(device)))
+}
+
+pub async fn delete(
+    State(state): State<Arc<AppState>>,
+    RequirePermission(claims, _): RequirePermission<ManageDevices>,
+    Path(part): Path<String>,
+) -> Result<Json<Value>, AppError> {
+    let deleted: Device = sqlx::query_as(r#"DELETE FROM devices WHERE part = $1 RETURNING *"#)
+        .bind(&part)
+        .fetch_optional(&state.pool)
+        .await?
+        .ok_or_else(|| AppError::IdNotFound(part.clone()))?;
+    log::info!("{} deleted device {}", claims.username, part);
+    schedule_rank_update(&state);
+    Ok(Json(json!({
+        "deleted": deleted,
+        "user": claims.username,
+    })))
+}
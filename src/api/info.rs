@@ -1,5 +1,37 @@
 pub async fn route_info() -> axum::Json<serde_json::Value> {
     axum::Json(serde_json::json!({
-        "routes": ["/register", "/login", "/user_profile", "/submit", "/retrieve"],
+        "routes": [
+            "/register",
+            "/login",
+            "/login/totp",
+            "/refresh",
+            "/logout",
+            "/user_profile",
+            "/user_profile/password",
+            "/user_profile/totp",
+            "/user_profile/totp/verify",
+            "/users",
+            "/users/:user",
+            "/users/:user/disable",
+            "/users/:user/enable",
+            "/users/:user/password",
+            "/users/:user/totp",
+            "/users/:user/roles",
+            "/certificates",
+            "/certificates/:id",
+            "/tokens",
+            "/submit",
+            "/retrieve",
+            "/timing",
+            "/pareto",
+            "/compare",
+            "/leaderboards",
+            "/devices",
+            "/results/:id/artifacts",
+            "/results/:id/design",
+            "/results/:id/timing/fit",
+            "/results/:id/synthesis/reports",
+            "/results/:id/synthesis/runs",
+        ],
     }))
 }
@@ -0,0 +1,329 @@
+use std::{collections::HashMap, sync::Arc, time::Duration};
+
+use axum::{
+    extract::{Path, State},
+    Json,
+};
+use serde_json::{json, Value};
+use sqlx::QueryBuilder;
+use time::OffsetDateTime;
+
+use crate::{
+    api::retrieve::derive_fields,
+    error::AppError,
+    models::{
+        auth::RequirePermission,
+        leaderboards::{Entry, Leaderboard},
+        results::Results,
+        roles::{ManageLeaderboards, ReadResults},
+    },
+    AppState,
+};
+
+/// Time to wait for further changes before the leaderboards are re-ranked.
+const RANK_UPDATE_DELAY: Duration = Duration::from_secs(2);
+
+/// The name and category of every result, by id.
+type ResultNames = HashMap<String, (Option<String>, Option<String>)>;
+/// The rank, previous rank and time of the last change of every result, by id.
+type StoredRanks = HashMap<String, (i32, Option<i32>, OffsetDateTime)>;
+
+async fn find_leaderboard(state: &AppState, name: &str) -> Result<Leaderboard, AppError> {
+    let definition: Value =
+        sqlx::query_scalar(r#"SELECT definition FROM leaderboards WHERE name = $1"#)
+            .bind(name)
+            .fetch_optional(&state.pool)
+            .await?
+            .ok_or_else(|| AppError::IdNotFound(name.to_owned()))?;
+    serde_json::from_value(definition)
+        .map_err(|e| AppError::ValidationError("leaderboard".into(), vec![e.to_string()]))
+}
+
+pub async fn list(
+    State(state): State<Arc<AppState>>,
+    RequirePermission(_claims, _): RequirePermission<ReadResults>,
+) -> Result<Json<Value>, AppError> {
+    let leaderboards: Vec<(String, Value, String)> =
+        sqlx::query_as(r#"SELECT name, definition, creator FROM leaderboards ORDER BY name ASC"#)
+            .fetch_all(&state.pool)
+            .await?;
+    let leaderboards: Vec<Value> = leaderboards
+        .into_iter()
+        .map(|(name, definition, creator)| {
+            json!({
+                "name": name,
+                "definition": definition,
+                "creator": creator,
+            })
+        })
+        .collect();
+    Ok(Json(json!(leaderboards)))
+}
+
+/// Create or replace a leaderboard definition. The rank history of a replaced leaderboard
+/// is reset, as its ranks are no longer comparable.
+pub async fn put(
+    State(state): State<Arc<AppState>>,
+    RequirePermission(claims, _): RequirePermission<ManageLeaderboards>,
+    Path(name): Path<String>,
+    Json(leaderboard): Json<Leaderboard>,
+) -> Result<Json<Value>, AppError> {
+    let errors = leaderboard.validate();
+    if !errors.is_empty() {
+        return Err(AppError::ValidationError("leaderboard".into(), errors));
+    }
+
+    let mut transaction = state.pool.begin().await?;
+    sqlx::query(
+        r#"
+        INSERT INTO leaderboards (name, definition, creator, timestamp)
+        VALUES ($1, $2, $3, $4)
+        ON CONFLICT (name) DO UPDATE
+        SET definition = EXCLUDED.definition,
+            creator    = EXCLUDED.creator,
+            timestamp  = EXCLUDED.timestamp
+        "#,
+    )
+    .bind(&name)
+    .bind(json!(leaderboard))
+    .bind(&claims.username)
+    .bind(OffsetDateTime::now_utc())
+    .execute(&mut transaction)
+    .await?;
+    sqlx::query(r#"DELETE FROM leaderboard_ranks WHERE leaderboard = $1"#)
+        .bind(&name)
+        .execute(&mut transaction)
+        .await?;
+    transaction.commit().await?;
+    schedule_rank_update(&state);
+
+    log::info!("{} saved leaderboard {}", claims.username, name);
+    Ok(Json(json!({
+        "name": name,
+        "definition": leaderboard,
+        "creator": claims.username,
+    })))
+}
+
+pub async fn delete(
+    State(state): State<Arc<AppState>>,
+    RequirePermission(claims, _): RequirePermission<ManageLeaderboards>,
+    Path(name): Path<String>,
+) -> Result<Json<Value>, AppError> {
+    let deleted = sqlx::query(r#"DELETE FROM leaderboards WHERE name = $1"#)
+        .bind(&name)
+        .execute(&state.pool)
+        .await?
+        .rows_affected();
+    if deleted == 0 {
+        return Err(AppError::IdNotFound(name));
+    }
+    log::info!("{} deleted leaderboard {}", claims.username, name);
+    Ok(Json(json!({
+        "deleted": name,
+        "user": claims.username,
+    })))
+}
+
+/// Rank the results in the scope of a leaderboard, also returning the ids of the skipped results
+/// and the names and categories of the ranked ones.
+async fn rank_results(
+    state: &AppState,
+    leaderboard: &Leaderboard,
+) -> Result<(Vec<Entry>, Vec<String>, ResultNames), AppError> {
+    let mut sql = QueryBuilder::new("SELECT * FROM results WHERE TRUE");
+    if let Some(ref category) = leaderboard.category {
+        sql.push(" AND starts_with(category, ")
+            .push_bind(category)
+            .push(")");
+    }
+    sql.push(" ORDER BY id ASC");
+    let mut rows: Vec<Results> = sql.build_query_as().fetch_all(&state.pool).await?;
+
+    let is_referenced = |field_name: &str| {
+        leaderboard
+            .paths()
+            .any(|p| p.split('.').next() == Some(field_name))
+    };
+    derive_fields(
+        state,
+        &mut rows,
+        leaderboard.best_run_rule,
+        is_referenced("utilization"),
+        is_referenced("metrics"),
+    )
+    .await?;
+
+    let names: ResultNames = rows
+        .iter()
+        .map(|row| (row.id.clone(), (row.name.clone(), row.category.clone())))
+        .collect();
+    let rows: Vec<(String, Value)> = rows
+        .into_iter()
+        .map(|row| (row.id.clone(), json!(row)))
+        .collect();
+    let (entries, skipped) = leaderboard
+        .rank(&rows)
+        .map_err(|e| AppError::ValidationError("leaderboard".into(), vec![e]))?;
+    Ok((entries, skipped, names))
+}
+
+/// The stored ranks of a leaderboard.
+async fn stored_ranks<'e, E>(executor: E, name: &str) -> Result<StoredRanks, AppError>
+where
+    E: sqlx::Executor<'e, Database = sqlx::Postgres>,
+{
+    let stored: Vec<(String, i32, Option<i32>, OffsetDateTime)> = sqlx::query_as(
+        r#"SELECT result_id, rank, previous_rank, changed FROM leaderboard_ranks WHERE leaderboard = $1"#,
+    )
+    .bind(name)
+    .fetch_all(executor)
+    .await?;
+    Ok(stored
+        .into_iter()
+        .map(|(id, rank, previous, changed)| (id, (rank, previous, changed)))
+        .collect())
+}
+
+/// Store the current ranks of a leaderboard, keeping the previous rank of every result whose
+/// rank changed. The leaderboard is locked, so concurrent updates are applied one at a time.
+async fn update_leaderboard_ranks(state: &AppState, name: &str) -> Result<(), AppError> {
+    let mut transaction = state.pool.begin().await?;
+    let Some(definition): Option<Value> =
+        sqlx::query_scalar(r#"SELECT definition FROM leaderboards WHERE name = $1 FOR UPDATE"#)
+            .bind(name)
+            .fetch_optional(&mut transaction)
+            .await?
+    else {
+        return Ok(());
+    };
+    let leaderboard: Leaderboard = serde_json::from_value(definition)
+        .map_err(|e| AppError::ValidationError("leaderboard".into(), vec![e.to_string()]))?;
+    let (entries, _, _) = rank_results(state, &leaderboard).await?;
+    let stored = stored_ranks(&mut transaction, name).await?;
+
+    let ranked: Vec<&str> = entries.iter().map(|e| e.result_id.as_str()).collect();
+    sqlx::query(
+        r#"DELETE FROM leaderboard_ranks WHERE leaderboard = $1 AND NOT (result_id = ANY($2))"#,
+    )
+    .bind(name)
+    .bind(&ranked)
+    .execute(&mut transaction)
+    .await?;
+
+    let now = OffsetDateTime::now_utc();
+    let changed: Vec<(&str, i32, Option<i32>)> = entries
+        .iter()
+        .filter_map(|entry| match stored.get(&entry.result_id) {
+            Some(&(rank, _, _)) if rank == entry.rank => None,
+            Some(&(rank, _, _)) => Some((entry.result_id.as_str(), entry.rank, Some(rank))),
+            None => Some((entry.result_id.as_str(), entry.rank, None)),
+        })
+        .collect();
+    if !changed.is_empty() {
+        let mut sql = QueryBuilder::new(
+            "INSERT INTO leaderboard_ranks (leaderboard, result_id, rank, previous_rank, changed) ",
+        );
+        sql.push_values(&changed, |mut b, (result_id, rank, previous_rank)| {
+            b.push_bind(name)
+                .push_bind(*result_id)
+                .push_bind(*rank)
+                .push_bind(*previous_rank)
+                .push_bind(now);
+        });
+        sql.push(
+            r#"
+            ON CONFLICT (leaderboard, result_id) DO UPDATE
+            SET rank          = EXCLUDED.rank,
+                previous_rank = EXCLUDED.previous_rank,
+                changed       = EXCLUDED.changed
+            "#,
+        );
+        sql.build().execute(&mut transaction).await?;
+    }
+    transaction.commit().await?;
+    Ok(())
+}
+
+/// Update the stored ranks of all leaderboards. Failures are logged, the results were changed
+/// anyway.
+async fn update_ranks(state: &AppState) {
+    let names: Vec<String> = match sqlx::query_scalar(r#"SELECT name FROM leaderboards"#)
+        .fetch_all(&state.pool)
+        .await
+    {
+        Ok(names) => names,
+        Err(e) => {
+            log::warn!("could not list the leaderboards to update their ranks: {e}");
+            return;
+        }
+    };
+    for name in names {
+        if let Err(e) = update_leaderboard_ranks(state, &name).await {
+            log::warn!("could not update the ranks of leaderboard {name}: {e}");
+        }
+    }
+}
+
+/// Re-rank the leaderboards in the background after results, synthesis runs, devices or
+/// leaderboards changed.
+pub fn schedule_rank_update(state: &AppState) {
+    state.rank_updates.notify_one();
+}
+
+/// Re-rank all leaderboards whenever an update was scheduled. Updates scheduled while the
+/// ranks are computed are coalesced into a single further update.
+pub async fn rank_updater(state: Arc<AppState>) {
+    loop {
+        state.rank_updates.notified().await;
+        // let a burst of submissions settle first
+        tokio::time::sleep(RANK_UPDATE_DELAY).await;
+        update_ranks(&state).await;
+    }
+}
+
+/// Rank the results in the scope of a leaderboard.
+///
+/// Each entry reports its `previous_rank` and `rank_change` relative to the last different
+/// ranking of the leaderboard, which is stored in the background whenever results are
+/// submitted, renamed or deleted.
+pub async fn get(
+    State(state): State<Arc<AppState>>,
+    RequirePermission(_claims, _): RequirePermission<ReadResults>,
+    Path(name): Path<String>,
+) -> Result<Json<Value>, AppError> {
+    let leaderboard = find_leaderboard(&state, &name).await?;
+    let (mut entries, skipped, names) = rank_results(&state, &leaderboard).await?;
+    let stored = stored_ranks(&state.pool, &name).await?;
+
+    for entry in &mut entries {
+        let (previous_rank, rank_changed) = match stored.get(&entry.result_id) {
+            Some(&(rank, previous, since)) if rank == entry.rank => (previous, Some(since)),
+            // not stored yet, e.g. while the ranks are being updated
+            Some(&(rank, _, _)) => (Some(rank), None),
+            None => (None, None),
+        };
+        entry.previous_rank = previous_rank;
+        entry.rank_change = previous_rank.map(|previous| previous - entry.rank);
+        entry.rank_changed = rank_changed;
+    }
+
+    let entries: Vec<Value> = entries
+        .into_iter()
+        .map(|entry| {
+            let mut value = json!(entry);
+            if let Some((name, category)) = names.get(&entry.result_id) {
+                value["name"] = json!(name);
+                value["category"] = json!(category);
+            }
+            value
+        })
+        .collect();
+
+    Ok(Json(json!({
+        "name": name,
+        "definition": leaderboard,
+        "entries": entries,
+        "skipped": skipped,
+    })))
+}
@@ -1,10 +1,22 @@
 pub mod admin;
+pub mod analysis;
+pub mod artifacts;
+pub mod certificates;
+pub mod compare;
+pub mod design;
+pub mod devices;
 pub mod info;
+pub mod leaderboards;
+pub mod pareto;
 pub mod users;
 pub mod submit;
 pub mod delete;
 pub mod rename;
 pub mod retrieve;
+pub mod synthesis;
+pub mod timing;
+pub mod tokens;
+pub mod totp;
 
 
 pub use delete::delete;
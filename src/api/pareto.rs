@@ -0,0 +1,147 @@
+use std::sync::Arc;
+
+use axum::{extract::State, Json};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+use sqlx::QueryBuilder;
+
+use crate::{
+    api::retrieve::derive_fields,
+    error::AppError,
+    models::{
+        auth::RequirePermission,
+        filters::{value_at, FieldFilter},
+        pareto::{dominance_ranks, Direction},
+        results::Results,
+        roles::ReadResults,
+        synthesis::BestRunRule,
+    },
+    AppState,
+};
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct Objective {
+    /// path of a stored or derived field, e.g. `synthesis.best.results.lut`
+    path: String,
+    direction: Direction,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ParetoQuery {
+    objectives: Vec<Objective>,
+    /// only include results whose category starts with this prefix
+    category: Option<String>,
+    #[serde(default)]
+    filters: Vec<FieldFilter>,
+    /// return all results with their dominance rank instead of only the Pareto front
+    #[serde(default)]
+    ranks: bool,
+    best_run_rule: Option<BestRunRule>,
+}
+
+/// The Pareto-optimal results for two or more objectives.
+///
+/// Results without a numeric value for every objective are listed in `skipped`.
+pub async fn pareto(
+    State(state): State<Arc<AppState>>,
+    RequirePermission(claims, _): RequirePermission<ReadResults>,
+    Json(query): Json<ParetoQuery>,
+) -> Result<Json<Value>, AppError> {
+    log::info!("pareto user:{} query: {:?}", claims.username, query);
+
+    if query.objectives.len() < 2 {
+        return Err(AppError::ValidationError(
+            "Pareto query".into(),
+            vec!["at least two objectives are required".to_owned()],
+        ));
+    }
+
+    let mut sql = QueryBuilder::new("SELECT * FROM results WHERE TRUE");
+    if let Some(ref category) = query.category {
+        sql.push(" AND starts_with(category, ")
+            .push_bind(category)
+            .push(")");
+    }
+    sql.push(" ORDER BY id ASC");
+    let mut rows: Vec<Results> = sql.build_query_as().fetch_all(&state.pool).await?;
+
+    let is_referenced = |field_name: &str| {
+        query
+            .objectives
+            .iter()
+            .map(|o| &o.path)
+            .chain(query.filters.iter().map(|f| &f.path))
+            .any(|p| p.split('.').next() == Some(field_name))
+    };
+    derive_fields(
+        &state,
+        &mut rows,
+        query.best_run_rule,
+        is_referenced("utilization"),
+        is_referenced("metrics"),
+    )
+    .await?;
+
+    let mut candidates = Vec::new();
+    let mut points = Vec::new();
+    let mut skipped = Vec::new();
+    for row in rows {
+        let value = json!(row);
+        if !query.filters.iter().all(|f| f.matches(&value)) {
+            continue;
+        }
+        let point: Option<Vec<f64>> = query
+            .objectives
+            .iter()
+            .map(|o| {
+                value_at(&value, &o.path)
+                    .and_then(|v| v.as_f64())
+                    .filter(|v| v.is_finite())
+            })
+            .collect();
+        match point {
+            Some(point) => {
+                candidates.push(row);
+                points.push(point);
+            }
+            None => skipped.push(row.id),
+        }
+    }
+
+    let directions: Vec<Direction> = query.objectives.iter().map(|o| o.direction).collect();
+    let ranks = dominance_ranks(&points, &directions);
+
+    let mut ranked: Vec<(usize, &Vec<f64>, Results)> = ranks
+        .into_iter()
+        .zip(&points)
+        .zip(candidates)
+        .map(|((rank, point), row)| (rank, point, row))
+        .filter(|(rank, _, _)| query.ranks || *rank == 1)
+        .collect();
+    ranked.sort_by(|a, b| a.0.cmp(&b.0).then(a.1[0].total_cmp(&b.1[0])));
+
+    let results: Vec<Value> = ranked
+        .into_iter()
+        .map(|(rank, point, row)| {
+            let values: Map<String, Value> = query
+                .objectives
+                .iter()
+                .zip(point)
+                .map(|(o, v)| (o.path.clone(), json!(v)))
+                .collect();
+            json!({
+                "id": row.id,
+                "name": row.name,
+                "category": row.category,
+                "values": values,
+                "rank": rank,
+            })
+        })
+        .collect();
+
+    Ok(Json(json!({
+        "objectives": query.objectives,
+        "results": results,
+        "skipped": skipped,
+    })))
+}
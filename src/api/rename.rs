@@ -4,7 +4,12 @@ use axum::{extract::State, Json};
 use serde::{Deserialize, Serialize};
 use serde_json::json;
 
-use crate::{error::AppError, models::auth::Claims, AppState};
+use crate::{
+    api::leaderboards::schedule_rank_update,
+    error::AppError,
+    models::{auth::RequirePermission, roles::RenameResult},
+    AppState,
+};
 
 #[derive(Serialize, Deserialize)]
 pub struct RenameRequest {
@@ -14,7 +19,7 @@ pub struct RenameRequest {
 
 pub async fn rename(
     State(state): State<Arc<AppState>>,
-    claims: Claims,
+    RequirePermission(claims, _): RequirePermission<RenameResult>,
     Json(request): Json<RenameRequest>,
 ) -> Result<axum::Json<serde_json::Value>, AppError> {
     let mut transaction = state.pool.begin().await?;
@@ -47,6 +52,7 @@ pub async fn rename(
     .await?;
 
     transaction.commit().await?;
+    schedule_rank_update(&state);
 
     Ok(axum::Json(json!({
         "success": true,
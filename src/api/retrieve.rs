@@ -1,4 +1,5 @@
 use std::{
+    cmp::Ordering,
     collections::{HashMap, HashSet},
     sync::Arc,
 };
@@ -9,10 +10,20 @@ use indexmap::IndexSet;
 use json_dotpath::DotPaths;
 use serde::Deserialize;
 use serde_json::json;
+use sqlx::QueryBuilder;
 
 use crate::{
+    api::{devices, synthesis::reselect_best, timing::TimingFilter},
     error::AppError,
-    models::{auth::Claims, results::Results},
+    models::{
+        auth::RequirePermission,
+        filters::{FieldFilter, SortKey},
+        metrics::{self, Implementation},
+        results::Results,
+        roles::ReadResults,
+        synthesis::{BestRunRule, SynthesisRun},
+    },
+    parsers::timing::rows_from_json,
     AppState,
 };
 
@@ -23,13 +34,30 @@ pub struct GetResultsParams {
     fields: Option<Vec<String>>,
     #[serde(default)]
     flatten: bool,
+    /// only include results whose category starts with this prefix, e.g. `SW:`
+    category: Option<String>,
+    /// only include results with a matching timing measurement
+    timing_filter: Option<TimingFilter>,
+    /// choose `synthesis.best` among the synthesis runs with this rule instead of the server's
+    best_run_rule: Option<BestRunRule>,
+    /// conditions on stored or derived fields, all of which must hold
+    #[serde(default)]
+    filters: Vec<FieldFilter>,
+    /// sort by these stored or derived fields instead of `id`
+    #[serde(default)]
+    sort: Vec<SortKey>,
 }
 
-fn do_filter(field: &mut serde_json::Value, field_name: &str, fields: &Vec<String>) {
+fn do_filter(field: &mut serde_json::Value, field_name: &str, fields: &[String]) {
     if fields.contains(&field_name.to_owned()) {
         return;
     }
     let paths = get_sub_paths(field_name, fields);
+    if paths.is_empty() {
+        // the column was only needed to compute derived fields
+        *field = serde_json::Value::Null;
+        return;
+    }
     let mut key_values: HashMap<_, serde_json::Value> = HashMap::new();
     for k in paths {
         if let Some(v) = field.dot_get(k).unwrap_or_default() {
@@ -39,7 +67,7 @@ fn do_filter(field: &mut serde_json::Value, field_name: &str, fields: &Vec<Strin
     *field = json!(key_values);
 }
 
-fn get_sub_paths<'a>(field_name: &'a str, fields: &'a Vec<String>) -> Vec<&'a str> {
+fn get_sub_paths<'a>(field_name: &'a str, fields: &'a [String]) -> Vec<&'a str> {
     fields
         .iter()
         .filter_map(|s| {
@@ -49,9 +77,63 @@ fn get_sub_paths<'a>(field_name: &'a str, fields: &'a Vec<String>) -> Vec<&'a st
         .collect()
 }
 
+/// Reselect `synthesis.best` of the rows that were stored with another rule than
+/// `best_run_rule`, or else the server's rule, and compute the derived `utilization` and
+/// `metrics` fields of `rows`.
+pub async fn derive_fields(
+    state: &AppState,
+    rows: &mut [Results],
+    best_run_rule: Option<BestRunRule>,
+    with_utilization: bool,
+    with_metrics: bool,
+) -> Result<(), AppError> {
+    let rule = best_run_rule.unwrap_or(state.best_run_rule);
+    // the rule of stored rows is outdated after `BEST_RUN_RULE` was changed
+    let outdated = |row: &Results| {
+        row.synthesis.is_object() && row.synthesis.pointer("/best/rule") != Some(&json!(rule))
+    };
+    let ids: Vec<String> = rows
+        .iter()
+        .filter(|row| outdated(row))
+        .map(|row| row.id.clone())
+        .collect();
+    if !ids.is_empty() {
+        let runs: Vec<SynthesisRun> =
+            sqlx::query_as(r#"SELECT * FROM synthesis_runs WHERE result_id = ANY($1)"#)
+                .bind(&ids)
+                .fetch_all(&state.pool)
+                .await?;
+        let mut runs_by_id: HashMap<String, Vec<SynthesisRun>> = HashMap::new();
+        for run in runs {
+            runs_by_id.entry(run.result_id.clone()).or_default().push(run);
+        }
+        for row in rows.iter_mut().filter(|row| outdated(row)) {
+            if let Some(synthesis) = row.synthesis.as_object_mut() {
+                let runs = runs_by_id.get(&row.id).map_or(&[][..], Vec::as_slice);
+                reselect_best(synthesis, runs, rule);
+            }
+        }
+    }
+
+    if with_utilization {
+        let catalog = devices::all_devices(&state.pool).await?;
+        for row in rows.iter_mut() {
+            row.utilization = devices::utilization(&catalog, row);
+        }
+    }
+
+    if with_metrics {
+        for row in rows.iter_mut() {
+            let implementation = Implementation::from_results(&row.synthesis, &row.asic);
+            row.metrics = metrics::compute(&rows_from_json(&row.timing), &implementation);
+        }
+    }
+    Ok(())
+}
+
 pub async fn retrieve(
     State(state): State<Arc<AppState>>,
-    claims: Claims,
+    RequirePermission(claims, _): RequirePermission<ReadResults>,
     Json(query): Json<GetResultsParams>,
 ) -> Result<axum::Json<serde_json::Value>, AppError> {
     log::info!("get_results user:{} query: {:?}", claims.username, query);
@@ -61,17 +143,46 @@ pub async fn retrieve(
     //     format!("WHERE {}", s)
     // });
 
-    let filter = "";
+    let json_fields = HashSet::from(["metadata", "timing", "synthesis", "asic", "software"]);
 
-    let json_fields = HashSet::from(["metadata", "timing", "synthesis"]);
+    let condition_paths: Vec<&str> = query
+        .filters
+        .iter()
+        .map(|f| f.path.as_str())
+        .chain(query.sort.iter().map(|s| s.path.as_str()))
+        .collect();
+    // derived fields are only computed when they are selected or filtered or sorted by
+    let is_referenced = |field_name: &str| {
+        query
+            .fields
+            .iter()
+            .flatten()
+            .map(String::as_str)
+            .chain(condition_paths.iter().copied())
+            .any(|p| p.split('.').next() == Some(field_name))
+    };
+    let with_utilization = is_referenced("utilization");
+    let with_metrics = is_referenced("metrics");
+    // filters and sorting may involve derived fields, so they are applied after the query
+    let in_memory = !condition_paths.is_empty();
 
     let field_sel = if let Some(ref fields) = query.fields {
         let mut field_names: IndexSet<_> = IndexSet::new();
         field_names.insert("id");
-        field_names.extend(fields.iter().filter_map(|s| {
-            match s.as_str() {
+        if with_utilization {
+            field_names.extend(["metadata", "synthesis"]);
+        }
+        if with_metrics {
+            field_names.extend(["timing", "synthesis", "asic"]);
+        }
+        let paths = fields
+            .iter()
+            .map(String::as_str)
+            .chain(condition_paths.iter().copied());
+        field_names.extend(paths.filter_map(|s| {
+            match s {
                 "id" => None,
-                "name" | "category" => Some(s.as_str()),
+                "name" | "category" => Some(s),
                 _ => s
                     .split(".")
                     .next()
@@ -83,26 +194,73 @@ pub async fn retrieve(
         "*".to_owned()
     };
 
-    let sql = format!(
-        r#"SELECT {} from results
-        {filter}
-        ORDER BY id ASC
-        OFFSET {}
-        LIMIT {};"#,
-        field_sel,
-        query.offset.unwrap_or(0),
-        query.limit.map_or("ALL".to_owned(), |i| i.to_string()),
-    );
+    let mut sql = QueryBuilder::new(format!("SELECT {} from results", field_sel));
+    sql.push(" WHERE TRUE");
+    if let Some(ref category) = query.category {
+        sql.push(" AND starts_with(category, ")
+            .push_bind(category)
+            .push(")");
+    }
+    if let Some(ref timing_filter) = query.timing_filter {
+        sql.push(" AND");
+        timing_filter.push_exists(&mut sql);
+    }
+    sql.push(" ORDER BY id ASC");
+    if !in_memory {
+        sql.push(" OFFSET ")
+            .push_bind(query.offset.unwrap_or(0))
+            .push(" LIMIT ")
+            .push_bind(query.limit);
+    }
 
-    log::info!("sql={}", sql);
+    log::info!("sql={}", sql.sql());
 
-    let mut rows: Vec<Results> = sqlx::query_as(sql.as_str()).fetch_all(&state.pool).await?;
+    let mut rows: Vec<Results> = sql.build_query_as().fetch_all(&state.pool).await?;
+
+    derive_fields(
+        &state,
+        &mut rows,
+        query.best_run_rule,
+        with_utilization,
+        with_metrics,
+    )
+    .await?;
+
+    if in_memory {
+        let mut values: Vec<(serde_json::Value, Results)> = rows
+            .into_iter()
+            .map(|row| (json!(row), row))
+            .filter(|(value, _)| query.filters.iter().all(|f| f.matches(value)))
+            .collect();
+        values.sort_by(|(a, _), (b, _)| {
+            query
+                .sort
+                .iter()
+                .map(|key| key.compare(a, b))
+                .find(|ordering| ordering.is_ne())
+                .unwrap_or(Ordering::Equal)
+        });
+        rows = values
+            .into_iter()
+            .map(|(_, row)| row)
+            .skip(query.offset.unwrap_or(0).max(0) as usize)
+            .take(
+                query
+                    .limit
+                    .map_or(usize::MAX, |limit| limit.max(0) as usize),
+            )
+            .collect();
+    }
 
     if let Some(ref fields) = query.fields {
         for row in &mut rows {
+            do_filter(&mut row.utilization, "utilization", fields);
+            do_filter(&mut row.metrics, "metrics", fields);
             do_filter(&mut row.timing, "timing", fields);
             do_filter(&mut row.synthesis, "synthesis", fields);
             do_filter(&mut row.metadata, "metadata", fields);
+            do_filter(&mut row.asic, "asic", fields);
+            do_filter(&mut row.software, "software", fields);
         }
     };
 
@@ -113,6 +271,9 @@ pub async fn retrieve(
             row.metadata = flattener.flatten(&row.metadata).unwrap_or_default();
             row.timing = flattener.flatten(&row.timing).unwrap_or_default();
             row.synthesis = flattener.flatten(&row.synthesis).unwrap_or_default();
+            row.asic = flattener.flatten(&row.asic).unwrap_or_default();
+            row.software = flattener.flatten(&row.software).unwrap_or_default();
+            row.metrics = flattener.flatten(&row.metrics).unwrap_or_default();
         }
     }
 
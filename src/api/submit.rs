@@ -1,38 +1,234 @@
 use std::sync::Arc;
 
-use axum::{extract::State, Json};
+use axum::{
+    async_trait,
+    body::Body,
+    extract::{FromRequest, Multipart, State},
+    http::{header::CONTENT_TYPE, Request},
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde::Deserialize;
 use serde_json::json;
 use sqlx::types::JsonValue;
 use time::OffsetDateTime;
 use tracing::info;
 
 use crate::{
+    api::{
+        design::{mirror_design, parse_design, store_design},
+        leaderboards::schedule_rank_update,
+        timing::reindex,
+    },
     error::AppError,
-    models::{auth::Claims, results::Results},
+    models::{
+        asic::AsicResults, auth::RequirePermission, design::Design, results::Results,
+        roles::SubmitResult, software::SoftwareResults,
+    },
+    parsers::timing::parse_timing_csv,
     AppState,
 };
 
+#[derive(Debug, Default, Deserialize)]
+pub struct SubmitParams {
+    id: Option<String>,
+    name: Option<String>,
+    category: Option<String>,
+}
+
+impl SubmitParams {
+    fn into_results(self) -> Result<Results, AppError> {
+        Ok(Results {
+            id: self
+                .id
+                .ok_or_else(|| AppError::InvalidUpload("missing id".into()))?,
+            name: self.name,
+            category: self.category,
+            metadata: JsonValue::Null,
+            timing: JsonValue::Null,
+            synthesis: JsonValue::Null,
+            asic: JsonValue::Null,
+            software: JsonValue::Null,
+            utilization: JsonValue::Null,
+            metrics: JsonValue::Null,
+        })
+    }
+}
+
+/// Validate the ASIC results of a submission and compute their gate equivalents.
+fn normalize_asic(asic: JsonValue) -> Result<JsonValue, AppError> {
+    if asic.is_null() {
+        return Ok(asic);
+    }
+    let mut asic: AsicResults = serde_json::from_value(asic)
+        .map_err(|e| AppError::ValidationError("ASIC results".into(), vec![e.to_string()]))?;
+    let errors = asic.validate();
+    if !errors.is_empty() {
+        return Err(AppError::ValidationError("ASIC results".into(), errors));
+    }
+    asic.compute_gate_equivalents();
+    Ok(json!(asic))
+}
+
+/// Validate the software benchmark results of a submission and compute their cycles per byte.
+fn normalize_software(software: JsonValue) -> Result<JsonValue, AppError> {
+    if software.is_null() {
+        return Ok(software);
+    }
+    let mut software: SoftwareResults = serde_json::from_value(software)
+        .map_err(|e| AppError::ValidationError("software results".into(), vec![e.to_string()]))?;
+    let errors = software.validate();
+    if !errors.is_empty() {
+        return Err(AppError::ValidationError("software results".into(), errors));
+    }
+    software.compute_cycles_per_byte();
+    Ok(json!(software))
+}
+
+fn parse_timing(csv: &str) -> Result<JsonValue, AppError> {
+    let rows = parse_timing_csv(csv)
+        .map_err(|errors| AppError::ParseError("timing CSV".into(), errors))?;
+    Ok(json!(rows))
+}
+
+/// Results submitted either as JSON, as a raw timing CSV (`text/csv`, with `id`, `name` and
+/// `category` in the query string) or as `multipart/form-data` with a `results` JSON part,
+/// a `timing` CSV part and/or a `design` part holding the `design.toml`.
+pub struct Submission {
+    pub results: Results,
+    /// the parsed design description and its original text
+    pub design: Option<(Design, String)>,
+}
+
+#[async_trait]
+impl<S> FromRequest<S, Body> for Submission
+where
+    S: Send + Sync,
+{
+    type Rejection = Response;
+
+    async fn from_request(req: Request<Body>, state: &S) -> Result<Self, Self::Rejection> {
+        let content_type = req
+            .headers()
+            .get(CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .unwrap_or_default()
+            .to_owned();
+
+        if content_type.starts_with("multipart/form-data") {
+            let mut multipart = Multipart::from_request(req, state)
+                .await
+                .map_err(IntoResponse::into_response)?;
+            Self::from_multipart(&mut multipart)
+                .await
+                .map_err(IntoResponse::into_response)
+        } else if content_type.starts_with("text/csv") {
+            let params: SubmitParams = serde_qs::from_str(req.uri().query().unwrap_or_default())
+                .map_err(|e| AppError::InvalidUpload(e.to_string()).into_response())?;
+            let csv = String::from_request(req, state)
+                .await
+                .map_err(IntoResponse::into_response)?;
+            let mut results = params.into_results().map_err(IntoResponse::into_response)?;
+            results.timing = parse_timing(&csv).map_err(IntoResponse::into_response)?;
+            Ok(Self {
+                results,
+                design: None,
+            })
+        } else {
+            let Json(results) = Json::<Results>::from_request(req, state)
+                .await
+                .map_err(IntoResponse::into_response)?;
+            Ok(Self {
+                results,
+                design: None,
+            })
+        }
+    }
+}
+
+impl Submission {
+    async fn from_multipart(multipart: &mut Multipart) -> Result<Self, AppError> {
+        let mut params = SubmitParams::default();
+        let mut results: Option<Results> = None;
+        let mut timing = None;
+        let mut design = None;
+
+        while let Some(field) = multipart
+            .next_field()
+            .await
+            .map_err(|e| AppError::InvalidUpload(e.to_string()))?
+        {
+            let name = field.name().unwrap_or_default().to_owned();
+            let text = field
+                .text()
+                .await
+                .map_err(|e| AppError::InvalidUpload(e.to_string()))?;
+            match name.as_str() {
+                "results" => {
+                    results = Some(
+                        serde_json::from_str(&text)
+                            .map_err(|e| AppError::InvalidUpload(format!("results: {e}")))?,
+                    )
+                }
+                "timing" => timing = Some(parse_timing(&text)?),
+                "design" => design = Some((parse_design(&text)?, text)),
+                "id" => params.id = Some(text),
+                "name" => params.name = Some(text),
+                "category" => params.category = Some(text),
+                _ => {
+                    return Err(AppError::InvalidUpload(format!(
+                        "unexpected field {name:?}"
+                    )))
+                }
+            }
+        }
+
+        let mut results = match results {
+            Some(mut results) => {
+                results.id = params.id.unwrap_or(results.id);
+                results.name = params.name.or(results.name);
+                results.category = params.category.or(results.category);
+                results
+            }
+            None => params.into_results()?,
+        };
+        if let Some(timing) = timing {
+            results.timing = timing;
+        }
+        Ok(Self { results, design })
+    }
+}
+
 pub async fn submit(
     State(state): State<Arc<AppState>>,
-    claims: Claims,
-    Json(results): Json<Results>,
+    RequirePermission(claims, _): RequirePermission<SubmitResult>,
+    Submission {
+        mut results,
+        design,
+    }: Submission,
 ) -> Result<axum::Json<serde_json::Value>, AppError> {
     info!("{} is submitting {}", claims.username, results.id);
+    results.asic = normalize_asic(results.asic)?;
+    results.software = normalize_software(results.software)?;
+    // the measurements are only re-indexed when new timing or software results are submitted
+    let reindex_timing = !results.timing.is_null() || !results.software.is_null();
+    // new metadata drops the mirror of a design stored earlier
+    let replaces_metadata = !results.metadata.is_null();
     let mut transaction = state.pool.begin().await?;
-    let existing = sqlx::query!(
-        r#"SELECT * from results WHERE id = $1;"#,
-        results.id,
-    )
-    .fetch_optional(&mut transaction)
-    .await?;
+    let existing = sqlx::query!(r#"SELECT * from results WHERE id = $1;"#, results.id,)
+        .fetch_optional(&mut transaction)
+        .await?;
 
     let sql_query = if let Some(record) = existing {
         info!("ID: {} already exists, updating", record.id);
 
+        // the token has to be allowed to overwrite the result as well as to move it
+        claims.require_category(record.category.as_deref())?;
         let category = match results.category {
             None => record.category,
             _ => results.category,
         };
+        claims.require_category(category.as_deref())?;
 
         let metadata = match results.metadata {
             JsonValue::Null => record.metadata,
@@ -46,6 +242,14 @@ pub async fn submit(
             JsonValue::Null => record.synthesis,
             _ => Some(results.synthesis),
         };
+        let asic = match results.asic {
+            JsonValue::Null => record.asic,
+            _ => Some(results.asic),
+        };
+        let software = match results.software {
+            JsonValue::Null => record.software,
+            _ => Some(results.software),
+        };
         sqlx::query!(
             r#"
             UPDATE results
@@ -54,7 +258,9 @@ pub async fn submit(
                 category  = $4,
                 metadata  = $5,
                 timing    = $6,
-                synthesis = $7
+                synthesis = $7,
+                asic      = $8,
+                software  = $9
             WHERE id = $1;
             "#,
             results.id,
@@ -63,9 +269,12 @@ pub async fn submit(
             category,
             metadata,
             timing,
-            synthesis
+            synthesis,
+            asic,
+            software
         )
     } else {
+        claims.require_category(results.category.as_deref())?;
         sqlx::query!(
             r#"
                 INSERT INTO results (
@@ -75,9 +284,11 @@ pub async fn submit(
                     category,
                     metadata,
                     timing,
-                    synthesis
+                    synthesis,
+                    asic,
+                    software
                 )
-                VALUES ($1, $2, $3, $4, $5, $6, $7)
+                VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
                 "#,
             results.id,
             results.name,
@@ -85,13 +296,25 @@ pub async fn submit(
             results.category,
             results.metadata,
             results.timing,
-            results.synthesis
+            results.synthesis,
+            results.asic,
+            results.software
         )
     };
 
     sql_query.execute(&mut transaction).await?;
 
+    if reindex_timing {
+        reindex(&mut transaction, &results.id).await?;
+    }
+    if let Some((ref design, ref original)) = design {
+        store_design(&mut transaction, &results.id, design, original).await?;
+    } else if replaces_metadata {
+        mirror_design(&mut transaction, &results.id).await?;
+    }
+
     transaction.commit().await?;
+    schedule_rank_update(&state);
 
     Ok(axum::Json(json!({
         "id": results.id,
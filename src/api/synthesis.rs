@@ -0,0 +1,427 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::{Multipart, Path, Query, State},
+    Json,
+};
+use serde::Deserialize;
+use serde_json::{json, Map, Value};
+use sqlx::{Postgres, Transaction};
+use time::OffsetDateTime;
+use tracing::info;
+
+use crate::{
+    api::{
+        artifacts::{
+            commit_artifacts, ensure_can_submit_to, ensure_result_exists, sanitize_filename,
+            store_artifact,
+        },
+        leaderboards::schedule_rank_update,
+    },
+    error::AppError,
+    models::{
+        auth::RequirePermission,
+        roles::{DeleteResult, ReadResults, SubmitResult},
+        synthesis::{BestRunRule, NewSynthesisRun, SynthesisRun, Tool},
+    },
+    parsers::parse_synthesis_reports,
+    AppState,
+};
+
+#[derive(Debug, Deserialize)]
+pub struct ReportParams {
+    tool: Option<Tool>,
+    device: Option<String>,
+    strategy: Option<String>,
+    target_clock_period: Option<f64>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+pub enum NewSynthesisRuns {
+    One(Box<NewSynthesisRun>),
+    Many(Vec<NewSynthesisRun>),
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RunsParams {
+    rule: Option<BestRunRule>,
+}
+
+/// Insert a run of a result, replacing a previous run with the same device, tool, tool
+/// version, strategy and target clock.
+pub async fn insert_run(
+    transaction: &mut Transaction<'_, Postgres>,
+    result_id: &str,
+    run: NewSynthesisRun,
+    submitter: &str,
+) -> Result<SynthesisRun, AppError> {
+    let tool = run.tool.or(run.results.tool);
+    let run: SynthesisRun = sqlx::query_as(
+        r#"
+        INSERT INTO synthesis_runs (
+            result_id,
+            device,
+            tool,
+            tool_version,
+            strategy,
+            target_clock_period,
+            settings,
+            results,
+            submitter,
+            timestamp
+        )
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)
+        ON CONFLICT (result_id, device, tool, tool_version, strategy, COALESCE(target_clock_period, 0))
+        DO UPDATE
+        SET settings  = EXCLUDED.settings,
+            results   = EXCLUDED.results,
+            submitter = EXCLUDED.submitter,
+            timestamp = EXCLUDED.timestamp
+        RETURNING *
+        "#,
+    )
+    .bind(result_id)
+    .bind(run.device.or(run.results.device.clone()).unwrap_or_default())
+    .bind(tool.map(|t| t.as_str()).unwrap_or_default())
+    .bind(
+        run.tool_version
+            .or(run.results.tool_version.clone())
+            .unwrap_or_default(),
+    )
+    .bind(run.strategy.unwrap_or_default())
+    .bind(run.target_clock_period.or(run.results.clock_period))
+    .bind(run.settings)
+    .bind(sqlx::types::Json(run.results))
+    .bind(submitter)
+    .bind(OffsetDateTime::now_utc())
+    .fetch_one(&mut *transaction)
+    .await?;
+    Ok(run)
+}
+
+async fn find_runs(
+    transaction: &mut Transaction<'_, Postgres>,
+    result_id: &str,
+) -> Result<Vec<SynthesisRun>, AppError> {
+    Ok(
+        sqlx::query_as(r#"SELECT * FROM synthesis_runs WHERE result_id = $1 ORDER BY id ASC"#)
+            .bind(result_id)
+            .fetch_all(&mut *transaction)
+            .await?,
+    )
+}
+
+/// The `synthesis.best` object of a result whose best run is `run`.
+pub fn best_run_json(run: &SynthesisRun, rule: BestRunRule) -> serde_json::Value {
+    json!({
+        "run_id": run.id,
+        "rule": rule,
+        "device": run.device,
+        "tool": run.tool,
+        "tool_version": run.tool_version,
+        "strategy": run.strategy,
+        "target_clock_period": run.target_clock_period,
+        "settings": run.settings,
+        "results": run.results,
+    })
+}
+
+/// Replace `synthesis.best` with the best of `runs` according to `rule`.
+///
+/// A `best` that no run produced, e.g. one submitted with the result, is only replaced by a run,
+/// never removed.
+pub fn reselect_best(synthesis: &mut Map<String, Value>, runs: &[SynthesisRun], rule: BestRunRule) {
+    match rule.select(runs) {
+        Some(best) => {
+            synthesis.insert("best".to_owned(), best_run_json(best, rule));
+        }
+        None => {
+            let by_run = synthesis.get("best").and_then(|best| best.get("run_id"));
+            if by_run.is_some() {
+                synthesis.remove("best");
+            }
+        }
+    }
+}
+
+/// Recompute `synthesis.best` of a result from its runs.
+///
+/// A `best` chosen among the runs is removed when no run has the metrics the rule needs, e.g.
+/// after the last run was deleted.
+pub async fn update_best(
+    transaction: &mut Transaction<'_, Postgres>,
+    result_id: &str,
+    rule: BestRunRule,
+) -> Result<Option<i64>, AppError> {
+    let runs = find_runs(transaction, result_id).await?;
+    let Some(best) = rule.select(&runs) else {
+        sqlx::query(
+            r#"UPDATE results SET synthesis = synthesis - 'best' WHERE id = $1 AND synthesis->'best' ? 'run_id'"#,
+        )
+        .bind(result_id)
+        .execute(&mut *transaction)
+        .await?;
+        return Ok(None);
+    };
+    sqlx::query(
+        r#"UPDATE results SET synthesis = jsonb_set(COALESCE(synthesis, '{}'), '{best}', $2) WHERE id = $1"#,
+    )
+    .bind(result_id)
+    .bind(best_run_json(best, rule))
+    .execute(&mut *transaction)
+    .await?;
+    Ok(Some(best.id))
+}
+
+pub async fn submit_runs(
+    State(state): State<Arc<AppState>>,
+    RequirePermission(claims, _): RequirePermission<SubmitResult>,
+    Path(id): Path<String>,
+    Json(runs): Json<NewSynthesisRuns>,
+) -> Result<Json<serde_json::Value>, AppError> {
+    ensure_can_submit_to(&state, &claims, &id).await?;
+    let runs = match runs {
+        NewSynthesisRuns::One(run) => vec![*run],
+        NewSynthesisRuns::Many(runs) => runs,
+    };
+
+    let mut transaction = state.pool.begin().await?;
+    let mut inserted = Vec::with_capacity(runs.len());
+    for run in runs {
+        inserted.push(
+            insert_run(&mut transaction, &id, run, &claims.username)
+                .await?
+                .id,
+        );
+    }
+    let best = update_best(&mut transaction, &id, state.best_run_rule).await?;
+    transaction.commit().await?;
+    schedule_rank_update(&state);
+
+    info!(
+        "{} submitted {} synthesis runs for {}",
+        claims.username,
+        inserted.len(),
+        id
+    );
+
+    Ok(Json(json!({
+        "id": id,
+        "runs": inserted,
+        "best": best,
+        "submitter": claims.username,
+    })))
+}
+
+pub async fn list_runs(
+    State(state): State<Arc<AppState>>,
+    RequirePermission(_claims, _): RequirePermission<ReadResults>,
+    Path(id): Path<String>,
+    Query(params): Query<RunsParams>,
+) -> Result<Json<serde_json::Value>, AppError> {
+    ensure_result_exists(&state, &id).await?;
+    let rule = params.rule.unwrap_or(state.best_run_rule);
+
+    let mut transaction = state.pool.begin().await?;
+    let runs = find_runs(&mut transaction, &id).await?;
+    transaction.commit().await?;
+
+    Ok(Json(json!({
+        "id": id,
+        "rule": rule,
+        "best": rule.select(&runs).map(|run| run.id),
+        "runs": runs,
+    })))
+}
+
+pub async fn delete_run(
+    State(state): State<Arc<AppState>>,
+    RequirePermission(claims, _): RequirePermission<DeleteResult>,
+    Path((id, run_id)): Path<(String, i64)>,
+) -> Result<Json<serde_json::Value>, AppError> {
+    let mut transaction = state.pool.begin().await?;
+    let deleted: Option<SynthesisRun> = sqlx::query_as(
+        r#"DELETE FROM synthesis_runs WHERE result_id = $1 AND id = $2 RETURNING *"#,
+    )
+    .bind(&id)
+    .bind(run_id)
+    .fetch_optional(&mut transaction)
+    .await?;
+    let Some(deleted) = deleted else {
+        return Err(AppError::IdNotFound(format!(
+            "{id}/synthesis/runs/{run_id}"
+        )));
+    };
+    let best = update_best(&mut transaction, &id, state.best_run_rule).await?;
+    transaction.commit().await?;
+    schedule_rank_update(&state);
+
+    log::info!("Deleted synthesis run {} of id={}", run_id, id);
+
+    Ok(Json(json!({
+        "id": id,
+        "deleted": deleted,
+        "best": best,
+        "user": claims.username,
+    })))
+}
+
+/// Parse raw reports, sent as the parts of a multipart request, into the canonical results of
+/// a new synthesis run. The reports are also kept as artifacts of the result.
+///
+/// The expected parts depend on the `tool` query parameter (Vivado by default):
+/// - `vivado`: `utilization`, `hierarchical_utilization` and/or `timing_summary`
+/// - `yosys`/`nextpnr`: `stat` (Yosys `stat -json`) and/or `nextpnr` (report JSON or log)
+/// - `quartus`: `fit_summary` (`.fit.summary`) and/or `sta` (`.sta.rpt`)
+pub async fn upload_reports(
+    State(state): State<Arc<AppState>>,
+    RequirePermission(claims, _): RequirePermission<SubmitResult>,
+    Path(id): Path<String>,
+    Query(params): Query<ReportParams>,
+    mut multipart: Multipart,
+) -> Result<Json<serde_json::Value>, AppError> {
+    ensure_can_submit_to(&state, &claims, &id).await?;
+    let tool = params.tool.unwrap_or(Tool::Vivado);
+
+    let mut reports = Vec::new();
+    while let Some(field) = multipart
+        .next_field()
+        .await
+        .map_err(|e| AppError::InvalidUpload(e.to_string()))?
+    {
+        let name = field.name().unwrap_or_default().to_owned();
+        if !tool.report_names().contains(&name.as_str()) {
+            return Err(AppError::InvalidUpload(format!(
+                "unexpected field {name:?}"
+            )));
+        }
+        // checked before anything is stored, since the run id the artifacts are named after is
+        // only known once the run is inserted
+        let filename = sanitize_filename(
+            &field
+                .file_name()
+                .map_or_else(|| format!("{name}.rpt"), str::to_owned),
+        )?;
+        let text = field
+            .text()
+            .await
+            .map_err(|e| AppError::InvalidUpload(e.to_string()))?;
+        reports.push((name, filename, text));
+    }
+
+    let report = |name: &str| {
+        reports
+            .iter()
+            .find(|(n, _, _)| n == name)
+            .map(|(_, _, text)| text.as_str())
+    };
+    let results = parse_synthesis_reports(tool, report)
+        .map_err(|errors| AppError::ParseError(format!("{tool:?} reports"), errors))?;
+    let run = NewSynthesisRun {
+        device: params.device,
+        tool: Some(tool),
+        tool_version: None,
+        strategy: params.strategy,
+        target_clock_period: params.target_clock_period,
+        settings: None,
+        results,
+    };
+
+    if reports.is_empty() {
+        return Err(AppError::InvalidUpload("no reports in request".into()));
+    }
+
+    let mut transaction = state.pool.begin().await?;
+    let run = insert_run(&mut transaction, &id, run, &claims.username).await?;
+    let best = update_best(&mut transaction, &id, state.best_run_rule).await?;
+    let mut stored = Vec::new();
+    for (_, filename, text) in &reports {
+        stored.push(
+            store_artifact(
+                &mut transaction,
+                &id,
+                &format!("run{}_{}", run.id, filename),
+                Some("text/plain"),
+                text.as_bytes(),
+                &claims.username,
+            )
+            .await?,
+        );
+    }
+    commit_artifacts(&state, transaction, &stored).await?;
+    schedule_rank_update(&state);
+
+    info!(
+        "{} uploaded {} {:?} reports for {}",
+        claims.username,
+        reports.len(),
+        tool,
+        id
+    );
+
+    Ok(Json(json!({
+        "id": id,
+        "run": run,
+        "best": best,
+        "submitter": claims.username,
+    })))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::synthesis::SynthesisResults;
+
+    fn run(id: i64, fmax: Option<f64>) -> SynthesisRun {
+        SynthesisRun {
+            id,
+            result_id: "result".to_owned(),
+            device: "xc7a12tcsg325-3".to_owned(),
+            tool: "vivado".to_owned(),
+            tool_version: "2022.1".to_owned(),
+            strategy: String::new(),
+            target_clock_period: None,
+            settings: None,
+            results: sqlx::types::Json(SynthesisResults {
+                fmax,
+                ..Default::default()
+            }),
+            submitter: "admin".to_owned(),
+            timestamp: OffsetDateTime::UNIX_EPOCH,
+        }
+    }
+
+    fn object(value: Value) -> Map<String, Value> {
+        value.as_object().unwrap().clone()
+    }
+
+    #[test]
+    fn keeps_submitted_best_without_runs() {
+        let submitted = json!({"best": {"results": {"Fmax": 123.0, "lut": 1000}}});
+        let mut synthesis = object(submitted.clone());
+        reselect_best(&mut synthesis, &[], BestRunRule::MaxFmax);
+        assert_eq!(Value::Object(synthesis), submitted);
+
+        // runs without the metrics of the rule do not replace it either
+        let mut synthesis = object(submitted.clone());
+        reselect_best(&mut synthesis, &[run(1, None)], BestRunRule::MaxFmax);
+        assert_eq!(Value::Object(synthesis), submitted);
+    }
+
+    #[test]
+    fn selects_best_run() {
+        let mut synthesis = object(json!({"best": {"results": {"Fmax": 123.0}}}));
+        let runs = [run(1, Some(100.0)), run(2, Some(150.0))];
+        reselect_best(&mut synthesis, &runs, BestRunRule::MaxFmax);
+        assert_eq!(synthesis["best"]["run_id"], json!(2));
+        assert_eq!(synthesis["best"]["rule"], json!("max_fmax"));
+    }
+
+    #[test]
+    fn removes_best_of_a_deleted_run() {
+        let mut synthesis = object(json!({"best": {"run_id": 1, "rule": "max_fmax"}}));
+        reselect_best(&mut synthesis, &[], BestRunRule::MinLut);
+        assert!(!synthesis.contains_key("best"));
+    }
+}
@@ -0,0 +1,226 @@
+use std::sync::Arc;
+
+use axum::{extract::State, Json};
+use serde::Deserialize;
+use serde_json::json;
+use sqlx::{PgPool, Postgres, QueryBuilder, Transaction};
+use tracing::info;
+
+use crate::{
+    error::AppError,
+    models::{
+        auth::RequirePermission,
+        roles::ReadResults,
+        timing::{normalize_operation, TimingAggregate, TimingMeasurement, TimingRow},
+    },
+    parsers::timing::rows_from_json,
+    AppState,
+};
+
+/// Conditions on the timing measurements of a result, all of which must hold for the same
+/// measurement.
+#[derive(Debug, Default, Deserialize)]
+pub struct TimingFilter {
+    pub operation: Option<String>,
+    pub ad_len: Option<i64>,
+    pub msg_len: Option<i64>,
+    pub min_cycles: Option<i64>,
+    pub max_cycles: Option<i64>,
+}
+
+impl TimingFilter {
+    /// Append the conditions on the `timing_measurements` columns of `table`.
+    pub fn push_conditions(&self, sql: &mut QueryBuilder<'_, Postgres>, table: &str) {
+        if let Some(ref operation) = self.operation {
+            sql.push(format!(" AND {table}.operation = "))
+                .push_bind(normalize_operation(operation));
+        }
+        if let Some(ad_len) = self.ad_len {
+            sql.push(format!(" AND {table}.ad_len = "))
+                .push_bind(ad_len);
+        }
+        if let Some(msg_len) = self.msg_len {
+            sql.push(format!(" AND {table}.msg_len = "))
+                .push_bind(msg_len);
+        }
+        if let Some(min_cycles) = self.min_cycles {
+            sql.push(format!(" AND {table}.cycles >= "))
+                .push_bind(min_cycles);
+        }
+        if let Some(max_cycles) = self.max_cycles {
+            sql.push(format!(" AND {table}.cycles <= "))
+                .push_bind(max_cycles);
+        }
+    }
+
+    /// Append a condition selecting the results with at least one matching measurement.
+    pub fn push_exists(&self, sql: &mut QueryBuilder<'_, Postgres>) {
+        sql.push(" EXISTS (SELECT 1 FROM timing_measurements t WHERE t.result_id = results.id");
+        self.push_conditions(sql, "t");
+        sql.push(")");
+    }
+}
+
+/// Rows inserted per statement, which binds 7 parameters per row and may bind at most 65535.
+const INSERT_CHUNK_ROWS: usize = 5000;
+
+/// Replace the timing measurements of a result with the rows of its `timing` JSON.
+pub async fn replace_measurements(
+    transaction: &mut Transaction<'_, Postgres>,
+    result_id: &str,
+    rows: &[TimingRow],
+) -> Result<(), AppError> {
+    sqlx::query!(
+        r#"DELETE FROM timing_measurements WHERE result_id = $1;"#,
+        result_id
+    )
+    .execute(&mut *transaction)
+    .await?;
+
+    for chunk in rows.chunks(INSERT_CHUNK_ROWS) {
+        let mut sql = QueryBuilder::new(
+            "INSERT INTO timing_measurements (result_id, msg_id, operation, ad_len, msg_len, cycles, extra) ",
+        );
+        sql.push_values(chunk, |mut b, row| {
+            b.push_bind(result_id)
+                .push_bind(row.msg_id)
+                .push_bind(&row.operation)
+                .push_bind(row.ad_len)
+                .push_bind(row.msg_len)
+                .push_bind(row.cycles)
+                .push_bind((!row.extra.is_empty()).then(|| json!(row.extra)));
+        });
+        sql.build().execute(&mut *transaction).await?;
+    }
+    Ok(())
+}
+
+/// The measurements of a result: its `timing` rows followed by the cycles of its `software`
+/// benchmarks.
+pub fn measurements(timing: &serde_json::Value, software: &serde_json::Value) -> Vec<TimingRow> {
+    let mut rows = rows_from_json(timing);
+    if let Some(cycles) = software.get("cycles") {
+        rows.extend(rows_from_json(cycles));
+    }
+    rows
+}
+
+/// Re-index the measurements of a result from its stored `timing` and `software` columns.
+pub async fn reindex(
+    transaction: &mut Transaction<'_, Postgres>,
+    result_id: &str,
+) -> Result<(), AppError> {
+    let (timing, software): (Option<serde_json::Value>, Option<serde_json::Value>) =
+        sqlx::query_as(r#"SELECT timing, software FROM results WHERE id = $1"#)
+            .bind(result_id)
+            .fetch_one(&mut *transaction)
+            .await?;
+    let rows = measurements(&timing.unwrap_or_default(), &software.unwrap_or_default());
+    replace_measurements(transaction, result_id, &rows).await
+}
+
+/// Populate the measurements of results submitted before the `timing_measurements` table
+/// existed.
+pub async fn index_missing(pool: &PgPool) -> Result<(), AppError> {
+    let missing: Vec<(String, Option<serde_json::Value>, Option<serde_json::Value>)> =
+        sqlx::query_as(
+            r#"
+        SELECT id, timing, software FROM results
+        WHERE (timing IS NOT NULL OR software IS NOT NULL)
+          AND NOT EXISTS (SELECT 1 FROM timing_measurements t WHERE t.result_id = results.id)
+        "#,
+        )
+        .fetch_all(pool)
+        .await?;
+
+    for (id, timing, software) in missing {
+        let rows = measurements(&timing.unwrap_or_default(), &software.unwrap_or_default());
+        if rows.is_empty() {
+            continue;
+        }
+        info!("indexing {} timing measurements of {}", rows.len(), id);
+        let mut transaction = pool.begin().await?;
+        replace_measurements(&mut transaction, &id, &rows).await?;
+        transaction.commit().await?;
+    }
+    Ok(())
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TimingQuery {
+    ids: Option<Vec<String>>,
+    /// only include results whose category starts with this prefix
+    category: Option<String>,
+    #[serde(flatten)]
+    filter: TimingFilter,
+    /// aggregate the measurements per category, operation and lengths instead of listing them
+    #[serde(default)]
+    aggregate: bool,
+    limit: Option<i64>,
+    offset: Option<i64>,
+}
+
+impl TimingQuery {
+    fn push_conditions(&self, sql: &mut QueryBuilder<'_, Postgres>) {
+        if let Some(ref ids) = self.ids {
+            sql.push(" AND t.result_id = ANY(")
+                .push_bind(ids.clone())
+                .push(")");
+        }
+        if let Some(ref category) = self.category {
+            sql.push(" AND starts_with(r.category, ")
+                .push_bind(category.clone())
+                .push(")");
+        }
+        self.filter.push_conditions(sql, "t");
+    }
+
+    fn push_pagination(&self, sql: &mut QueryBuilder<'_, Postgres>) {
+        sql.push(" OFFSET ")
+            .push_bind(self.offset.unwrap_or(0))
+            .push(" LIMIT ")
+            .push_bind(self.limit);
+    }
+}
+
+pub async fn query(
+    State(state): State<Arc<AppState>>,
+    RequirePermission(claims, _): RequirePermission<ReadResults>,
+    Json(query): Json<TimingQuery>,
+) -> Result<Json<serde_json::Value>, AppError> {
+    log::info!("timing query user:{} query: {:?}", claims.username, query);
+
+    if query.aggregate {
+        let mut sql = QueryBuilder::new(
+            r#"SELECT r.category, t.operation, t.ad_len, t.msg_len,
+                count(*) AS results,
+                min(t.cycles) AS min_cycles,
+                max(t.cycles) AS max_cycles,
+                avg(t.cycles)::float8 AS avg_cycles,
+                avg(t.cycles::float8 / NULLIF(t.ad_len + t.msg_len, 0)) AS cycles_per_byte
+            FROM timing_measurements t JOIN results r ON r.id = t.result_id
+            WHERE TRUE"#,
+        );
+        query.push_conditions(&mut sql);
+        sql.push(
+            " GROUP BY r.category, t.operation, t.ad_len, t.msg_len ORDER BY r.category, t.operation, t.ad_len, t.msg_len",
+        );
+        query.push_pagination(&mut sql);
+
+        let rows: Vec<TimingAggregate> = sql.build_query_as().fetch_all(&state.pool).await?;
+        return Ok(Json(json!(rows)));
+    }
+
+    let mut sql = QueryBuilder::new(
+        r#"SELECT t.result_id, r.name, r.category, t.msg_id, t.operation, t.ad_len, t.msg_len, t.cycles, t.extra
+        FROM timing_measurements t JOIN results r ON r.id = t.result_id
+        WHERE TRUE"#,
+    );
+    query.push_conditions(&mut sql);
+    sql.push(" ORDER BY t.result_id, t.operation, t.ad_len, t.msg_len");
+    query.push_pagination(&mut sql);
+
+    let rows: Vec<TimingMeasurement> = sql.build_query_as().fetch_all(&state.pool).await?;
+
+    Ok(Json(json!(rows)))
+}
@@ -0,0 +1,150 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::{Path, State},
+    Json,
+};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use time::{Duration, OffsetDateTime};
+use uuid::Uuid;
+
+use crate::{
+    error::AppError,
+    models::{
+        auth::Claims,
+        roles::Permission,
+        tokens::{self, ApiToken, Scope},
+    },
+    AppState,
+};
+
+const DEFAULT_EXPIRY_DAYS: i64 = 90;
+const MAX_EXPIRY_DAYS: i64 = 365;
+
+/// API tokens can only be managed after logging in with a password.
+fn require_login(claims: &Claims) -> Result<(), AppError> {
+    if claims.scopes.is_some() {
+        return Err(AppError::Forbidden(
+            "API tokens cannot be used to manage API tokens".to_owned(),
+        ));
+    }
+    Ok(())
+}
+
+#[derive(Debug, Deserialize)]
+pub struct NewToken {
+    name: String,
+    scopes: Vec<Scope>,
+    expires_in_days: Option<i64>,
+}
+
+/// Create an API token for the logged in user. The token is only returned by this request.
+pub async fn create(
+    State(state): State<Arc<AppState>>,
+    claims: Claims,
+    Json(new_token): Json<NewToken>,
+) -> Result<Json<Value>, AppError> {
+    require_login(&claims)?;
+
+    let mut errors = Vec::new();
+    if new_token.name.trim().is_empty() {
+        errors.push("name must not be empty".to_owned());
+    }
+    if new_token.scopes.is_empty() {
+        errors.push("at least one scope is required".to_owned());
+    }
+    for scope in &new_token.scopes {
+        if !claims.has_permission(scope.permission()) {
+            errors.push(format!(
+                "scope {scope} requires the {} permission",
+                scope.permission()
+            ));
+        }
+    }
+    let days = new_token.expires_in_days.unwrap_or(DEFAULT_EXPIRY_DAYS);
+    if !(1..=MAX_EXPIRY_DAYS).contains(&days) {
+        errors.push(format!(
+            "expires_in_days must be between 1 and {MAX_EXPIRY_DAYS}, got {days}"
+        ));
+    }
+    if !errors.is_empty() {
+        return Err(AppError::ValidationError("API token".into(), errors));
+    }
+
+    let token = tokens::generate();
+    let now = OffsetDateTime::now_utc();
+    let scopes: Vec<String> = new_token.scopes.iter().map(Scope::to_string).collect();
+    let created: ApiToken = sqlx::query_as(
+        r#"
+        INSERT INTO api_tokens (user_id, name, token_hash, scopes, created, expires)
+        VALUES ($1, $2, $3, $4, $5, $6)
+        RETURNING id, name, user_id, scopes, created, expires, last_used
+        "#,
+    )
+    .bind(claims.sub)
+    .bind(new_token.name.trim())
+    .bind(tokens::hash(&token))
+    .bind(&scopes)
+    .bind(now)
+    .bind(now + Duration::days(days))
+    .fetch_one(&state.pool)
+    .await?;
+
+    log::info!(
+        "{} created API token {} with scopes {:?}",
+        claims.username,
+        created.id,
+        created.scopes
+    );
+    let mut response = json!(created);
+    response["token"] = json!(token);
+    Ok(Json(response))
+}
+
+/// The API tokens of the logged in user, without the tokens themselves.
+pub async fn list(
+    State(state): State<Arc<AppState>>,
+    claims: Claims,
+) -> Result<Json<Value>, AppError> {
+    require_login(&claims)?;
+    let tokens: Vec<ApiToken> = sqlx::query_as(
+        r#"
+        SELECT id, name, user_id, scopes, created, expires, last_used
+        FROM api_tokens
+        WHERE user_id = $1
+        ORDER BY created DESC
+        "#,
+    )
+    .bind(claims.sub)
+    .fetch_all(&state.pool)
+    .await?;
+    Ok(Json(json!(tokens)))
+}
+
+/// Revoke an API token of the logged in user, or of any user for admins.
+pub async fn revoke(
+    State(state): State<Arc<AppState>>,
+    claims: Claims,
+    Path(id): Path<i32>,
+) -> Result<Json<Value>, AppError> {
+    require_login(&claims)?;
+    let owner: Uuid = sqlx::query_scalar(r#"SELECT user_id FROM api_tokens WHERE id = $1"#)
+        .bind(id)
+        .fetch_optional(&state.pool)
+        .await?
+        .ok_or_else(|| AppError::IdNotFound(id.to_string()))?;
+    if owner != claims.sub {
+        claims.require(Permission::ManageUsers)?;
+    }
+
+    sqlx::query(r#"DELETE FROM api_tokens WHERE id = $1"#)
+        .bind(id)
+        .execute(&state.pool)
+        .await?;
+    log::info!("{} revoked API token {} of {}", claims.username, id, owner);
+    Ok(Json(json!({
+        "revoked": id,
+        "user": owner,
+    })))
+}
@@ -0,0 +1,335 @@
+use std::{
+    net::SocketAddr,
+    sync::Arc,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use axum::{
+    extract::{ConnectInfo, State},
+    Json,
+};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use sqlx::{Postgres, Transaction};
+use time::OffsetDateTime;
+use tracing::{info, warn};
+use uuid::Uuid;
+
+use crate::{
+    api::users::{find_user_by_id, issue_tokens, record_login},
+    error::AppError,
+    models::{
+        auth::Claims,
+        tokens::{self, random_hex},
+        totp,
+    },
+    AppState,
+};
+
+/// Time to enter the code after the password was accepted.
+const CHALLENGE_LIFETIME: Duration = Duration::from_secs(5 * 60);
+/// Wrong codes before a login challenge is dropped and the password has to be entered again.
+const CHALLENGE_ATTEMPTS: i32 = 5;
+
+/// TOTP enrollment needs a session started with a password.
+fn require_login(claims: &Claims) -> Result<(), AppError> {
+    if claims.scopes.is_some() {
+        return Err(AppError::Forbidden(
+            "API tokens cannot be used to manage two-factor authentication".to_owned(),
+        ));
+    }
+    Ok(())
+}
+
+fn unix_time() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("Time went backwards")
+        .as_secs()
+}
+
+pub async fn totp_enabled(
+    transaction: &mut Transaction<'_, Postgres>,
+    user_id: Uuid,
+) -> Result<bool, AppError> {
+    Ok(sqlx::query_scalar(
+        r#"SELECT EXISTS(SELECT 1 FROM user_totp WHERE user_id = $1 AND enabled)"#,
+    )
+    .bind(user_id)
+    .fetch_one(&mut *transaction)
+    .await?)
+}
+
+/// Check a TOTP code, or else a recovery code, of a user, using it up.
+///
+/// With `pending`, the code is checked against a secret that is not enabled yet.
+async fn check_code(
+    transaction: &mut Transaction<'_, Postgres>,
+    user_id: Uuid,
+    code: &str,
+    pending: bool,
+) -> Result<bool, AppError> {
+    let Some((secret, last_step)): Option<(String, Option<i64>)> = sqlx::query_as(
+        r#"SELECT secret, last_step FROM user_totp WHERE user_id = $1 AND enabled = $2 FOR UPDATE"#,
+    )
+    .bind(user_id)
+    .bind(!pending)
+    .fetch_optional(&mut *transaction)
+    .await?
+    else {
+        return Ok(false);
+    };
+
+    if let Some(step) = totp::verify(&secret, code, unix_time(), last_step) {
+        sqlx::query(r#"UPDATE user_totp SET last_step = $2 WHERE user_id = $1"#)
+            .bind(user_id)
+            .bind(step)
+            .execute(&mut *transaction)
+            .await?;
+        return Ok(true);
+    }
+    if pending {
+        return Ok(false);
+    }
+
+    let used = sqlx::query(
+        r#"
+        UPDATE totp_recovery_codes SET used = $3
+        WHERE user_id = $1 AND code_hash = $2 AND used IS NULL
+        "#,
+    )
+    .bind(user_id)
+    .bind(tokens::hash(&totp::normalize_recovery_code(code)))
+    .bind(OffsetDateTime::now_utc())
+    .execute(&mut *transaction)
+    .await?
+    .rows_affected();
+    if used == 1 {
+        warn!("User {user_id} used a TOTP recovery code");
+    }
+    Ok(used == 1)
+}
+
+/// Start a login that waits for the TOTP code of the user, returning the challenge token.
+pub async fn create_challenge(
+    transaction: &mut Transaction<'_, Postgres>,
+    user_id: Uuid,
+) -> Result<Value, AppError> {
+    let challenge = random_hex(32);
+    sqlx::query(r#"DELETE FROM login_challenges WHERE user_id = $1 OR expires < $2"#)
+        .bind(user_id)
+        .bind(OffsetDateTime::now_utc())
+        .execute(&mut *transaction)
+        .await?;
+    sqlx::query(
+        r#"INSERT INTO login_challenges (token_hash, user_id, expires) VALUES ($1, $2, $3)"#,
+    )
+    .bind(tokens::hash(&challenge))
+    .bind(user_id)
+    .bind(OffsetDateTime::now_utc() + CHALLENGE_LIFETIME)
+    .execute(&mut *transaction)
+    .await?;
+    Ok(json!({
+        "totp_required": true,
+        "challenge": challenge,
+        "expires_in": CHALLENGE_LIFETIME.as_secs(),
+    }))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ChallengeResponse {
+    challenge: String,
+    /// TOTP code or recovery code
+    code: String,
+}
+
+/// The second step of a login with TOTP: exchange the challenge of `/login` and a code for the
+/// tokens.
+pub async fn login(
+    State(state): State<Arc<AppState>>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    Json(response): Json<ChallengeResponse>,
+) -> Result<Json<Value>, AppError> {
+    let mut transaction = state.pool.begin().await?;
+    let (user_id, expires, attempts): (Uuid, OffsetDateTime, i32) = sqlx::query_as(
+        r#"
+        SELECT user_id, expires, attempts FROM login_challenges
+        WHERE token_hash = $1
+        FOR UPDATE
+        "#,
+    )
+    .bind(tokens::hash(&response.challenge))
+    .fetch_optional(&mut transaction)
+    .await?
+    .ok_or(AppError::InvalidToken)?;
+    if expires <= OffsetDateTime::now_utc() {
+        return Err(AppError::InvalidToken);
+    }
+
+    let user = find_user_by_id(&state.pool, user_id).await?;
+    let limiter = &state.rate_limiter;
+    limiter.check_login(&user.email, addr.ip())?;
+
+    if !check_code(&mut transaction, user_id, &response.code, false).await? {
+        warn!(
+            "Wrong TOTP code for user: {} from {}",
+            user.email,
+            addr.ip()
+        );
+        limiter.login_failed(&user.email, addr.ip());
+        if attempts + 1 >= CHALLENGE_ATTEMPTS {
+            sqlx::query(r#"DELETE FROM login_challenges WHERE user_id = $1"#)
+                .bind(user_id)
+                .execute(&mut transaction)
+                .await?;
+        } else {
+            sqlx::query(
+                r#"UPDATE login_challenges SET attempts = attempts + 1 WHERE token_hash = $1"#,
+            )
+            .bind(tokens::hash(&response.challenge))
+            .execute(&mut transaction)
+            .await?;
+        }
+        transaction.commit().await?;
+        return Err(AppError::WrongCredential);
+    }
+
+    sqlx::query(r#"DELETE FROM login_challenges WHERE user_id = $1"#)
+        .bind(user_id)
+        .execute(&mut transaction)
+        .await?;
+    info!("User: {} successfully logged in with TOTP", user.email);
+    limiter.login_succeeded(&user.email);
+    record_login(&mut transaction, user_id).await?;
+    let tokens = issue_tokens(&state, &mut transaction, &user, &random_hex(16), true).await?;
+    transaction.commit().await?;
+    Ok(Json(tokens))
+}
+
+/// Generate a new TOTP secret for the logged in user. It is enabled by `verify` once the user
+/// has entered it in their authenticator app.
+pub async fn enroll(
+    State(state): State<Arc<AppState>>,
+    claims: Claims,
+) -> Result<Json<Value>, AppError> {
+    require_login(&claims)?;
+    let mut transaction = state.pool.begin().await?;
+    if totp_enabled(&mut transaction, claims.sub).await? {
+        return Err(AppError::ValidationError(
+            "TOTP".into(),
+            vec!["two-factor authentication is already enabled, disable it first".to_owned()],
+        ));
+    }
+
+    let secret = totp::generate_secret();
+    sqlx::query(
+        r#"
+        INSERT INTO user_totp (user_id, secret, enabled, created)
+        VALUES ($1, $2, FALSE, $3)
+        ON CONFLICT (user_id) DO UPDATE
+        SET secret = EXCLUDED.secret, last_step = NULL, created = EXCLUDED.created
+        "#,
+    )
+    .bind(claims.sub)
+    .bind(&secret)
+    .bind(OffsetDateTime::now_utc())
+    .execute(&mut transaction)
+    .await?;
+    transaction.commit().await?;
+
+    let uri =
+        totp::provisioning_uri(&claims.username, &secret).ok_or(AppError::InternalServerError)?;
+    let secret = totp::display_secret(&secret).ok_or(AppError::InternalServerError)?;
+    Ok(Json(json!({
+        "secret": secret,
+        "provisioning_uri": uri,
+    })))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Code {
+    code: String,
+}
+
+/// Enable two-factor authentication with the first code of the enrolled secret, returning the
+/// recovery codes. They are only shown once.
+pub async fn verify(
+    State(state): State<Arc<AppState>>,
+    claims: Claims,
+    Json(code): Json<Code>,
+) -> Result<Json<Value>, AppError> {
+    require_login(&claims)?;
+    let mut transaction = state.pool.begin().await?;
+    if !check_code(&mut transaction, claims.sub, &code.code, true).await? {
+        return Err(AppError::WrongCredential);
+    }
+    sqlx::query(r#"UPDATE user_totp SET enabled = TRUE WHERE user_id = $1"#)
+        .bind(claims.sub)
+        .execute(&mut transaction)
+        .await?;
+
+    let recovery_codes = totp::recovery_codes();
+    sqlx::query(r#"DELETE FROM totp_recovery_codes WHERE user_id = $1"#)
+        .bind(claims.sub)
+        .execute(&mut transaction)
+        .await?;
+    let hashes: Vec<String> = recovery_codes
+        .iter()
+        .map(|code| tokens::hash(&totp::normalize_recovery_code(code)))
+        .collect();
+    sqlx::query(
+        r#"INSERT INTO totp_recovery_codes (user_id, code_hash) SELECT $1, unnest($2::text[])"#,
+    )
+    .bind(claims.sub)
+    .bind(&hashes)
+    .execute(&mut transaction)
+    .await?;
+    transaction.commit().await?;
+
+    info!(
+        "User: {} enabled two-factor authentication",
+        claims.username
+    );
+    Ok(Json(json!({
+        "enabled": true,
+        "recovery_codes": recovery_codes,
+    })))
+}
+
+/// Remove the TOTP secret and the recovery codes of a user.
+pub async fn remove(
+    transaction: &mut Transaction<'_, Postgres>,
+    user_id: Uuid,
+) -> Result<bool, AppError> {
+    sqlx::query(r#"DELETE FROM totp_recovery_codes WHERE user_id = $1"#)
+        .bind(user_id)
+        .execute(&mut *transaction)
+        .await?;
+    let removed = sqlx::query(r#"DELETE FROM user_totp WHERE user_id = $1"#)
+        .bind(user_id)
+        .execute(&mut *transaction)
+        .await?
+        .rows_affected();
+    Ok(removed > 0)
+}
+
+/// Disable two-factor authentication of the logged in user, confirmed with a current code.
+pub async fn disable(
+    State(state): State<Arc<AppState>>,
+    claims: Claims,
+    Json(code): Json<Code>,
+) -> Result<Json<Value>, AppError> {
+    require_login(&claims)?;
+    let mut transaction = state.pool.begin().await?;
+    if !check_code(&mut transaction, claims.sub, &code.code, false).await? {
+        return Err(AppError::WrongCredential);
+    }
+    remove(&mut transaction, claims.sub).await?;
+    transaction.commit().await?;
+
+    info!(
+        "User: {} disabled two-factor authentication",
+        claims.username
+    );
+    Ok(Json(json!({ "enabled": false })))
+}
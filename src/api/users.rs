@@ -1,32 +1,195 @@
-use crate::{error::AppError, models::{auth::{Claims, User}, self}};
-use std::sync::Arc;
+use crate::{
+    api::totp,
+    error::AppError,
+    models::{
+        self,
+        auth::{Claims, User, UserInfo},
+        roles::Role,
+        tokens::{self, random_hex},
+    },
+};
+use std::{net::SocketAddr, sync::Arc, time::Duration};
 
-use axum::{Json, extract::State};
-use jsonwebtoken::{encode, Header};
+use axum::{
+    extract::{ConnectInfo, State},
+    Json,
+};
+use serde::Deserialize;
 use serde_json::{json, Value};
-use sqlx::PgPool;
+use sqlx::{PgPool, Postgres, Transaction};
+use time::OffsetDateTime;
 use tracing::{info, warn};
+use uuid::Uuid;
 
-use crate::{
-    utils::get_timestamp_8_hours_from_now,
-    AppState, KEYS,
-};
+use crate::{password::PasswordMatch, utils::get_timestamp_from_now, AppState};
 
-pub async fn user_profile(claims: Claims) -> Result<axum::Json<serde_json::Value>, AppError> {
-    // if the token is verified and data is extracted from the token by the implimentation in utils.rs then only the below code will run
-    Ok(axum::Json(serde_json::json!({"username": claims.username})))
+/// Login tokens are short-lived, sessions are kept alive with refresh tokens.
+const ACCESS_TOKEN_LIFETIME: Duration = Duration::from_secs(15 * 60);
+const REFRESH_TOKEN_LIFETIME: Duration = Duration::from_secs(30 * 24 * 3600);
+
+pub async fn find_user_info(pool: &PgPool, id: Uuid) -> Result<UserInfo, AppError> {
+    sqlx::query_as(&format!(
+        "SELECT {} FROM users WHERE id = $1",
+        UserInfo::COLUMNS
+    ))
+    .bind(id)
+    .fetch_optional(pool)
+    .await?
+    .ok_or(AppError::UserDoesNotExist)
+}
+
+pub async fn user_profile(
+    State(state): State<Arc<AppState>>,
+    claims: Claims,
+) -> Result<Json<Value>, AppError> {
+    let info = find_user_info(&state.pool, claims.sub).await?;
+    let mut profile = json!(info);
+    // the username was part of the profile before the other fields
+    profile["username"] = json!(info.email);
+    if let Some(ref scopes) = claims.scopes {
+        profile["scopes"] = json!(scopes);
+    }
+    Ok(Json(profile))
 }
 
+#[derive(Debug, Deserialize)]
+pub struct ProfileUpdate {
+    email: Option<String>,
+    name: Option<String>,
+}
 
-pub async fn find_user(pool: &PgPool, user_id: &str) -> Result<User, AppError> {
-    // get the user for the email from database
-    let user = sqlx::query_as::<_, models::auth::User>(
+/// Change the email and/or name of a user.
+pub async fn update_user(
+    pool: &PgPool,
+    id: Uuid,
+    update: &ProfileUpdate,
+) -> Result<UserInfo, AppError> {
+    let email = update.email.as_deref().map(str::trim);
+    if email == Some("") {
+        return Err(AppError::MissingCredential);
+    }
+    let updated = sqlx::query(
         r#"
-        SELECT email, password, name, is_admin FROM users
-        where email = $1
+        UPDATE users
+        SET email = COALESCE($2, email),
+            name  = COALESCE($3, name)
+        WHERE id = $1
         "#,
     )
-    .bind(user_id)
+    .bind(id)
+    .bind(email)
+    .bind(update.name.as_deref().map(str::trim))
+    .execute(pool)
+    .await
+    .map_err(|err| match err {
+        sqlx::Error::Database(ref e) if e.code().as_deref() == Some("23505") => {
+            AppError::UserAlreadyExits
+        }
+        err => AppError::SqlxError(err),
+    })?
+    .rows_affected();
+    if updated == 0 {
+        return Err(AppError::UserDoesNotExist);
+    }
+    find_user_info(pool, id).await
+}
+
+/// Edit the profile of the logged in user.
+///
+/// After changing the email, tokens issued before keep showing the old one as `username`
+/// until they are refreshed.
+pub async fn update_profile(
+    State(state): State<Arc<AppState>>,
+    claims: Claims,
+    Json(update): Json<ProfileUpdate>,
+) -> Result<Json<Value>, AppError> {
+    if claims.scopes.is_some() {
+        return Err(AppError::Forbidden(
+            "API tokens cannot be used to change the profile".to_owned(),
+        ));
+    }
+    let info = update_user(&state.pool, claims.sub, &update).await?;
+    if info.email != claims.username {
+        info!(
+            "User: {} changed their email to {}",
+            claims.username, info.email
+        );
+    }
+    Ok(Json(json!(info)))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PasswordChange {
+    current_password: String,
+    new_password: String,
+}
+
+/// Change the password of the logged in user, which ends all their sessions.
+pub async fn change_password(
+    State(state): State<Arc<AppState>>,
+    claims: Claims,
+    Json(change): Json<PasswordChange>,
+) -> Result<Json<Value>, AppError> {
+    if claims.scopes.is_some() {
+        return Err(AppError::Forbidden(
+            "API tokens cannot be used to change passwords".to_owned(),
+        ));
+    }
+    if change.new_password.is_empty() {
+        return Err(AppError::MissingCredential);
+    }
+    let user = find_user_by_id(&state.pool, claims.sub).await?;
+    let matches = state
+        .password_hasher
+        .verify(&user.password, &change.current_password)?;
+    if matches == PasswordMatch::Wrong {
+        warn!("Wrong current password for user: {}", &claims.username);
+        return Err(AppError::WrongCredential);
+    }
+
+    let mut transaction = state.pool.begin().await?;
+    set_password(&state, &mut transaction, claims.sub, &change.new_password).await?;
+    transaction.commit().await?;
+
+    info!("User: {} changed their password", &claims.username);
+    Ok(Json(
+        json!({ "success": "password changed, all sessions ended" }),
+    ))
+}
+
+/// Replace the password of a user and end their sessions.
+pub async fn set_password(
+    state: &AppState,
+    transaction: &mut Transaction<'_, Postgres>,
+    id: Uuid,
+    password: &str,
+) -> Result<(), AppError> {
+    let password_hash = state.password_hasher.hash(password)?;
+    sqlx::query(r#"UPDATE users SET password = $2 WHERE id = $1"#)
+        .bind(id)
+        .bind(password_hash)
+        .execute(&mut *transaction)
+        .await?;
+    end_sessions(transaction, id).await
+}
+
+const USER_COLUMNS: &str = r#"id, email, password, name, disabled,
+    ARRAY(SELECT role FROM user_roles WHERE user_id = users.id ORDER BY role) AS roles"#;
+
+pub async fn find_user_by_id(pool: &PgPool, id: Uuid) -> Result<User, AppError> {
+    sqlx::query_as(&format!("SELECT {USER_COLUMNS} FROM users WHERE id = $1"))
+        .bind(id)
+        .fetch_optional(pool)
+        .await?
+        .ok_or(AppError::UserDoesNotExist)
+}
+
+pub async fn find_user(pool: &PgPool, email: &str) -> Result<User, AppError> {
+    // get the user for the email from database
+    let user = sqlx::query_as::<_, models::auth::User>(&format!(
+        "SELECT {USER_COLUMNS} FROM users WHERE email = $1"
+    ))
+    .bind(email)
     .fetch_optional(pool)
     .await
     .map_err(|err| {
@@ -37,34 +200,248 @@ pub async fn find_user(pool: &PgPool, user_id: &str) -> Result<User, AppError> {
     user.ok_or(AppError::UserDoesNotExist)
 }
 
-
 pub async fn login(
     State(state): State<Arc<AppState>>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
     Json(credentials): Json<models::auth::User>,
 ) -> Result<Json<Value>, AppError> {
     if credentials.email.is_empty() {
         return Err(AppError::MissingCredential);
     }
+    let limiter = &state.rate_limiter;
+    limiter.check_login(&credentials.email, addr.ip())?;
 
-    let user = find_user(&state.pool, &credentials.email).await?;
+    let user = match find_user(&state.pool, &credentials.email).await {
+        Ok(user) => user,
+        Err(err) => {
+            // guessing account names counts against the IP address as well
+            limiter.login_failed(&credentials.email, addr.ip());
+            return Err(err);
+        }
+    };
 
-    let matches = argon2::verify_encoded(&user.password, &credentials.password.as_bytes()).unwrap();
+    let matches = state
+        .password_hasher
+        .verify(&user.password, &credentials.password)?;
 
-    if matches {}
-    
-    if matches {
+    if matches != PasswordMatch::Wrong {
+        if user.disabled {
+            warn!("Login attempt of disabled user: {}", &credentials.email);
+            return Err(AppError::AuthenticationError(format!(
+                "user {} is disabled",
+                credentials.email
+            )));
+        }
+        let mut transaction = state.pool.begin().await?;
+        if matches == PasswordMatch::Outdated {
+            info!("Rehashing the password of {}", &credentials.email);
+            sqlx::query(r#"UPDATE users SET password = $2 WHERE id = $1"#)
+                .bind(user.id)
+                .bind(state.password_hasher.hash(&credentials.password)?)
+                .execute(&mut transaction)
+                .await?;
+        }
+        if totp::totp_enabled(&mut transaction, user.id).await? {
+            // the login is completed at /login/totp
+            let challenge = totp::create_challenge(&mut transaction, user.id).await?;
+            transaction.commit().await?;
+            return Ok(Json(challenge));
+        }
         info!("User: {} successfully logged in", &credentials.email);
-        let claims = Claims {
-            username: user.email,
-            is_admin: user.is_admin,
-            exp: get_timestamp_8_hours_from_now(),
-        };
-        let token = encode(&Header::default(), &claims, &KEYS.encoding)
-        .map_err(|_| AppError::TokenCreation)?;
-        // return bearer token
-        Ok(Json(json!({ "access_token": token, "type": "Bearer" })))
+        limiter.login_succeeded(&credentials.email);
+        record_login(&mut transaction, user.id).await?;
+        let tokens = issue_tokens(&state, &mut transaction, &user, &random_hex(16), false).await?;
+        transaction.commit().await?;
+        Ok(Json(tokens))
     } else {
-        warn!("Wrong credentials for user: {}", &credentials.email);
+        warn!(
+            "Wrong credentials for user: {} from {}",
+            &credentials.email,
+            addr.ip()
+        );
+        limiter.login_failed(&credentials.email, addr.ip());
         Err(AppError::WrongCredential)
     }
 }
+
+pub async fn record_login(
+    transaction: &mut Transaction<'_, Postgres>,
+    id: Uuid,
+) -> Result<(), AppError> {
+    sqlx::query(r#"UPDATE users SET last_login = $2 WHERE id = $1"#)
+        .bind(id)
+        .bind(OffsetDateTime::now_utc())
+        .execute(&mut *transaction)
+        .await?;
+    Ok(())
+}
+
+/// Issue an access token and a refresh token for a session of `user`.
+///
+/// `mfa` tells whether the session was started with a second factor. Without it, the admin role
+/// is withheld while `ADMIN_TOTP_REQUIRED` is set.
+pub async fn issue_tokens(
+    state: &AppState,
+    transaction: &mut Transaction<'_, Postgres>,
+    user: &User,
+    session: &str,
+    mfa: bool,
+) -> Result<Value, AppError> {
+    let mut roles = user.parsed_roles();
+    let admin_withheld = state.admin_totp_required && !mfa && roles.contains(&Role::Admin);
+    if admin_withheld {
+        roles.retain(|role| *role != Role::Admin);
+    }
+    let claims = Claims {
+        sub: user.id,
+        username: user.email.clone(),
+        roles,
+        scopes: None,
+        jti: random_hex(16),
+        sid: Some(session.to_owned()),
+        iat: OffsetDateTime::now_utc().unix_timestamp_nanos() as f64 / 1e9,
+        exp: get_timestamp_from_now(ACCESS_TOKEN_LIFETIME),
+    };
+    let access_token = state.jwt_keys.encode(&claims)?;
+
+    let refresh_token = random_hex(32);
+    let now = OffsetDateTime::now_utc();
+    sqlx::query(
+        r#"
+        INSERT INTO refresh_tokens (token_hash, session, user_id, created, expires, mfa)
+        VALUES ($1, $2, $3, $4, $5, $6)
+        "#,
+    )
+    .bind(tokens::hash(&refresh_token))
+    .bind(session)
+    .bind(user.id)
+    .bind(now)
+    .bind(now + REFRESH_TOKEN_LIFETIME)
+    .bind(mfa)
+    .execute(&mut *transaction)
+    .await?;
+
+    let mut tokens = json!({
+        "access_token": access_token,
+        "type": "Bearer",
+        "expires_in": ACCESS_TOKEN_LIFETIME.as_secs(),
+        "refresh_token": refresh_token,
+    });
+    if admin_withheld {
+        tokens["warning"] = json!(
+            "the admin role requires two-factor authentication, enable it at /user_profile/totp"
+        );
+    }
+    Ok(tokens)
+}
+
+/// End all login sessions of a user, e.g. after changing their password, revoking their access
+/// tokens as well.
+pub async fn end_sessions(
+    transaction: &mut Transaction<'_, Postgres>,
+    id: Uuid,
+) -> Result<(), AppError> {
+    sqlx::query(r#"DELETE FROM refresh_tokens WHERE user_id = $1"#)
+        .bind(id)
+        .execute(&mut *transaction)
+        .await?;
+    sqlx::query(r#"UPDATE users SET tokens_valid_after = $2 WHERE id = $1"#)
+        .bind(id)
+        .bind(OffsetDateTime::now_utc())
+        .execute(&mut *transaction)
+        .await?;
+    Ok(())
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RefreshRequest {
+    refresh_token: String,
+}
+
+/// Exchange a refresh token for a new access token and a new refresh token.
+///
+/// Every refresh token can only be used once. Presenting a used one means it was stolen or
+/// replayed, so the whole session is ended.
+pub async fn refresh(
+    State(state): State<Arc<AppState>>,
+    Json(request): Json<RefreshRequest>,
+) -> Result<Json<Value>, AppError> {
+    let mut transaction = state.pool.begin().await?;
+    let (session, user_id, expires, used, mfa): (String, Uuid, OffsetDateTime, bool, bool) =
+        sqlx::query_as(
+            r#"
+            SELECT session, user_id, expires, used, mfa FROM refresh_tokens
+            WHERE token_hash = $1
+            FOR UPDATE
+            "#,
+        )
+        .bind(tokens::hash(&request.refresh_token))
+        .fetch_optional(&mut transaction)
+        .await?
+        .ok_or(AppError::InvalidToken)?;
+
+    if used {
+        warn!("Reuse of a refresh token of {user_id}, ending session {session}");
+        sqlx::query(r#"DELETE FROM refresh_tokens WHERE session = $1"#)
+            .bind(&session)
+            .execute(&mut transaction)
+            .await?;
+        transaction.commit().await?;
+        return Err(AppError::InvalidToken);
+    }
+    if expires <= OffsetDateTime::now_utc() {
+        return Err(AppError::InvalidToken);
+    }
+
+    sqlx::query(r#"UPDATE refresh_tokens SET used = TRUE WHERE token_hash = $1"#)
+        .bind(tokens::hash(&request.refresh_token))
+        .execute(&mut transaction)
+        .await?;
+    let user = find_user_by_id(&state.pool, user_id).await?;
+    if user.disabled {
+        return Err(AppError::InvalidToken);
+    }
+    let tokens = issue_tokens(&state, &mut transaction, &user, &session, mfa).await?;
+    transaction.commit().await?;
+    Ok(Json(tokens))
+}
+
+/// Revoke the access token of the request and end its session.
+pub async fn logout(
+    State(state): State<Arc<AppState>>,
+    claims: Claims,
+) -> Result<Json<Value>, AppError> {
+    if claims.jti.is_empty() {
+        return Err(AppError::Forbidden(
+            "only login sessions can log out, API tokens are revoked with DELETE /tokens/:id"
+                .to_owned(),
+        ));
+    }
+    let expires = OffsetDateTime::from_unix_timestamp(claims.exp as i64)
+        .map_err(|_| AppError::InvalidToken)?;
+
+    let mut transaction = state.pool.begin().await?;
+    sqlx::query(r#"DELETE FROM revoked_tokens WHERE expires < $1"#)
+        .bind(OffsetDateTime::now_utc())
+        .execute(&mut transaction)
+        .await?;
+    sqlx::query(
+        r#"INSERT INTO revoked_tokens (jti, expires) VALUES ($1, $2) ON CONFLICT DO NOTHING"#,
+    )
+    .bind(&claims.jti)
+    .bind(expires)
+    .execute(&mut transaction)
+    .await?;
+    if let Some(ref session) = claims.sid {
+        sqlx::query(r#"DELETE FROM refresh_tokens WHERE session = $1"#)
+            .bind(session)
+            .execute(&mut transaction)
+            .await?;
+    }
+    transaction.commit().await?;
+
+    info!("User: {} logged out", claims.username);
+    Ok(Json(
+        json!({ "success": format!("logged out {}", claims.username) }),
+    ))
+}
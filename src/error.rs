@@ -1,9 +1,15 @@
-use std::fmt::Display;
+use std::{fmt::Display, time::Duration};
 
-use axum::{http::StatusCode, response::IntoResponse, Json};
+use axum::{
+    http::{header, StatusCode},
+    response::IntoResponse,
+    Json,
+};
 use miette::Diagnostic;
 use serde_json::json;
 
+use crate::parsers::LineError;
+
 #[derive(Debug, thiserror::Error)]
 pub enum AppError {
     WrongCredential,
@@ -16,9 +22,17 @@ pub enum AppError {
     IdNotFound(String),
     IdExists(String),
     AuthenticationError(String),
+    Forbidden(String),
     InvalidToken,
     TokenError(String),
     SqlxError(sqlx::Error),
+    ArtifactNotFound(String, String),
+    InvalidUpload(String),
+    IoError(std::io::Error),
+    ParseError(String, Vec<LineError>),
+    ValidationError(String, Vec<String>),
+    /// the client has to wait this long before retrying
+    TooManyRequests(Duration),
 }
 
 impl Display for AppError {
@@ -35,8 +49,24 @@ impl From<sqlx::Error> for AppError {
     }
 }
 
+impl From<std::io::Error> for AppError {
+    fn from(e: std::io::Error) -> Self {
+        AppError::IoError(e)
+    }
+}
+
 impl IntoResponse for AppError {
     fn into_response(self) -> axum::response::Response {
+        if let Self::TooManyRequests(wait) = self {
+            // round up, so that retrying after `Retry-After` seconds succeeds
+            let seconds = wait.as_secs() + u64::from(wait.subsec_nanos() > 0);
+            return (
+                StatusCode::TOO_MANY_REQUESTS,
+                [(header::RETRY_AFTER, seconds.to_string())],
+                Json(json!({ "error": format!("too many requests, retry in {seconds}s") })),
+            )
+                .into_response();
+        }
         let (status, err_msg) = match self {
             Self::InternalServerError => (
                 StatusCode::INTERNAL_SERVER_ERROR,
@@ -53,13 +83,14 @@ impl IntoResponse for AppError {
                 StatusCode::UNAUTHORIZED,
                 format!("Authentication error: {msg}"),
             ),
+            Self::Forbidden(msg) => (StatusCode::FORBIDDEN, format!("Forbidden: {msg}")),
             Self::TokenError(msg) => (
                 StatusCode::BAD_REQUEST,
                 format!("Error in authentication token: {msg}"),
             ),
             Self::InvalidToken => (
                 StatusCode::BAD_REQUEST,
-                format!("Invalid authentication token"),
+                "Invalid authentication token".into(),
             ),
             Self::UserAlreadyExits => (StatusCode::BAD_REQUEST, "User already exists".into()),
             Self::InvalidQuery => (StatusCode::BAD_REQUEST, "Invalid query".into()),
@@ -69,6 +100,31 @@ impl IntoResponse for AppError {
                 StatusCode::INTERNAL_SERVER_ERROR,
                 format!("Database error: {e}"),
             ),
+            Self::ArtifactNotFound(id, filename) => (
+                StatusCode::NOT_FOUND,
+                format!("Artifact {filename} of ID: {id} was not found."),
+            ),
+            Self::InvalidUpload(msg) => (StatusCode::BAD_REQUEST, format!("Invalid upload: {msg}")),
+            Self::IoError(e) => (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Storage error: {e}"),
+            ),
+            Self::ParseError(what, errors) => (
+                StatusCode::BAD_REQUEST,
+                format!(
+                    "Could not parse {what}: {}",
+                    errors
+                        .iter()
+                        .map(ToString::to_string)
+                        .collect::<Vec<_>>()
+                        .join("; ")
+                ),
+            ),
+            Self::ValidationError(what, errors) => (
+                StatusCode::BAD_REQUEST,
+                format!("Invalid {what}: {}", errors.join("; ")),
+            ),
+            Self::TooManyRequests(_) => unreachable!("handled above"),
         };
         (status, Json(json!({ "error": err_msg }))).into_response()
     }
@@ -1,13 +1,3 @@
-use std::net::SocketAddr;
-
-use axum::{
-    extract::Host,
-    handler::Handler,
-    http::{StatusCode, Uri},
-    response::Redirect,
-    BoxError,
-};
-
 #[derive(Clone, Copy)]
 pub struct Ports {
     pub http: u16,
@@ -0,0 +1,191 @@
+use std::{collections::HashMap, env, fs, path::Path};
+
+use jsonwebtoken::{
+    decode, decode_header, encode, Algorithm, DecodingKey, EncodingKey, Header, Validation,
+};
+use serde::{de::DeserializeOwned, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::error::AppError;
+
+/// A key that signatures of access tokens are checked with.
+struct VerificationKey {
+    algorithm: Algorithm,
+    key: DecodingKey,
+}
+
+/// The key access tokens are signed with and the keys they are verified with, by key id (`kid`).
+///
+/// Tokens are signed with an Ed25519 (`EdDSA`) or RSA (`RS256`) key pair, or with a shared
+/// secret (`HS256`) when no key pair is configured. Keeping the public keys of previous key
+/// pairs in the verification keys lets tokens signed before a rotation stay valid until they
+/// expire.
+pub struct JwtKeys {
+    kid: String,
+    algorithm: Algorithm,
+    signing: EncodingKey,
+    verification: HashMap<String, VerificationKey>,
+}
+
+/// The id of a key: the start of the SHA-256 checksum of its public part.
+fn key_id(public: &[u8]) -> String {
+    Sha256::digest(public)
+        .iter()
+        .take(8)
+        .map(|b| format!("{b:02x}"))
+        .collect()
+}
+
+fn read_key(path: &Path) -> Result<Vec<u8>, String> {
+    fs::read(path).map_err(|e| format!("could not read JWT key {}: {e}", path.display()))
+}
+
+/// Parse a PEM encoded public key, returning its algorithm.
+fn parse_public_key(pem: &[u8], path: &Path) -> Result<VerificationKey, String> {
+    if let Ok(key) = DecodingKey::from_ed_pem(pem) {
+        return Ok(VerificationKey {
+            algorithm: Algorithm::EdDSA,
+            key,
+        });
+    }
+    DecodingKey::from_rsa_pem(pem)
+        .map(|key| VerificationKey {
+            algorithm: Algorithm::RS256,
+            key,
+        })
+        .map_err(|_| {
+            format!(
+                "{} is not a PEM encoded Ed25519 or RSA public key",
+                path.display()
+            )
+        })
+}
+
+impl JwtKeys {
+    /// Sign with the secret `secret` (HS256).
+    pub fn from_secret(secret: &[u8], kid: Option<String>) -> Self {
+        let kid = kid.unwrap_or_else(|| key_id(secret));
+        let verification = HashMap::from([(
+            kid.clone(),
+            VerificationKey {
+                algorithm: Algorithm::HS256,
+                key: DecodingKey::from_secret(secret),
+            },
+        )]);
+        Self {
+            kid,
+            algorithm: Algorithm::HS256,
+            signing: EncodingKey::from_secret(secret),
+            verification,
+        }
+    }
+
+    /// Sign with the PEM encoded private key at `private` whose public key is at `public`.
+    pub fn from_key_pair(
+        private: &Path,
+        public: &Path,
+        kid: Option<String>,
+    ) -> Result<Self, String> {
+        let public_pem = read_key(public)?;
+        let verification_key = parse_public_key(&public_pem, public)?;
+        let private_pem = read_key(private)?;
+        let signing = match verification_key.algorithm {
+            Algorithm::EdDSA => EncodingKey::from_ed_pem(&private_pem),
+            _ => EncodingKey::from_rsa_pem(&private_pem),
+        }
+        .map_err(|e| format!("invalid JWT private key {}: {e}", private.display()))?;
+        let kid = kid.unwrap_or_else(|| key_id(&public_pem));
+        let algorithm = verification_key.algorithm;
+        Ok(Self {
+            kid: kid.clone(),
+            algorithm,
+            signing,
+            verification: HashMap::from([(kid, verification_key)]),
+        })
+    }
+
+    /// Also accept tokens signed with the private key of the PEM encoded public key at `path`.
+    pub fn add_verification_key(&mut self, path: &Path, kid: Option<String>) -> Result<(), String> {
+        let pem = read_key(path)?;
+        let key = parse_public_key(&pem, path)?;
+        self.verification
+            .insert(kid.unwrap_or_else(|| key_id(&pem)), key);
+        Ok(())
+    }
+
+    /// Load the keys from `JWT_PRIVATE_KEY` and `JWT_PUBLIC_KEY` (PEM files), with the key id
+    /// `JWT_KEY_ID`, and the previous public keys in `JWT_VERIFY_KEYS`, a comma separated list
+    /// of `path` or `kid=path` entries.
+    ///
+    /// Without a key pair, tokens are signed with `JWT_SECRET`, or else with the secret returned
+    /// by `secret`, which is not called otherwise.
+    pub fn from_env(secret: impl FnOnce() -> Result<String, String>) -> Result<Self, String> {
+        let kid = env::var("JWT_KEY_ID")
+            .ok()
+            .filter(|kid| !kid.trim().is_empty());
+        let mut keys = match (env::var("JWT_PRIVATE_KEY"), env::var("JWT_PUBLIC_KEY")) {
+            (Ok(private), Ok(public)) => {
+                Self::from_key_pair(Path::new(&private), Path::new(&public), kid)?
+            }
+            (Ok(_), Err(_)) | (Err(_), Ok(_)) => {
+                return Err("JWT_PRIVATE_KEY and JWT_PUBLIC_KEY have to be set together".to_owned())
+            }
+            (Err(_), Err(_)) => {
+                let secret = match env::var("JWT_SECRET") {
+                    Ok(secret) => secret,
+                    Err(_) => secret()?,
+                };
+                Self::from_secret(secret.trim().as_bytes(), kid)
+            }
+        };
+        for entry in env::var("JWT_VERIFY_KEYS")
+            .unwrap_or_default()
+            .split(',')
+            .map(str::trim)
+            .filter(|entry| !entry.is_empty())
+        {
+            match entry.split_once('=') {
+                Some((kid, path)) => {
+                    keys.add_verification_key(Path::new(path.trim()), Some(kid.trim().to_owned()))?
+                }
+                None => keys.add_verification_key(Path::new(entry), None)?,
+            }
+        }
+        Ok(keys)
+    }
+
+    /// The id and algorithm of the signing key.
+    pub fn signing_key(&self) -> (&str, Algorithm) {
+        (&self.kid, self.algorithm)
+    }
+
+    /// The ids of the keys tokens are accepted from.
+    pub fn verification_key_ids(&self) -> Vec<&str> {
+        let mut ids: Vec<&str> = self.verification.keys().map(String::as_str).collect();
+        ids.sort_unstable();
+        ids
+    }
+
+    pub fn encode<T: Serialize>(&self, claims: &T) -> Result<String, AppError> {
+        let header = Header {
+            kid: Some(self.kid.clone()),
+            ..Header::new(self.algorithm)
+        };
+        encode(&header, claims, &self.signing).map_err(|_| AppError::TokenCreation)
+    }
+
+    /// Verify a token with the key named by its `kid`. Tokens without one are checked with the
+    /// signing key.
+    pub fn decode<T: DeserializeOwned>(&self, token: &str) -> Result<T, AppError> {
+        let header = decode_header(token).map_err(|_| AppError::InvalidToken)?;
+        let kid = header.kid.as_deref().unwrap_or(&self.kid);
+        let key = self.verification.get(kid).ok_or(AppError::InvalidToken)?;
+        // the algorithm comes from the key, never from the token
+        if header.alg != key.algorithm {
+            return Err(AppError::InvalidToken);
+        }
+        decode::<T>(token, &key.key, &Validation::new(key.algorithm))
+            .map(|data| data.claims)
+            .map_err(|_| AppError::InvalidToken)
+    }
+}
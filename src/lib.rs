@@ -1,24 +1,29 @@
-use once_cell::sync::Lazy;
-use secrecy::Secret;
 use sqlx::migrate::Migrator;
 
 // import module
 pub mod api;
 pub mod error;
 pub mod http_server;
+pub mod jwt;
 pub mod models;
+pub mod mtls;
+pub mod parsers;
+pub mod password;
+pub mod rate_limit;
+pub mod storage;
 pub mod utils;
 
-// secret key for JWT token
-static KEYS: Lazy<models::auth::Keys> = Lazy::new(|| {
-    let secret =
-        std::env::var("JWT_SECRET").unwrap_or_else(|_| include_str!("../SECRET").to_owned());
-    models::auth::Keys::new(secret.as_bytes())
-});
-
 pub static MIGRATOR: Migrator = sqlx::migrate!(); // defaults to "./migrations"
 
 pub struct AppState {
     pub pool: sqlx::PgPool,
-    pub secret: Secret<String>,
+    pub jwt_keys: jwt::JwtKeys,
+    pub artifacts: storage::ArtifactStore,
+    pub best_run_rule: models::synthesis::BestRunRule,
+    pub rate_limiter: rate_limit::RateLimiter,
+    pub password_hasher: password::PasswordHasher,
+    /// withhold the admin role from sessions started without TOTP
+    pub admin_totp_required: bool,
+    /// wakes the task that re-ranks the leaderboards, see `api::leaderboards::rank_updater`
+    pub rank_updates: tokio::sync::Notify,
 }
@@ -1,23 +1,29 @@
 use axum::{
     error_handling::HandleErrorLayer,
-    routing::{get, post},
+    middleware,
+    routing::{delete, get, post, put},
     Router, http::StatusCode, extract::DefaultBodyLimit,
 };
 use axum_server::tls_rustls::RustlsConfig;
 use cergdb::{
-    api::{self, admin::insert_new_user, users::find_user},
+    api::{self, admin::insert_new_user},
+    jwt::JwtKeys,
     models::auth::User,
+    mtls::{self, ClientCertAcceptor},
+    password::{PasswordConfig, PasswordHasher},
+    rate_limit::{self, RateLimitConfig, RateLimiter},
+    storage::ArtifactStore,
     AppState, MIGRATOR,
 };
 use clap::Parser;
 use miette::IntoDiagnostic;
-use secrecy::Secret;
 use sqlx::postgres::PgPoolOptions;
+use tokio::sync::Notify;
 use tower::{BoxError, ServiceBuilder};
 use tower_http::trace::TraceLayer;
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
 
-use std::{env, fs, path::PathBuf, sync::Arc, time::Duration};
+use std::{env, fs, net::SocketAddr, path::PathBuf, sync::Arc, time::Duration};
 
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
@@ -36,6 +42,9 @@ struct Args {
 
     #[arg(short, long, value_name = "DIR")]
     logs_dir: Option<PathBuf>,
+
+    #[arg(short, long, value_name = "DIR")]
+    artifacts_dir: Option<PathBuf>,
 }
 
 #[tokio::main]
@@ -78,46 +87,184 @@ async fn main() -> miette::Result<()> {
         .await
         .expect("unable to connect to database");
 
-    let secret = Secret::from(
-        env::var("SECRET").unwrap_or(
-            fs::read_to_string(args.secret.unwrap_or(root_path.join("SECRET")))
-                .expect("Could not open SECRET file."),
-        ),
+    let secret_path = args.secret.unwrap_or(root_path.join("SECRET"));
+    let jwt_keys = JwtKeys::from_env(|| {
+        env::var("SECRET").or_else(|_| {
+            fs::read_to_string(&secret_path)
+                .map_err(|e| format!("could not open SECRET file {}: {e}", secret_path.display()))
+        })
+    })
+    .map_err(|e| miette::miette!("{e}"))?;
+    let (kid, algorithm) = jwt_keys.signing_key();
+    log::info!(
+        "signing tokens with key {kid} ({algorithm:?}), accepting keys {:?}",
+        jwt_keys.verification_key_ids()
     );
 
     MIGRATOR.run(&pool).await.into_diagnostic()?;
+    api::timing::index_missing(&pool).await.into_diagnostic()?;
+
+    let artifacts = ArtifactStore::new(
+        args.artifacts_dir
+            .or(env::var("ARTIFACTS_DIR").ok().map(PathBuf::from))
+            .unwrap_or(root_path.join("artifacts")),
+    );
+    artifacts.init().await.into_diagnostic()?;
 
-    let state = Arc::new(AppState { pool, secret });
+    let best_run_rule = env::var("BEST_RUN_RULE")
+        .map_or(Ok(Default::default()), |rule| rule.parse())
+        .map_err(|e| miette::miette!("{e}"))?;
+
+    let rate_limits = RateLimitConfig::from_env().map_err(|e| miette::miette!("{e}"))?;
+    log::info!("rate limits: {rate_limits:?}");
+
+    let password_config = PasswordConfig::from_env().map_err(|e| miette::miette!("{e}"))?;
+    log::info!(
+        "password hashing: {} with {} KiB, {} iterations, parallelism {}{}",
+        password_config.variant.as_lowercase_str(),
+        password_config.memory,
+        password_config.iterations,
+        password_config.parallelism,
+        if password_config.pepper.is_some() { " and a pepper" } else { "" }
+    );
 
-    if find_user(&state.pool, "admin").await.is_err() {
+    let admin_totp_required = env::var("ADMIN_TOTP_REQUIRED")
+        .map_or(Ok(true), |required| required.trim().parse())
+        .map_err(|e| miette::miette!("invalid value of ADMIN_TOTP_REQUIRED: {e}"))?;
+    if !admin_totp_required {
+        log::warn!("admins can log in without two-factor authentication");
+    }
+
+    let state = Arc::new(AppState {
+        pool,
+        jwt_keys,
+        artifacts,
+        best_run_rule,
+        rate_limiter: RateLimiter::new(rate_limits),
+        password_hasher: PasswordHasher::new(password_config),
+        admin_totp_required,
+        rank_updates: Notify::new(),
+    });
+    tokio::spawn(api::leaderboards::rank_updater(state.clone()));
+
+    // the bootstrap admin may have been renamed, so look for any admin
+    let admin_exists: bool = sqlx::query_scalar(
+        r#"
+        SELECT EXISTS(SELECT 1 FROM user_roles WHERE role = 'admin')
+            OR EXISTS(SELECT 1 FROM users WHERE email = 'admin')
+        "#,
+    )
+    .fetch_one(&state.pool)
+    .await
+    .into_diagnostic()?;
+    if !admin_exists {
         let password = env::var("ADMIN_PASSWORD").unwrap_or(
             fs::read_to_string(args.password.unwrap_or(root_path.join("PASSWORD")))
                 .expect("Could not open PASSWORD file."),
         );
         log::info!("setting admin password");
         let admin = User {
+            id: Default::default(),
             email: "admin".to_owned(),
-            password: password,
+            password,
             name: "Administrator".to_owned(),
-            is_admin: true,
+            roles: vec!["admin".to_owned()],
+            disabled: false,
         };
         let mut transaction = state.pool.begin().await.into_diagnostic()?;
         let new_user_id = insert_new_user(&state, &mut transaction, &admin)
             .await
             .into_diagnostic()?;
         transaction.commit().await.into_diagnostic()?;
-        assert!(new_user_id == "admin");
+        log::info!("created admin user {new_user_id}");
     }
 
     let app = Router::new()
         .route("/", get(api::info::route_info))
         .route("/login", post(api::users::login))
+        .route("/login/totp", post(api::totp::login))
+        .route("/refresh", post(api::users::refresh))
+        .route("/logout", post(api::users::logout))
         .route("/register", post(api::admin::register))
-        .route("/user_profile", get(api::users::user_profile))
+        .route(
+            "/user_profile",
+            get(api::users::user_profile).patch(api::users::update_profile),
+        )
+        .route("/user_profile/password", post(api::users::change_password))
+        .route(
+            "/user_profile/totp",
+            post(api::totp::enroll).delete(api::totp::disable),
+        )
+        .route("/user_profile/totp/verify", post(api::totp::verify))
+        .route("/users", get(api::admin::list_users))
+        .route(
+            "/users/:user",
+            delete(api::admin::delete_user).patch(api::admin::update),
+        )
+        .route("/users/:user/disable", post(api::admin::disable_user))
+        .route("/users/:user/enable", post(api::admin::enable_user))
+        .route("/users/:user/password", put(api::admin::reset_password))
+        .route("/users/:user/totp", delete(api::admin::reset_totp))
+        .route(
+            "/users/:user/roles",
+            get(api::admin::get_roles).put(api::admin::put_roles),
+        )
+        .route(
+            "/certificates",
+            get(api::certificates::list).post(api::certificates::create),
+        )
+        .route("/certificates/:id", delete(api::certificates::delete))
+        .route("/tokens", get(api::tokens::list).post(api::tokens::create))
+        .route("/tokens/:id", delete(api::tokens::revoke))
         .route("/submit", post(api::submit::submit))
         .route("/delete", post(api::delete))
         .route("/retrieve", post(api::retrieve))
         .route("/rename", post(api::rename))
+        .route("/timing", post(api::timing::query))
+        .route("/pareto", post(api::pareto::pareto))
+        .route("/compare", get(api::compare::compare))
+        .route("/leaderboards", get(api::leaderboards::list))
+        .route(
+            "/leaderboards/:name",
+            get(api::leaderboards::get)
+                .put(api::leaderboards::put)
+                .delete(api::leaderboards::delete),
+        )
+        .route(
+            "/devices",
+            get(api::devices::list).post(api::devices::create),
+        )
+        .route(
+            "/devices/:part",
+            get(api::devices::get)
+                .put(api::devices::update)
+                .delete(api::devices::delete),
+        )
+        .route(
+            "/results/:id/design",
+            get(api::design::get).put(api::design::upload),
+        )
+        .route("/results/:id/timing/fit", get(api::analysis::fit_cycles))
+        .route(
+            "/results/:id/synthesis/reports",
+            post(api::synthesis::upload_reports),
+        )
+        .route(
+            "/results/:id/synthesis/runs",
+            get(api::synthesis::list_runs).post(api::synthesis::submit_runs),
+        )
+        .route(
+            "/results/:id/synthesis/runs/:run_id",
+            delete(api::synthesis::delete_run),
+        )
+        .route(
+            "/results/:id/artifacts",
+            get(api::artifacts::list).post(api::artifacts::upload),
+        )
+        .route(
+            "/results/:id/artifacts/:filename",
+            get(api::artifacts::download).delete(api::artifacts::delete),
+        )
         .layer(
             ServiceBuilder::new()
                 .layer(HandleErrorLayer::new(|error: BoxError| async move {
@@ -134,6 +281,10 @@ async fn main() -> miette::Result<()> {
                 .layer(TraceLayer::new_for_http())
                 .into_inner(),
         )
+        .layer(middleware::from_fn_with_state(
+            state.clone(),
+            rate_limit::limit_requests,
+        ))
         .layer(DefaultBodyLimit::disable())
         .with_state(state);
 
@@ -157,10 +308,31 @@ async fn main() -> miette::Result<()> {
         port
     );
 
-    let addr = std::net::SocketAddr::new(ip, port);
+    let addr = SocketAddr::new(ip, port);
     tracing::debug!("listening on {}", &addr);
     // let server = axum::Server::bind(&addr);
-    if tls {
+    if let (true, Ok(client_ca)) = (tls, env::var("TLS_CLIENT_CA_PEM")) {
+        let required = env::var("TLS_CLIENT_CERT_REQUIRED")
+            .map_or(Ok(false), |required| required.trim().parse())
+            .map_err(|e| miette::miette!("invalid value of TLS_CLIENT_CERT_REQUIRED: {e}"))?;
+        let config = mtls::server_config(
+            &PathBuf::from(env::var("TLS_CERT_PEM").into_diagnostic()?),
+            &PathBuf::from(env::var("TLS_KEY_PEM").into_diagnostic()?),
+            &PathBuf::from(&client_ca),
+            required,
+        )
+        .map_err(|e| miette::miette!("{e}"))?;
+        log::info!(
+            "{} client certificates signed by {client_ca}",
+            if required { "requiring" } else { "accepting" }
+        );
+
+        axum_server::bind(addr)
+            .acceptor(ClientCertAcceptor::new(config))
+            .serve(app.into_make_service_with_connect_info::<SocketAddr>())
+            .await
+            .expect("failed to start TLS server");
+    } else if tls {
         let config = RustlsConfig::from_pem_file(
             PathBuf::from(env::var("TLS_CERT_PEM").into_diagnostic()?),
             PathBuf::from(env::var("TLS_KEY_PEM").into_diagnostic()?),
@@ -169,12 +341,12 @@ async fn main() -> miette::Result<()> {
         .into_diagnostic()?;
 
         axum_server::bind_rustls(addr, config)
-            .serve(app.into_make_service())
+            .serve(app.into_make_service_with_connect_info::<SocketAddr>())
             .await
             .expect("failed to start TLS server");
     } else {
         axum_server::bind(addr)
-            .serve(app.into_make_service())
+            .serve(app.into_make_service_with_connect_info::<SocketAddr>())
             .await
             .expect("failed to start server");
     };
@@ -0,0 +1,14 @@
+use serde::Serialize;
+use time::OffsetDateTime;
+
+#[derive(Debug, Serialize, sqlx::FromRow)]
+pub struct Artifact {
+    pub result_id: String,
+    pub filename: String,
+    pub sha256: String,
+    pub mime_type: String,
+    pub size: i64,
+    pub uploader: String,
+    #[serde(with = "time::serde::rfc3339")]
+    pub timestamp: OffsetDateTime,
+}
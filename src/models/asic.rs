@@ -0,0 +1,60 @@
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+/// Results of an ASIC implementation on a given standard-cell library.
+///
+/// `gate_equivalents` is always computed by the server from `area` and `nand2_area`.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct AsicResults {
+    /// technology node in nanometers
+    pub technology_node: f64,
+    /// standard-cell library, e.g. `NanGate45` or `tcbn65lp`
+    pub library: String,
+    /// cell area in µm²
+    pub area: f64,
+    /// area of a 2-input NAND gate of the library in µm²
+    pub nand2_area: f64,
+    /// total power in mW
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub power: Option<f64>,
+    /// frequency in MHz at which `power` was estimated
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub power_frequency: Option<f64>,
+    /// maximum clock frequency in MHz
+    #[serde(rename = "Fmax", default, skip_serializing_if = "Option::is_none")]
+    pub fmax: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub gate_equivalents: Option<f64>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+impl AsicResults {
+    pub fn validate(&self) -> Vec<String> {
+        let mut errors = Vec::new();
+        let positive = [
+            ("technology_node", Some(self.technology_node)),
+            ("area", Some(self.area)),
+            ("nand2_area", Some(self.nand2_area)),
+            ("power_frequency", self.power_frequency),
+            ("Fmax", self.fmax),
+        ];
+        for (name, value) in positive {
+            if let Some(value) = value.filter(|v| v.is_nan() || *v <= 0.0) {
+                errors.push(format!("{name} must be positive, got {value}"));
+            }
+        }
+        if let Some(power) = self.power.filter(|p| p.is_nan() || *p < 0.0) {
+            errors.push(format!("power must not be negative, got {power}"));
+        }
+        if self.library.trim().is_empty() {
+            errors.push("library must not be empty".to_owned());
+        }
+        errors
+    }
+
+    /// Area normalized to the NAND2 area of the library.
+    pub fn compute_gate_equivalents(&mut self) {
+        self.gate_equivalents = Some(self.area / self.nand2_area);
+    }
+}
@@ -1,70 +1,293 @@
+use std::{marker::PhantomData, sync::Arc};
+
 use axum::{
     async_trait,
-    extract::FromRequestParts,
+    extract::{FromRef, FromRequestParts},
     headers::{authorization::Bearer, Authorization},
     http::request::Parts,
     RequestPartsExt, TypedHeader,
 };
-use jsonwebtoken::{decode, DecodingKey, EncodingKey, Validation};
+use time::OffsetDateTime;
+use uuid::Uuid;
 
-use crate::{error::AppError, KEYS};
+use super::{
+    roles::{Permission, RequiredPermission, Role},
+    tokens::{self, Scope, TOKEN_PREFIX},
+};
+use crate::{error::AppError, mtls::ClientCertificate, AppState};
 use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Deserialize, sqlx::FromRow, Serialize)]
 pub struct User {
+    #[serde(skip)]
+    #[sqlx(default)]
+    pub id: Uuid,
     pub email: String,
     #[serde(skip_serializing)]
     pub password: String,
     #[serde(default)]
     pub name: String,
+    /// names of the roles in `user_roles`
     #[serde(default)]
-    pub is_admin: bool,
+    #[sqlx(default)]
+    pub roles: Vec<String>,
+    #[serde(skip)]
+    #[sqlx(default)]
+    pub disabled: bool,
+}
+
+impl User {
+    /// The roles of the user, ignoring unknown names.
+    pub fn parsed_roles(&self) -> Vec<Role> {
+        self.roles.iter().filter_map(|r| r.parse().ok()).collect()
+    }
+}
+
+/// A user as shown to admins and in their profile.
+#[derive(Debug, sqlx::FromRow, Serialize)]
+pub struct UserInfo {
+    pub id: Uuid,
+    pub email: String,
+    pub name: Option<String>,
+    pub roles: Vec<String>,
+    pub disabled: bool,
+    #[serde(with = "time::serde::rfc3339")]
+    pub created_at: OffsetDateTime,
+    #[serde(with = "time::serde::rfc3339::option")]
+    pub last_login: Option<OffsetDateTime>,
+}
+
+impl UserInfo {
+    /// Columns of `users` to select a `UserInfo`.
+    pub const COLUMNS: &'static str = r#"id, email, name, disabled, created_at, last_login,
+        ARRAY(SELECT role FROM user_roles WHERE user_id = users.id ORDER BY role) AS roles"#;
 }
 
 #[derive(Debug, Deserialize, Serialize)]
 pub struct Claims {
+    /// id of the user
+    pub sub: Uuid,
+    /// email of the user when the token was issued
     pub username: String,
-    pub is_admin: bool,
+    #[serde(default)]
+    pub roles: Vec<Role>,
+    /// set when authenticated with an API token, which is limited to these scopes
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scopes: Option<Vec<Scope>>,
+    /// unique id of a login token, which `/logout` adds to the revocation list
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub jti: String,
+    /// the login session the token was issued for, shared with its refresh tokens
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sid: Option<String>,
+    /// when a login token was issued, with the fraction of the second so that it can be compared
+    /// with the time the sessions of the user were ended
+    #[serde(default)]
+    pub iat: f64,
     pub exp: u64,
 }
 
 impl std::fmt::Display for Claims {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "Username: {}, IsAdmin: {}", self.username, self.is_admin)
+        write!(f, "Username: {}, Roles: {:?}", self.username, self.roles)
+    }
+}
+
+impl Claims {
+    pub fn has_permission(&self, permission: Permission) -> bool {
+        let in_scope = match self.scopes {
+            Some(ref scopes) => scopes.iter().any(|scope| scope.permission() == permission),
+            None => true,
+        };
+        in_scope
+            && self
+                .roles
+                .iter()
+                .any(|role| role.permissions().contains(&permission))
+    }
+
+    pub fn require(&self, permission: Permission) -> Result<(), AppError> {
+        if !self.has_permission(permission) {
+            return Err(AppError::Forbidden(format!(
+                "User {} does not have the {permission} permission",
+                self.username
+            )));
+        }
+        Ok(())
+    }
+
+    /// Check that the user may submit results of `category`, which API tokens restrict with
+    /// their `submit:<pattern>` scopes.
+    pub fn require_category(&self, category: Option<&str>) -> Result<(), AppError> {
+        self.require(Permission::SubmitResults)?;
+        match self.scopes {
+            Some(ref scopes) if !scopes.iter().any(|s| s.allows_category(category)) => {
+                Err(AppError::Forbidden(format!(
+                    "the API token of {} does not allow submitting to category {}",
+                    self.username,
+                    category.unwrap_or("<none>")
+                )))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Look up an API token, recording its use.
+    async fn from_api_token(state: &AppState, token: &str) -> Result<Self, AppError> {
+        let now = OffsetDateTime::now_utc();
+        let (sub, username, scopes, roles, expires): (
+            Uuid,
+            String,
+            Vec<String>,
+            Vec<String>,
+            OffsetDateTime,
+        ) = sqlx::query_as(
+            r#"
+            UPDATE api_tokens t
+            SET last_used = $2
+            WHERE token_hash = $1 AND expires > $2
+                AND EXISTS(SELECT 1 FROM users WHERE id = t.user_id AND NOT disabled)
+            RETURNING user_id, (SELECT email FROM users WHERE id = t.user_id), scopes,
+                ARRAY(SELECT role FROM user_roles WHERE user_id = t.user_id) AS roles,
+                expires
+            "#,
+        )
+        .bind(tokens::hash(token))
+        .bind(now)
+        .fetch_optional(&state.pool)
+        .await?
+        .ok_or(AppError::InvalidToken)?;
+        Ok(Claims {
+            sub,
+            username,
+            roles: roles.iter().filter_map(|r| r.parse().ok()).collect(),
+            scopes: Some(scopes.iter().filter_map(|s| s.parse().ok()).collect()),
+            jti: String::new(),
+            sid: None,
+            iat: 0.0,
+            exp: expires.unix_timestamp() as u64,
+        })
+    }
+
+    /// Look up the user a verified client certificate is mapped to by one of its identities,
+    /// recording its use. Like API tokens, certificates are limited to their scopes.
+    async fn from_client_certificate(
+        state: &AppState,
+        identities: &[String],
+    ) -> Result<Self, AppError> {
+        let now = OffsetDateTime::now_utc();
+        let mapped: Vec<(Uuid, String, Vec<String>, Vec<String>)> = sqlx::query_as(
+            r#"
+            UPDATE client_certificates c
+            SET last_used = $2
+            WHERE identity = ANY($1)
+                AND EXISTS(SELECT 1 FROM users WHERE id = c.user_id AND NOT disabled)
+            RETURNING user_id, (SELECT email FROM users WHERE id = c.user_id), scopes,
+                ARRAY(SELECT role FROM user_roles WHERE user_id = c.user_id) AS roles
+            "#,
+        )
+        .bind(identities)
+        .bind(now)
+        .fetch_all(&state.pool)
+        .await?;
+        let Some((sub, username, _, roles)) = mapped.first().cloned() else {
+            tracing::warn!("no user for the client certificate {identities:?}");
+            return Err(AppError::InvalidToken);
+        };
+        if mapped.iter().any(|(user, ..)| *user != sub) {
+            tracing::warn!("the client certificate {identities:?} is mapped to several users");
+            return Err(AppError::InvalidToken);
+        }
+        let mut scopes: Vec<Scope> = mapped
+            .iter()
+            .flat_map(|(_, _, scopes, _)| scopes.iter().filter_map(|s| s.parse().ok()))
+            .collect();
+        scopes.dedup();
+        Ok(Claims {
+            sub,
+            username,
+            roles: roles.iter().filter_map(|r| r.parse().ok()).collect(),
+            scopes: Some(scopes),
+            jti: String::new(),
+            sid: None,
+            iat: 0.0,
+            exp: now.unix_timestamp() as u64,
+        })
     }
 }
 
 #[async_trait]
 impl<S> FromRequestParts<S> for Claims
 where
+    Arc<AppState>: FromRef<S>,
     S: Send + Sync,
 {
     type Rejection = AppError;
 
-    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let state = Arc::<AppState>::from_ref(state);
         // Extract the token from the authorization header
-        let TypedHeader(Authorization(bearer)) = parts
-            .extract::<TypedHeader<Authorization<Bearer>>>()
-            .await
-            .map_err(|_| AppError::InvalidToken)?;
+        let bearer = match parts.extract::<TypedHeader<Authorization<Bearer>>>().await {
+            Ok(TypedHeader(Authorization(bearer))) => bearer,
+            // without a token, fall back to the client certificate of the connection
+            Err(_) => {
+                return match parts.extensions.get::<ClientCertificate>() {
+                    Some(cert) if !cert.identities.is_empty() => {
+                        Claims::from_client_certificate(&state, &cert.identities).await
+                    }
+                    _ => Err(AppError::InvalidToken),
+                }
+            }
+        };
+        if bearer.token().starts_with(TOKEN_PREFIX) {
+            return Claims::from_api_token(&state, bearer.token()).await;
+        }
         // Decode the user data
-        let token_data = decode::<Claims>(bearer.token(), &KEYS.decoding, &Validation::default())
-            .map_err(|_| AppError::InvalidToken)?;
+        let claims: Claims = state.jwt_keys.decode(bearer.token())?;
+        // tokens without an id predate the revocation list
+        if claims.jti.is_empty() {
+            return Err(AppError::InvalidToken);
+        }
+        // tokens of disabled and deleted users are rejected as well, and so are tokens issued
+        // before the sessions of the user were ended
+        let revoked: bool = sqlx::query_scalar(
+            r#"
+            SELECT EXISTS(SELECT 1 FROM revoked_tokens WHERE jti = $1)
+                OR NOT EXISTS(
+                    SELECT 1 FROM users
+                    WHERE id = $2 AND NOT disabled
+                        AND (tokens_valid_after IS NULL OR tokens_valid_after <= to_timestamp($3))
+                )
+            "#,
+        )
+        .bind(&claims.jti)
+        .bind(claims.sub)
+        .bind(claims.iat)
+        .fetch_one(&state.pool)
+        .await?;
+        if revoked {
+            return Err(AppError::InvalidToken);
+        }
 
-        Ok(token_data.claims)
+        Ok(claims)
     }
 }
 
-pub struct Keys {
-    pub encoding: EncodingKey,
-    pub decoding: DecodingKey,
-}
+/// The claims of a user that has the permission `P`, e.g. `RequirePermission<DeleteResult>`.
+pub struct RequirePermission<P>(pub Claims, pub PhantomData<P>);
 
-impl Keys {
-    pub fn new(secret: &[u8]) -> Self {
-        Self {
-            encoding: EncodingKey::from_secret(secret),
-            decoding: DecodingKey::from_secret(secret),
-        }
+#[async_trait]
+impl<S, P> FromRequestParts<S> for RequirePermission<P>
+where
+    Arc<AppState>: FromRef<S>,
+    S: Send + Sync,
+    P: RequiredPermission,
+{
+    type Rejection = AppError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let claims = Claims::from_request_parts(parts, state).await?;
+        claims.require(P::PERMISSION)?;
+        Ok(Self(claims, PhantomData))
     }
 }
@@ -0,0 +1,327 @@
+use serde::Serialize;
+
+use super::timing::TimingRow;
+
+/// Block sizes in bytes used to count the AD and message blocks of a measurement.
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct BlockSizes {
+    pub ad: i64,
+    pub msg: i64,
+}
+
+impl BlockSizes {
+    fn blocks(&self, row: &TimingRow) -> [f64; 2] {
+        [
+            ceil_div(row.ad_len, self.ad) as f64,
+            ceil_div(row.msg_len, self.msg) as f64,
+        ]
+    }
+}
+
+fn ceil_div(len: i64, block: i64) -> i64 {
+    (len + block - 1).div_euclid(block)
+}
+
+#[derive(Debug, Serialize)]
+pub struct Residual {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub msg_id: Option<i64>,
+    pub ad_len: i64,
+    pub msg_len: i64,
+    pub cycles: i64,
+    pub predicted: f64,
+    pub residual: f64,
+    /// the measurement deviates from the fit and is probably wrong
+    pub outlier: bool,
+}
+
+/// `cycles = a + b·⌈AD/blk⌉ + c·⌈M/blk⌉` fitted to the measurements of one operation.
+///
+/// `b` or `c` is `None` when the measurements do not vary the corresponding block count,
+/// e.g. `b` for hash measurements.
+#[derive(Debug, Serialize)]
+pub struct CycleFit {
+    pub operation: String,
+    pub block_bytes: BlockSizes,
+    pub a: f64,
+    pub b: Option<f64>,
+    pub c: Option<f64>,
+    pub formula: String,
+    /// root mean square of the residuals of the measurements that are not outliers
+    pub rmse: f64,
+    pub measurements: usize,
+    pub outliers: usize,
+    pub residuals: Vec<Residual>,
+}
+
+/// Solve the normal equations `XᵀX β = Xᵀy` by Gaussian elimination with partial pivoting.
+fn least_squares(xs: &[Vec<f64>], ys: &[f64]) -> Option<Vec<f64>> {
+    let n = xs.first()?.len();
+    let mut m = vec![vec![0.0; n + 1]; n];
+    for (x, y) in xs.iter().zip(ys) {
+        for (row, xi) in m.iter_mut().zip(x) {
+            for (cell, xj) in row.iter_mut().zip(x) {
+                *cell += xi * xj;
+            }
+            row[n] += xi * y;
+        }
+    }
+    for col in 0..n {
+        let pivot = (col..n).max_by(|&i, &j| m[i][col].abs().total_cmp(&m[j][col].abs()))?;
+        if m[pivot][col].abs() < 1e-9 {
+            return None;
+        }
+        m.swap(col, pivot);
+        let pivot_row = m[col].clone();
+        for (i, row) in m.iter_mut().enumerate() {
+            if i != col {
+                let factor = row[col] / pivot_row[col];
+                for (cell, p) in row.iter_mut().zip(&pivot_row).skip(col) {
+                    *cell -= factor * p;
+                }
+            }
+        }
+    }
+    Some(
+        m.iter()
+            .enumerate()
+            .map(|(i, row)| row[n] / row[i])
+            .collect(),
+    )
+}
+
+fn median(values: &mut [f64]) -> f64 {
+    values.sort_by(f64::total_cmp);
+    let mid = values.len() / 2;
+    if values.len().is_multiple_of(2) {
+        (values[mid - 1] + values[mid]) / 2.0
+    } else {
+        values[mid]
+    }
+}
+
+/// Flag the residuals further than `threshold` robust standard deviations (from the
+/// median absolute deviation) from the median residual. Deviations below one cycle are
+/// never flagged.
+fn outliers(residuals: &[f64], threshold: f64) -> Vec<bool> {
+    let center = median(&mut residuals.to_vec());
+    let mut deviations: Vec<f64> = residuals.iter().map(|r| (r - center).abs()).collect();
+    let sigma = 1.4826 * median(&mut deviations);
+    residuals
+        .iter()
+        .map(|r| {
+            let deviation = (r - center).abs();
+            deviation >= 1.0 && deviation > threshold * sigma
+        })
+        .collect()
+}
+
+/// Fit the cycle formula to `rows` of a single operation.
+///
+/// The coefficients are fitted once, measurements deviating by more than `threshold`
+/// robust standard deviations are flagged, and the fit is repeated without them.
+pub fn fit(
+    operation: &str,
+    rows: &[&TimingRow],
+    block_bytes: BlockSizes,
+    threshold: f64,
+) -> Result<CycleFit, String> {
+    let blocks: Vec<[f64; 2]> = rows.iter().map(|row| block_bytes.blocks(row)).collect();
+    let cycles: Vec<f64> = rows.iter().map(|row| row.cycles as f64).collect();
+
+    // block counts that do not vary cannot be told apart from the constant term
+    let varies: Vec<bool> = (0..2)
+        .map(|k| blocks.iter().any(|b| b[k] != blocks[0][k]))
+        .collect();
+    let design_row = |b: &[f64; 2]| {
+        let mut x = vec![1.0];
+        x.extend((0..2).filter(|&k| varies[k]).map(|k| b[k]));
+        x
+    };
+    let unknowns = 1 + varies.iter().filter(|v| **v).count();
+    if rows.len() < unknowns {
+        return Err(format!(
+            "{operation}: {} measurements are not enough to fit {unknowns} coefficients",
+            rows.len()
+        ));
+    }
+
+    let solve = |included: &[bool]| -> Result<Vec<f64>, String> {
+        let (xs, ys): (Vec<Vec<f64>>, Vec<f64>) = blocks
+            .iter()
+            .zip(&cycles)
+            .zip(included)
+            .filter(|(_, included)| **included)
+            .map(|((b, c), _)| (design_row(b), *c))
+            .unzip();
+        least_squares(&xs, &ys).ok_or_else(|| {
+            format!(
+                "{operation}: the AD and message block counts of the measurements are collinear"
+            )
+        })
+    };
+    let predict = |beta: &[f64], b: &[f64; 2]| -> f64 {
+        design_row(b).iter().zip(beta).map(|(x, c)| x * c).sum()
+    };
+    let residuals = |beta: &[f64]| -> Vec<f64> {
+        blocks
+            .iter()
+            .zip(&cycles)
+            .map(|(b, c)| c - predict(beta, b))
+            .collect()
+    };
+
+    let mut beta = solve(&vec![true; rows.len()])?;
+    let flagged = outliers(&residuals(&beta), threshold);
+    let inliers: Vec<bool> = flagged.iter().map(|o| !o).collect();
+    if flagged.iter().any(|o| *o) && inliers.iter().filter(|i| **i).count() >= unknowns {
+        if let Ok(refit) = solve(&inliers) {
+            beta = refit;
+        }
+    }
+    let final_residuals = residuals(&beta);
+    let flagged = outliers(&final_residuals, threshold);
+
+    let mut coefficients = beta.iter().copied();
+    let a = coefficients.next().unwrap_or_default();
+    let b = if varies[0] { coefficients.next() } else { None };
+    let c = if varies[1] { coefficients.next() } else { None };
+
+    let mut formula = format!("cycles = {a:.2}");
+    if let Some(b) = b {
+        formula.push_str(&format!(" + {b:.2}·⌈AD/{}⌉", block_bytes.ad));
+    }
+    if let Some(c) = c {
+        formula.push_str(&format!(" + {c:.2}·⌈M/{}⌉", block_bytes.msg));
+    }
+
+    let inlier_residuals: Vec<f64> = final_residuals
+        .iter()
+        .zip(&flagged)
+        .filter(|(_, outlier)| !**outlier)
+        .map(|(r, _)| *r)
+        .collect();
+    let rmse = if inlier_residuals.is_empty() {
+        0.0
+    } else {
+        (inlier_residuals.iter().map(|r| r * r).sum::<f64>() / inlier_residuals.len() as f64).sqrt()
+    };
+
+    let residuals: Vec<Residual> = rows
+        .iter()
+        .zip(final_residuals)
+        .zip(&flagged)
+        .map(|((row, residual), outlier)| Residual {
+            msg_id: row.msg_id,
+            ad_len: row.ad_len,
+            msg_len: row.msg_len,
+            cycles: row.cycles,
+            predicted: row.cycles as f64 - residual,
+            residual,
+            outlier: *outlier,
+        })
+        .collect();
+
+    Ok(CycleFit {
+        operation: operation.to_owned(),
+        block_bytes,
+        a,
+        b,
+        c,
+        formula,
+        rmse,
+        measurements: residuals.len(),
+        outliers: flagged.iter().filter(|o| **o).count(),
+        residuals,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::Map;
+
+    use super::*;
+
+    const BLOCKS: BlockSizes = BlockSizes { ad: 16, msg: 16 };
+
+    fn row(ad_len: i64, msg_len: i64, cycles: i64) -> TimingRow {
+        TimingRow {
+            msg_id: None,
+            operation: "enc".to_owned(),
+            ad_len,
+            msg_len,
+            cycles,
+            extra: Map::new(),
+        }
+    }
+
+    /// `cycles = 10 + 3·⌈AD/16⌉ + 5·⌈M/16⌉`
+    fn exact(ad_len: i64, msg_len: i64) -> TimingRow {
+        row(
+            ad_len,
+            msg_len,
+            10 + 3 * ceil_div(ad_len, 16) + 5 * ceil_div(msg_len, 16),
+        )
+    }
+
+    fn assert_close(actual: f64, expected: f64) {
+        assert!((actual - expected).abs() < 1e-6, "{actual} != {expected}");
+    }
+
+    #[test]
+    fn counts_partial_blocks() {
+        assert_eq!(ceil_div(0, 16), 0);
+        assert_eq!(ceil_div(1, 16), 1);
+        assert_eq!(ceil_div(16, 16), 1);
+        assert_eq!(ceil_div(17, 16), 2);
+    }
+
+    #[test]
+    fn fits_exact_measurements() {
+        let rows = [
+            exact(0, 0),
+            exact(16, 0),
+            exact(0, 16),
+            exact(20, 64),
+            exact(64, 1536),
+        ];
+        let rows: Vec<&TimingRow> = rows.iter().collect();
+        let fit = fit("enc", &rows, BLOCKS, 3.0).unwrap();
+        assert_close(fit.a, 10.0);
+        assert_close(fit.b.unwrap(), 3.0);
+        assert_close(fit.c.unwrap(), 5.0);
+        assert_close(fit.rmse, 0.0);
+        assert_eq!(fit.formula, "cycles = 10.00 + 3.00·⌈AD/16⌉ + 5.00·⌈M/16⌉");
+        assert_eq!(fit.outliers, 0);
+    }
+
+    #[test]
+    fn flags_outliers_and_refits_without_them() {
+        let mut rows: Vec<TimingRow> = (0..8).map(|m| exact(16 * (m % 3), 16 * m)).collect();
+        rows.push(row(0, 32, 500));
+        let rows: Vec<&TimingRow> = rows.iter().collect();
+        let fit = fit("enc", &rows, BLOCKS, 3.0).unwrap();
+        assert_eq!(fit.outliers, 1);
+        assert!(fit.residuals[8].outlier);
+        assert_close(fit.a, 10.0);
+        assert_close(fit.c.unwrap(), 5.0);
+        assert_close(fit.rmse, 0.0);
+    }
+
+    #[test]
+    fn leaves_out_constant_block_counts() {
+        let rows = [exact(0, 16), exact(0, 64), exact(0, 256)];
+        let rows: Vec<&TimingRow> = rows.iter().collect();
+        let fit = fit("hash", &rows, BLOCKS, 3.0).unwrap();
+        assert_eq!(fit.b, None);
+        assert_close(fit.c.unwrap(), 5.0);
+        assert_eq!(fit.formula, "cycles = 10.00 + 5.00·⌈M/16⌉");
+    }
+
+    #[test]
+    fn needs_a_measurement_per_coefficient() {
+        let rows = [exact(0, 16), exact(16, 64)];
+        let rows: Vec<&TimingRow> = rows.iter().collect();
+        assert!(fit("enc", &rows, BLOCKS, 3.0).is_err());
+    }
+}
@@ -0,0 +1,294 @@
+use serde::{Deserialize, Deserializer, Serialize};
+use serde_json::{Map, Value};
+
+/// An LWC `design.toml` design description.
+///
+/// Only the fields needed for indexing and validation are typed, everything else is kept as is.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Design {
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(
+        default,
+        alias = "authors",
+        deserialize_with = "one_or_many",
+        skip_serializing_if = "Vec::is_empty"
+    )]
+    pub author: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+    #[serde(default)]
+    pub language: Language,
+    pub rtl: Rtl,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub lwc: Option<Lwc>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct Language {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub vhdl: Option<LanguageSettings>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub verilog: Option<LanguageSettings>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct LanguageSettings {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub standard: Option<Value>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Rtl {
+    #[serde(default)]
+    pub sources: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub top: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub clock_port: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub clock: Option<Clock>,
+    #[serde(default, alias = "generics", skip_serializing_if = "Map::is_empty")]
+    pub parameters: Map<String, Value>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Clock {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub port: Option<String>,
+    /// clock period in nanoseconds
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub period: Option<f64>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct Lwc {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub aead: Option<Algorithm>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hash: Option<Algorithm>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ports: Option<Ports>,
+    /// block sizes in bits, keyed by segment type (`AD`, `PT`, `HM`)
+    #[serde(default, skip_serializing_if = "Map::is_empty")]
+    pub block_bits: Map<String, Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ccw: Option<i64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ccsw: Option<i64>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Algorithm {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub algorithm: Option<String>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct Ports {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pdi: Option<Port>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sdi: Option<Port>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rdi: Option<Port>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Port {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bit_width: Option<i64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub num_shares: Option<i64>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+fn one_or_many<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<String>, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum OneOrMany {
+        One(String),
+        Many(Vec<String>),
+    }
+    Ok(match OneOrMany::deserialize(deserializer)? {
+        OneOrMany::One(s) => vec![s],
+        OneOrMany::Many(v) => v,
+    })
+}
+
+impl Design {
+    fn parameter(&self, names: &[&str]) -> Option<i64> {
+        names
+            .iter()
+            .find_map(|name| self.rtl.parameters.get(*name))
+            .and_then(|v| v.as_i64().or_else(|| v.as_str()?.trim().parse().ok()))
+    }
+
+    fn port_width(&self, port: impl Fn(&Ports) -> Option<&Port>) -> Option<i64> {
+        self.lwc
+            .as_ref()?
+            .ports
+            .as_ref()
+            .and_then(port)
+            .and_then(|p| p.bit_width)
+    }
+
+    /// `vhdl`, `verilog` or `mixed`, falling back to the extensions of the sources
+    pub fn language_name(&self) -> Option<String> {
+        let has_ext = |exts: &[&str]| {
+            self.rtl
+                .sources
+                .iter()
+                .any(|s| exts.iter().any(|ext| s.to_lowercase().ends_with(ext)))
+        };
+        let vhdl = self.language.vhdl.is_some() || has_ext(&[".vhd", ".vhdl"]);
+        let verilog = self.language.verilog.is_some() || has_ext(&[".v", ".sv"]);
+        match (vhdl, verilog) {
+            (true, true) => Some("mixed".to_owned()),
+            (true, false) => Some("vhdl".to_owned()),
+            (false, true) => Some("verilog".to_owned()),
+            (false, false) => None,
+        }
+    }
+
+    pub fn clock_port(&self) -> Option<&str> {
+        self.rtl
+            .clock_port
+            .as_deref()
+            .or_else(|| self.rtl.clock.as_ref()?.port.as_deref())
+    }
+
+    pub fn clock_period(&self) -> Option<f64> {
+        self.rtl.clock.as_ref()?.period
+    }
+
+    /// public data input width (`W`)
+    pub fn w(&self) -> Option<i64> {
+        self.port_width(|p| p.pdi.as_ref())
+            .or_else(|| self.parameter(&["W", "G_W"]))
+    }
+
+    /// secret data input width (`SW`)
+    pub fn sw(&self) -> Option<i64> {
+        self.port_width(|p| p.sdi.as_ref())
+            .or_else(|| self.parameter(&["SW", "G_SW"]))
+    }
+
+    /// cipher core data width (`CCW`)
+    pub fn ccw(&self) -> Option<i64> {
+        self.lwc
+            .as_ref()
+            .and_then(|lwc| lwc.ccw)
+            .or_else(|| self.parameter(&["CCW", "G_CCW"]))
+    }
+
+    /// cipher core key width (`CCSW`)
+    pub fn ccsw(&self) -> Option<i64> {
+        self.lwc
+            .as_ref()
+            .and_then(|lwc| lwc.ccsw)
+            .or_else(|| self.parameter(&["CCSW", "G_CCSW"]))
+    }
+
+    pub fn algorithm(&self) -> Option<&str> {
+        let lwc = self.lwc.as_ref()?;
+        lwc.aead
+            .as_ref()
+            .or(lwc.hash.as_ref())?
+            .algorithm
+            .as_deref()
+    }
+
+    /// block size in bits of the given segment type, e.g. `AD` or `PT`
+    pub fn block_bits(&self, segment: &str) -> Option<i64> {
+        self.lwc.as_ref()?.block_bits.get(segment)?.as_i64()
+    }
+
+    /// Check the description for missing or inconsistent values.
+    pub fn validate(&self) -> Vec<String> {
+        let mut errors = Vec::new();
+        if self.name.trim().is_empty() {
+            errors.push("name must not be empty".to_owned());
+        }
+        if self.rtl.sources.is_empty() {
+            errors.push("rtl.sources must list at least one source file".to_owned());
+        }
+        if !matches!(self.rtl.top.as_deref(), Some(top) if !top.trim().is_empty()) {
+            errors.push("rtl.top is required".to_owned());
+        }
+        if self.language_name().is_none() {
+            errors.push("could not determine the HDL language".to_owned());
+        }
+        if let Some(period) = self.clock_period() {
+            if period.is_nan() || period <= 0.0 {
+                errors.push(format!("rtl.clock.period must be positive, got {period}"));
+            }
+        }
+        let widths = [
+            ("W", self.w()),
+            ("SW", self.sw()),
+            ("CCW", self.ccw()),
+            ("CCSW", self.ccsw()),
+        ];
+        for (name, width) in widths {
+            if let Some(width) = width {
+                if width <= 0 || width % 8 != 0 || !(width as u64).is_power_of_two() {
+                    errors.push(format!(
+                        "{name} must be a power of two multiple of 8, got {width}"
+                    ));
+                }
+            }
+        }
+        if let (Some(w), Some(ccw)) = (self.w(), self.ccw()) {
+            if w > 0 && ccw > 0 && w % ccw != 0 && ccw % w != 0 {
+                errors.push(format!(
+                    "W ({w}) and CCW ({ccw}) must be multiples of each other"
+                ));
+            }
+        }
+        if let Some(ref lwc) = self.lwc {
+            if lwc.aead.is_none() && lwc.hash.is_none() {
+                errors.push("lwc must describe an aead and/or hash algorithm".to_owned());
+            }
+            for (segment, bits) in &lwc.block_bits {
+                if !matches!(bits.as_i64(), Some(b) if b > 0 && b % 8 == 0) {
+                    errors.push(format!(
+                        "lwc.block_bits.{segment} must be a positive multiple of 8, got {bits}"
+                    ));
+                }
+            }
+        }
+        errors
+    }
+}
+
+/// A row of the `designs` table.
+#[derive(Debug, Serialize, sqlx::FromRow)]
+pub struct DesignRecord {
+    pub result_id: String,
+    pub name: String,
+    pub language: Option<String>,
+    pub top: Option<String>,
+    pub clock_port: Option<String>,
+    pub clock_period: Option<f64>,
+    pub w: Option<i32>,
+    pub sw: Option<i32>,
+    pub ccw: Option<i32>,
+    pub ccsw: Option<i32>,
+    pub algorithm: Option<String>,
+    pub sources: Vec<String>,
+    pub parsed: Value,
+    pub original: String,
+}
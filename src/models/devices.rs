@@ -0,0 +1,78 @@
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+
+/// An FPGA device of the catalog and its resource capacities.
+#[derive(Debug, Clone, Deserialize, Serialize, sqlx::FromRow)]
+pub struct Device {
+    /// part number without package and speed grade, e.g. `xc7a100t` or `lfe5u-25f`
+    #[serde(default)]
+    pub part: String,
+    pub vendor: String,
+    pub family: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub speed_grade: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub lut: Option<i32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ff: Option<i32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub slice: Option<i32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dsp: Option<i32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bram: Option<f64>,
+}
+
+fn normalize(part: &str) -> String {
+    part.chars()
+        .filter(char::is_ascii_alphanumeric)
+        .collect::<String>()
+        .to_lowercase()
+}
+
+impl Device {
+    /// Find the catalog device of a device name as reported by a tool, e.g. `xc7a100tcsg324-1`,
+    /// `7a100tcsg324` or `LFE5U-25F-6BG256C`. The longest matching part number wins.
+    pub fn lookup<'a>(catalog: &'a [Device], device: &str) -> Option<&'a Device> {
+        let device = normalize(device);
+        let candidates = [
+            device.clone(),
+            format!("xc{device}"),
+            format!("ice40{device}"),
+        ];
+        catalog
+            .iter()
+            .filter(|d| {
+                let part = normalize(&d.part);
+                candidates.iter().any(|c| c.starts_with(&part))
+            })
+            .max_by_key(|d| d.part.len())
+    }
+
+    /// Utilization of this device, in percent, by the `lut`, `ff`, `slice`, `dsp` and
+    /// `bram_tile`/`bram` counts of canonical synthesis results.
+    pub fn utilization(&self, results: &Value) -> Value {
+        let mut utilization = Map::new();
+        utilization.insert("device".to_owned(), json!(self.part));
+        let capacities = [
+            ("lut", "lut", self.lut.map(f64::from)),
+            ("ff", "ff", self.ff.map(f64::from)),
+            ("slice", "slice", self.slice.map(f64::from)),
+            ("dsp", "dsp", self.dsp.map(f64::from)),
+            ("bram", "bram_tile", self.bram),
+            ("bram", "bram", self.bram),
+        ];
+        for (name, key, capacity) in capacities {
+            let used = results.get(key).and_then(|v| {
+                v.as_f64()
+                    .or_else(|| v.as_str().and_then(|s| s.trim().parse().ok()))
+            });
+            if let (Some(used), Some(capacity)) = (used, capacity.filter(|c| *c > 0.0)) {
+                utilization
+                    .entry(name)
+                    .or_insert_with(|| json!(100.0 * used / capacity));
+            }
+        }
+        Value::Object(utilization)
+    }
+}
@@ -0,0 +1,85 @@
+use std::cmp::Ordering;
+
+use json_dotpath::DotPaths;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+#[derive(Debug, Clone, Copy, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FilterOp {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Exists,
+}
+
+/// A condition on the value at `path`, e.g.
+/// `{"path": "metrics.enc.long.throughput", "op": "ge", "value": 1000}`.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct FieldFilter {
+    pub path: String,
+    pub op: FilterOp,
+    #[serde(default)]
+    pub value: Value,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct SortKey {
+    pub path: String,
+    #[serde(default)]
+    pub descending: bool,
+}
+
+/// Numbers, strings and booleans are ordered, other values are only compared for equality.
+pub fn compare_values(a: &Value, b: &Value) -> Option<Ordering> {
+    match (a, b) {
+        (Value::Number(a), Value::Number(b)) => a.as_f64()?.partial_cmp(&b.as_f64()?),
+        (Value::String(a), Value::String(b)) => Some(a.cmp(b)),
+        (Value::Bool(a), Value::Bool(b)) => Some(a.cmp(b)),
+        _ => (a == b).then_some(Ordering::Equal),
+    }
+}
+
+pub fn value_at(row: &Value, path: &str) -> Option<Value> {
+    row.dot_get::<Value>(path)
+        .unwrap_or_default()
+        .filter(|v| !v.is_null())
+}
+
+impl FieldFilter {
+    pub fn matches(&self, row: &Value) -> bool {
+        let value = value_at(row, &self.path);
+        let ordering = value.as_ref().and_then(|v| compare_values(v, &self.value));
+        match self.op {
+            FilterOp::Exists => value.is_some(),
+            FilterOp::Eq => ordering == Some(Ordering::Equal),
+            FilterOp::Ne => ordering != Some(Ordering::Equal),
+            FilterOp::Lt => ordering == Some(Ordering::Less),
+            FilterOp::Le => matches!(ordering, Some(Ordering::Less | Ordering::Equal)),
+            FilterOp::Gt => ordering == Some(Ordering::Greater),
+            FilterOp::Ge => matches!(ordering, Some(Ordering::Greater | Ordering::Equal)),
+        }
+    }
+}
+
+impl SortKey {
+    /// Results without a value at `path` always come last.
+    pub fn compare(&self, a: &Value, b: &Value) -> Ordering {
+        match (value_at(a, &self.path), value_at(b, &self.path)) {
+            (Some(a), Some(b)) => {
+                let ordering = compare_values(&a, &b).unwrap_or(Ordering::Equal);
+                if self.descending {
+                    ordering.reverse()
+                } else {
+                    ordering
+                }
+            }
+            (Some(_), None) => Ordering::Less,
+            (None, Some(_)) => Ordering::Greater,
+            (None, None) => Ordering::Equal,
+        }
+    }
+}
@@ -0,0 +1,386 @@
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+use time::OffsetDateTime;
+
+use super::{
+    filters::{value_at, FieldFilter},
+    pareto::Direction,
+    synthesis::BestRunRule,
+};
+
+/// How the values of a metric are made comparable before they are weighted.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub enum Normalization {
+    /// `0` for the worst and `1` for the best result
+    #[default]
+    #[serde(rename = "minmax", alias = "min_max")]
+    MinMax,
+    /// standard deviations from the mean, positive when better than the mean
+    #[serde(rename = "zscore", alias = "z_score")]
+    ZScore,
+    /// ratio to the value of the baseline result, above `1` when better than the baseline
+    #[serde(rename = "baseline")]
+    Baseline,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct LeaderboardMetric {
+    /// path of a stored or derived field, e.g. `metrics.enc.long.throughput_per_lut`
+    pub path: String,
+    #[serde(default = "default_weight")]
+    pub weight: f64,
+    #[serde(default)]
+    pub direction: Direction,
+}
+
+fn default_weight() -> f64 {
+    1.0
+}
+
+/// Subtracted from the score of the results matching `condition`, e.g. results without
+/// side-channel protection.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct Penalty {
+    #[serde(flatten)]
+    pub condition: FieldFilter,
+    pub penalty: f64,
+}
+
+/// A saved leaderboard definition.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct Leaderboard {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    /// only rank results whose category starts with this prefix
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub category: Option<String>,
+    pub metrics: Vec<LeaderboardMetric>,
+    #[serde(default)]
+    pub normalization: Normalization,
+    /// id of the result the metrics are normalized by with the `baseline` normalization
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub baseline: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub filters: Vec<FieldFilter>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub penalties: Vec<Penalty>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub best_run_rule: Option<BestRunRule>,
+}
+
+/// A result's position on a leaderboard.
+#[derive(Debug, Serialize)]
+pub struct Entry {
+    pub rank: i32,
+    pub result_id: String,
+    pub score: f64,
+    /// sum of the penalties that apply to the result
+    #[serde(skip_serializing_if = "is_zero")]
+    pub penalty: f64,
+    pub values: Map<String, Value>,
+    pub normalized: Map<String, Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub previous_rank: Option<i32>,
+    /// positive when the result moved up since the previous ranking
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rank_change: Option<i32>,
+    #[serde(
+        skip_serializing_if = "Option::is_none",
+        with = "time::serde::rfc3339::option"
+    )]
+    pub rank_changed: Option<OffsetDateTime>,
+}
+
+fn is_zero(value: &f64) -> bool {
+    *value == 0.0
+}
+
+fn mean_and_std(values: &[f64]) -> (f64, f64) {
+    let n = values.len() as f64;
+    let mean = values.iter().sum::<f64>() / n;
+    let variance = values.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / n;
+    (mean, variance.sqrt())
+}
+
+impl Leaderboard {
+    pub fn validate(&self) -> Vec<String> {
+        let mut errors = Vec::new();
+        if self.metrics.is_empty() {
+            errors.push("at least one metric is required".to_owned());
+        }
+        for metric in &self.metrics {
+            if !metric.weight.is_finite() {
+                errors.push(format!("weight of {} must be finite", metric.path));
+            }
+        }
+        for penalty in &self.penalties {
+            if !penalty.penalty.is_finite() {
+                errors.push(format!(
+                    "penalty on {} must be finite",
+                    penalty.condition.path
+                ));
+            }
+        }
+        if self.normalization == Normalization::Baseline && self.baseline.is_none() {
+            errors.push("the baseline normalization requires a baseline result id".to_owned());
+        }
+        errors
+    }
+
+    /// Paths of all fields the leaderboard depends on.
+    pub fn paths(&self) -> impl Iterator<Item = &str> {
+        self.metrics
+            .iter()
+            .map(|m| m.path.as_str())
+            .chain(self.filters.iter().map(|f| f.path.as_str()))
+            .chain(self.penalties.iter().map(|p| p.condition.path.as_str()))
+    }
+
+    fn metric_values(&self, row: &Value) -> Option<Vec<f64>> {
+        self.metrics
+            .iter()
+            .map(|m| {
+                value_at(row, &m.path)
+                    .and_then(|v| v.as_f64())
+                    .filter(|v| v.is_finite())
+            })
+            .collect()
+    }
+
+    /// Rank `rows` (result ids and their JSON representation including derived fields) by
+    /// the weighted sum of their normalized metrics minus their penalties.
+    ///
+    /// Returns the entries from best to worst and the ids of the results that lack a metric.
+    pub fn rank(&self, rows: &[(String, Value)]) -> Result<(Vec<Entry>, Vec<String>), String> {
+        let baseline = match (self.normalization, &self.baseline) {
+            (Normalization::Baseline, Some(id)) => {
+                let row = rows
+                    .iter()
+                    .find(|(row_id, _)| row_id == id)
+                    .ok_or_else(|| format!("baseline {id} is not in the leaderboard's scope"))?;
+                let values = self
+                    .metric_values(&row.1)
+                    .ok_or_else(|| format!("baseline {id} lacks a metric"))?;
+                if values.contains(&0.0) {
+                    return Err(format!("baseline {id} has a metric equal to zero"));
+                }
+                Some(values)
+            }
+            _ => None,
+        };
+
+        let mut candidates = Vec::new();
+        let mut skipped = Vec::new();
+        for (id, row) in rows {
+            if !self.filters.iter().all(|f| f.matches(row)) {
+                continue;
+            }
+            match self.metric_values(row) {
+                Some(values) => candidates.push((id, row, values)),
+                None => skipped.push(id.clone()),
+            }
+        }
+        if candidates.is_empty() {
+            return Ok((Vec::new(), skipped));
+        }
+
+        let columns: Vec<Vec<f64>> = (0..self.metrics.len())
+            .map(|k| candidates.iter().map(|(_, _, values)| values[k]).collect())
+            .collect();
+        let normalize = |k: usize, value: f64| -> f64 {
+            let direction = self.metrics[k].direction;
+            let column = &columns[k];
+            match self.normalization {
+                Normalization::MinMax => {
+                    let min = column.iter().copied().fold(f64::INFINITY, f64::min);
+                    let max = column.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+                    if max == min {
+                        1.0
+                    } else if direction == Direction::Max {
+                        (value - min) / (max - min)
+                    } else {
+                        (max - value) / (max - min)
+                    }
+                }
+                Normalization::ZScore => {
+                    let (mean, std) = mean_and_std(column);
+                    if std == 0.0 {
+                        0.0
+                    } else if direction == Direction::Max {
+                        (value - mean) / std
+                    } else {
+                        (mean - value) / std
+                    }
+                }
+                Normalization::Baseline => {
+                    let base = baseline.as_ref().map_or(1.0, |b| b[k]);
+                    if direction == Direction::Max {
+                        value / base
+                    } else if value == 0.0 {
+                        f64::INFINITY
+                    } else {
+                        base / value
+                    }
+                }
+            }
+        };
+
+        let mut entries: Vec<Entry> = candidates
+            .into_iter()
+            .map(|(id, row, values)| {
+                let normalized: Vec<f64> = values
+                    .iter()
+                    .enumerate()
+                    .map(|(k, v)| normalize(k, *v))
+                    .collect();
+                let penalty: f64 = self
+                    .penalties
+                    .iter()
+                    .filter(|p| p.condition.matches(row))
+                    .map(|p| p.penalty)
+                    .sum();
+                let score = self
+                    .metrics
+                    .iter()
+                    .zip(&normalized)
+                    .map(|(m, n)| m.weight * n)
+                    .sum::<f64>()
+                    - penalty;
+                let by_path = |numbers: &[f64]| -> Map<String, Value> {
+                    self.metrics
+                        .iter()
+                        .zip(numbers)
+                        .map(|(m, v)| (m.path.clone(), json!(v)))
+                        .collect()
+                };
+                Entry {
+                    rank: 0,
+                    result_id: id.clone(),
+                    score,
+                    penalty,
+                    values: by_path(&values),
+                    normalized: by_path(&normalized),
+                    previous_rank: None,
+                    rank_change: None,
+                    rank_changed: None,
+                }
+            })
+            .collect();
+
+        entries.sort_by(|a, b| b.score.total_cmp(&a.score));
+        // equal scores share a rank, e.g. 1, 2, 2, 4
+        let mut previous: Option<(f64, i32)> = None;
+        for (i, entry) in entries.iter_mut().enumerate() {
+            entry.rank = match previous {
+                Some((score, rank)) if score == entry.score => rank,
+                _ => i as i32 + 1,
+            };
+            previous = Some((entry.score, entry.rank));
+        }
+        Ok((entries, skipped))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn leaderboard(definition: Value) -> Leaderboard {
+        serde_json::from_value(definition).unwrap()
+    }
+
+    fn rows() -> Vec<(String, Value)> {
+        [
+            (
+                "a",
+                json!({"throughput": 100.0, "lut": 1000, "device": "ecp5"}),
+            ),
+            (
+                "b",
+                json!({"throughput": 300.0, "lut": 3000, "device": "artix7"}),
+            ),
+            (
+                "c",
+                json!({"throughput": 200.0, "lut": 1000, "device": "artix7"}),
+            ),
+            ("d", json!({"lut": 500, "device": "artix7"})),
+        ]
+        .into_iter()
+        .map(|(id, row)| (id.to_owned(), row))
+        .collect()
+    }
+
+    fn ranking(entries: &[Entry]) -> Vec<(&str, i32)> {
+        entries
+            .iter()
+            .map(|e| (e.result_id.as_str(), e.rank))
+            .collect()
+    }
+
+    #[test]
+    fn ranks_by_weighted_min_max_scores() {
+        let leaderboard = leaderboard(json!({
+            "metrics": [
+                {"path": "throughput"},
+                {"path": "lut", "direction": "min", "weight": 2},
+            ],
+        }));
+        let (entries, skipped) = leaderboard.rank(&rows()).unwrap();
+        assert_eq!(ranking(&entries), [("c", 1), ("a", 2), ("b", 3)]);
+        assert_eq!(entries[0].score, 2.5);
+        assert_eq!(entries[0].normalized["lut"], json!(1.0));
+        assert_eq!(entries[2].values["throughput"], json!(300.0));
+        assert_eq!(skipped, ["d"]);
+    }
+
+    #[test]
+    fn applies_filters_and_penalties() {
+        let leaderboard = leaderboard(json!({
+            "metrics": [{"path": "throughput"}],
+            "filters": [{"path": "device", "op": "eq", "value": "artix7"}],
+            "penalties": [{"path": "lut", "op": "gt", "value": 2000, "penalty": 0.5}],
+        }));
+        let (entries, skipped) = leaderboard.rank(&rows()).unwrap();
+        assert_eq!(ranking(&entries), [("b", 1), ("c", 2)]);
+        assert_eq!(entries[0].penalty, 0.5);
+        assert_eq!(entries[0].score, 0.5);
+        assert_eq!(skipped, ["d"]);
+    }
+
+    #[test]
+    fn shares_ranks_of_equal_scores() {
+        let leaderboard = leaderboard(json!({
+            "metrics": [{"path": "lut", "direction": "min"}],
+            "filters": [{"path": "throughput", "op": "exists"}],
+        }));
+        let (entries, _) = leaderboard.rank(&rows()).unwrap();
+        assert_eq!(ranking(&entries), [("a", 1), ("c", 1), ("b", 3)]);
+    }
+
+    #[test]
+    fn normalizes_by_baseline() {
+        let leaderboard = leaderboard(json!({
+            "metrics": [{"path": "throughput"}],
+            "normalization": "baseline",
+            "baseline": "a",
+        }));
+        let (entries, _) = leaderboard.rank(&rows()).unwrap();
+        assert_eq!(ranking(&entries), [("b", 1), ("c", 2), ("a", 3)]);
+        assert_eq!(entries[0].score, 3.0);
+
+        let missing = Leaderboard {
+            baseline: Some("d".to_owned()),
+            ..leaderboard
+        };
+        assert_eq!(
+            missing.rank(&rows()).unwrap_err(),
+            "baseline d lacks a metric"
+        );
+    }
+
+    #[test]
+    fn validates_definitions() {
+        let invalid = leaderboard(json!({"metrics": [], "normalization": "baseline"}));
+        assert_eq!(invalid.validate().len(), 2);
+    }
+}
@@ -0,0 +1,107 @@
+use serde_json::{json, Map, Value};
+
+use super::timing::TimingRow;
+
+/// Message sizes in bytes for which throughputs are reported.
+pub const MESSAGE_SIZES: [i64; 3] = [16, 64, 1536];
+
+/// The implementation figures the metrics are normalized by.
+#[derive(Debug, Default)]
+pub struct Implementation {
+    /// maximum clock frequency in MHz
+    pub fmax: Option<f64>,
+    pub lut: Option<f64>,
+    pub slice: Option<f64>,
+    pub gate_equivalents: Option<f64>,
+}
+
+fn number(value: &Value) -> Option<f64> {
+    value
+        .as_f64()
+        .or_else(|| value.as_str().and_then(|s| s.trim().parse().ok()))
+        .filter(|v| v.is_finite() && *v > 0.0)
+}
+
+impl Implementation {
+    /// Figures of `synthesis.best.results`, falling back to the `asic` results for `Fmax`.
+    pub fn from_results(synthesis: &Value, asic: &Value) -> Self {
+        let best = &synthesis["best"]["results"];
+        Self {
+            fmax: number(&best["Fmax"]).or_else(|| number(&asic["Fmax"])),
+            lut: number(&best["lut"]),
+            slice: number(&best["slice"]),
+            gate_equivalents: number(&asic["gate_equivalents"]),
+        }
+    }
+
+    /// Throughput in Mbps and its efficiency per LUT, slice and kGE.
+    fn throughput(&self, bits: f64, cycles: f64) -> Option<Value> {
+        let fmax = self.fmax?;
+        if cycles <= 0.0 || bits <= 0.0 {
+            return None;
+        }
+        let throughput = bits * fmax / cycles;
+        let mut metrics = Map::new();
+        metrics.insert("throughput".to_owned(), json!(throughput));
+        let per = [
+            ("throughput_per_lut", self.lut),
+            ("throughput_per_slice", self.slice),
+            (
+                "throughput_per_kge",
+                self.gate_equivalents.map(|ge| ge / 1000.0),
+            ),
+        ];
+        for (name, area) in per {
+            if let Some(area) = area {
+                metrics.insert(name.to_owned(), json!(throughput / area));
+            }
+        }
+        Some(Value::Object(metrics))
+    }
+}
+
+/// Throughput metrics per operation for the standard message sizes (`16B`, `64B`, `1536B`)
+/// without associated data, and for long messages (`long`).
+///
+/// The long-message throughput is the asymptote given by the two longest messages:
+/// `8 × (m₂ - m₁) × Fmax / (c₂ - c₁)`.
+pub fn compute(rows: &[TimingRow], implementation: &Implementation) -> Value {
+    let Some(fmax) = implementation.fmax else {
+        return Value::Null;
+    };
+    let mut metrics = Map::new();
+    metrics.insert("Fmax".to_owned(), json!(fmax));
+
+    let mut operations: Vec<&str> = rows.iter().map(|row| row.operation.as_str()).collect();
+    operations.sort_unstable();
+    operations.dedup();
+
+    for operation in operations {
+        let mut messages: Vec<&TimingRow> = rows
+            .iter()
+            .filter(|row| row.operation == operation && row.ad_len == 0 && row.msg_len > 0)
+            .collect();
+        messages.sort_by_key(|row| (row.msg_len, row.cycles));
+        messages.dedup_by_key(|row| row.msg_len);
+
+        let mut sizes = Map::new();
+        for size in MESSAGE_SIZES {
+            if let Some(row) = messages.iter().find(|row| row.msg_len == size) {
+                if let Some(m) = implementation.throughput(8.0 * size as f64, row.cycles as f64) {
+                    sizes.insert(format!("{size}B"), m);
+                }
+            }
+        }
+        if let [.., shorter, longer] = messages[..] {
+            let bits = 8.0 * (longer.msg_len - shorter.msg_len) as f64;
+            let cycles = (longer.cycles - shorter.cycles) as f64;
+            if let Some(m) = implementation.throughput(bits, cycles) {
+                sizes.insert("long".to_owned(), m);
+            }
+        }
+        if !sizes.is_empty() {
+            metrics.insert(operation.to_owned(), Value::Object(sizes));
+        }
+    }
+    Value::Object(metrics)
+}
@@ -1,2 +1,17 @@
+pub mod artifacts;
+pub mod asic;
 pub mod auth;
+pub mod cycle_fit;
+pub mod design;
+pub mod devices;
+pub mod filters;
+pub mod leaderboards;
+pub mod metrics;
+pub mod pareto;
 pub mod results;
+pub mod roles;
+pub mod software;
+pub mod synthesis;
+pub mod timing;
+pub mod tokens;
+pub mod totp;
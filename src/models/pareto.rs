@@ -0,0 +1,89 @@
+use serde::{Deserialize, Serialize};
+
+/// Whether an objective is minimized (e.g. area) or maximized (e.g. throughput).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Direction {
+    #[serde(alias = "minimize")]
+    Min,
+    #[default]
+    #[serde(alias = "maximize")]
+    Max,
+}
+
+impl Direction {
+    /// Whether `a` is strictly better than `b`.
+    pub fn better(self, a: f64, b: f64) -> bool {
+        match self {
+            Direction::Min => a < b,
+            Direction::Max => a > b,
+        }
+    }
+}
+
+/// Whether `a` dominates `b`, i.e. is no worse in every objective and better in at least one.
+pub fn dominates(a: &[f64], b: &[f64], directions: &[Direction]) -> bool {
+    let mut better = false;
+    for ((a, b), direction) in a.iter().zip(b).zip(directions) {
+        if direction.better(*b, *a) {
+            return false;
+        }
+        better |= direction.better(*a, *b);
+    }
+    better
+}
+
+/// Dominance rank of each point: 1 for the Pareto front, 2 for the front of the remaining
+/// points, and so on.
+pub fn dominance_ranks(points: &[Vec<f64>], directions: &[Direction]) -> Vec<usize> {
+    let mut ranks = vec![0; points.len()];
+    let mut rank = 0;
+    while ranks.contains(&0) {
+        rank += 1;
+        let front: Vec<usize> = (0..points.len())
+            .filter(|&i| ranks[i] == 0)
+            .filter(|&i| {
+                !(0..points.len())
+                    .any(|j| ranks[j] == 0 && dominates(&points[j], &points[i], directions))
+            })
+            .collect();
+        for i in front {
+            ranks[i] = rank;
+        }
+    }
+    ranks
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// minimize area, maximize throughput
+    const DIRECTIONS: [Direction; 2] = [Direction::Min, Direction::Max];
+
+    #[test]
+    fn dominance_needs_one_strictly_better_objective() {
+        assert!(dominates(&[1.0, 10.0], &[2.0, 10.0], &DIRECTIONS));
+        assert!(dominates(&[1.0, 11.0], &[2.0, 10.0], &DIRECTIONS));
+        assert!(!dominates(&[1.0, 10.0], &[1.0, 10.0], &DIRECTIONS));
+        assert!(!dominates(&[1.0, 9.0], &[2.0, 10.0], &DIRECTIONS));
+    }
+
+    #[test]
+    fn ranks_successive_fronts() {
+        let points = vec![
+            vec![100.0, 50.0],
+            vec![200.0, 80.0],
+            vec![150.0, 40.0],
+            vec![300.0, 60.0],
+            vec![100.0, 50.0],
+            vec![400.0, 30.0],
+        ];
+        assert_eq!(dominance_ranks(&points, &DIRECTIONS), [1, 1, 2, 2, 1, 3]);
+    }
+
+    #[test]
+    fn ranks_no_points() {
+        assert!(dominance_ranks(&[], &DIRECTIONS).is_empty());
+    }
+}
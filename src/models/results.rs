@@ -19,4 +19,19 @@ pub struct Results {
     #[serde(default, skip_serializing_if = "JsonValue::is_null")]
     #[sqlx(default)]
     pub synthesis: JsonValue,
+    #[serde(default, skip_serializing_if = "JsonValue::is_null")]
+    #[sqlx(default)]
+    pub asic: JsonValue,
+    #[serde(default, skip_serializing_if = "JsonValue::is_null")]
+    #[sqlx(default)]
+    pub software: JsonValue,
+    /// utilization percentages of the targeted device, computed by `/retrieve`
+    #[serde(default, skip_serializing_if = "JsonValue::is_null")]
+    #[sqlx(default)]
+    pub utilization: JsonValue,
+    /// throughput metrics derived from `timing` and the best synthesis results, computed by
+    /// `/retrieve`
+    #[serde(default, skip_serializing_if = "JsonValue::is_null")]
+    #[sqlx(default)]
+    pub metrics: JsonValue,
 }
@@ -0,0 +1,113 @@
+use std::{fmt, str::FromStr};
+
+use serde::{Deserialize, Serialize};
+
+/// What a user is allowed to do. Each role includes the permissions of the roles before it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    /// retrieve and analyze results
+    Reader,
+    /// submit results, timing, synthesis reports, designs and artifacts
+    Submitter,
+    /// rename and delete results, manage the device catalog and leaderboards
+    Maintainer,
+    /// manage users and their roles
+    Admin,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Permission {
+    ReadResults,
+    SubmitResults,
+    RenameResults,
+    DeleteResults,
+    ManageDevices,
+    ManageLeaderboards,
+    ManageUsers,
+}
+
+impl Role {
+    pub const ALL: [Role; 4] = [Role::Reader, Role::Submitter, Role::Maintainer, Role::Admin];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Role::Reader => "reader",
+            Role::Submitter => "submitter",
+            Role::Maintainer => "maintainer",
+            Role::Admin => "admin",
+        }
+    }
+
+    pub fn permissions(self) -> &'static [Permission] {
+        static ALL: [Permission; 7] = [
+            Permission::ReadResults,
+            Permission::SubmitResults,
+            Permission::RenameResults,
+            Permission::DeleteResults,
+            Permission::ManageDevices,
+            Permission::ManageLeaderboards,
+            Permission::ManageUsers,
+        ];
+        match self {
+            Role::Reader => &ALL[..1],
+            Role::Submitter => &ALL[..2],
+            Role::Maintainer => &ALL[..6],
+            Role::Admin => &ALL,
+        }
+    }
+}
+
+impl fmt::Display for Role {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for Role {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Role::ALL
+            .into_iter()
+            .find(|role| role.as_str() == s.trim().to_lowercase())
+            .ok_or_else(|| format!("unknown role: {s}"))
+    }
+}
+
+impl fmt::Display for Permission {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match serde_json::to_value(self) {
+            Ok(serde_json::Value::String(name)) => f.write_str(&name),
+            _ => write!(f, "{self:?}"),
+        }
+    }
+}
+
+/// A permission as a type, for `RequirePermission<P>`.
+pub trait RequiredPermission: Send + Sync + 'static {
+    const PERMISSION: Permission;
+}
+
+macro_rules! required_permissions {
+    ($($name:ident => $permission:ident),* $(,)?) => {
+        $(
+            pub struct $name;
+
+            impl RequiredPermission for $name {
+                const PERMISSION: Permission = Permission::$permission;
+            }
+        )*
+    };
+}
+
+required_permissions! {
+    ReadResults => ReadResults,
+    SubmitResult => SubmitResults,
+    RenameResult => RenameResults,
+    DeleteResult => DeleteResults,
+    ManageDevices => ManageDevices,
+    ManageLeaderboards => ManageLeaderboards,
+    ManageUsers => ManageUsers,
+}
@@ -0,0 +1,83 @@
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+
+use super::timing::{normalize_operation, TimingRow};
+
+/// Results of a software implementation benchmarked on a given platform.
+///
+/// `cycles_per_byte` of each measurement is always computed by the server.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct SoftwareResults {
+    /// benchmark platform or board, e.g. `STM32F407` or `x86_64`
+    pub platform: String,
+    /// microcontroller or CPU core, e.g. `Cortex-M4`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mcu: Option<String>,
+    /// clock frequency of the platform in MHz
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub frequency: Option<f64>,
+    /// compiler and version, e.g. `arm-none-eabi-gcc 12.2`
+    pub compiler: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub compiler_flags: Option<String>,
+    /// code size (ROM) in bytes
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rom: Option<i64>,
+    /// RAM usage (stack and static data) in bytes
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ram: Option<i64>,
+    /// cycles for each operation and message length
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub cycles: Vec<TimingRow>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+/// Cycles per processed byte (associated data and message) of a measurement.
+pub fn cycles_per_byte(row: &TimingRow) -> Option<f64> {
+    let bytes = row.ad_len + row.msg_len;
+    (bytes > 0).then(|| row.cycles as f64 / bytes as f64)
+}
+
+impl SoftwareResults {
+    pub fn validate(&self) -> Vec<String> {
+        let mut errors = Vec::new();
+        if self.platform.trim().is_empty() {
+            errors.push("platform must not be empty".to_owned());
+        }
+        if self.compiler.trim().is_empty() {
+            errors.push("compiler must not be empty".to_owned());
+        }
+        if let Some(frequency) = self.frequency.filter(|f| f.is_nan() || *f <= 0.0) {
+            errors.push(format!("frequency must be positive, got {frequency}"));
+        }
+        for (name, value) in [("rom", self.rom), ("ram", self.ram)] {
+            if let Some(value) = value.filter(|v| *v < 0) {
+                errors.push(format!("{name} must not be negative, got {value}"));
+            }
+        }
+        for (i, row) in self.cycles.iter().enumerate() {
+            if row.ad_len < 0 || row.msg_len < 0 {
+                errors.push(format!("cycles[{i}]: lengths must not be negative"));
+            }
+            if row.cycles <= 0 {
+                errors.push(format!(
+                    "cycles[{i}]: cycles must be positive, got {}",
+                    row.cycles
+                ));
+            }
+        }
+        errors
+    }
+
+    /// Normalize the operation names and add the cycles per byte of each measurement.
+    pub fn compute_cycles_per_byte(&mut self) {
+        for row in &mut self.cycles {
+            row.operation = normalize_operation(&row.operation);
+            match cycles_per_byte(row) {
+                Some(cpb) => row.extra.insert("cycles_per_byte".to_owned(), json!(cpb)),
+                None => row.extra.remove("cycles_per_byte"),
+            };
+        }
+    }
+}
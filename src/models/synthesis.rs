@@ -0,0 +1,197 @@
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+/// The toolchain that produced a set of synthesis results.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Tool {
+    Vivado,
+    Yosys,
+    Nextpnr,
+    Quartus,
+}
+
+impl Tool {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Tool::Vivado => "vivado",
+            Tool::Yosys => "yosys",
+            Tool::Nextpnr => "nextpnr",
+            Tool::Quartus => "quartus",
+        }
+    }
+
+    /// names of the multipart fields holding the reports of this tool
+    pub fn report_names(&self) -> &'static [&'static str] {
+        match self {
+            Tool::Vivado => &["utilization", "hierarchical_utilization", "timing_summary"],
+            Tool::Yosys | Tool::Nextpnr => &["stat", "nextpnr"],
+            Tool::Quartus => &["fit_summary", "sta"],
+        }
+    }
+}
+
+/// Canonical synthesis/implementation results, independent of the flow that produced them.
+///
+/// Keys follow the ones already used in `synthesis.best.results`, so parsed reports can be
+/// queried with the same field paths as results submitted by the Python flow.
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct SynthesisResults {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tool: Option<Tool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tool_version: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub device: Option<String>,
+    /// maximum clock frequency in MHz
+    #[serde(rename = "Fmax", default, skip_serializing_if = "Option::is_none")]
+    pub fmax: Option<f64>,
+    /// clock period in nanoseconds the design was constrained with
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub clock_period: Option<f64>,
+    /// worst negative slack in nanoseconds
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub wns: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub lut: Option<i64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ff: Option<i64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub slice: Option<i64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub latch: Option<i64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dsp: Option<i64>,
+    /// block RAM tiles, a RAMB18 counts as half a tile
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bram_tile: Option<f64>,
+    /// block RAM primitives of devices without Xilinx-style tiles
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bram: Option<i64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub lutram: Option<i64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub srl: Option<i64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub io: Option<i64>,
+    #[serde(
+        rename = "_hierarchical_utilization",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub hierarchical_utilization: Option<Value>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+impl SynthesisResults {
+    /// Fill the values missing in `self` from `other`.
+    pub fn merge(&mut self, other: SynthesisResults) {
+        macro_rules! merge {
+            ($($field:ident),*) => {
+                $(if self.$field.is_none() {
+                    self.$field = other.$field;
+                })*
+            };
+        }
+        merge!(
+            tool,
+            tool_version,
+            device,
+            fmax,
+            clock_period,
+            wns,
+            lut,
+            ff,
+            slice,
+            latch,
+            dsp,
+            bram_tile,
+            bram,
+            lutram,
+            srl,
+            io,
+            hierarchical_utilization
+        );
+        for (k, v) in other.extra {
+            self.extra.entry(k).or_insert(v);
+        }
+    }
+}
+
+/// A row of the `synthesis_runs` table.
+#[derive(Debug, Clone, Serialize, sqlx::FromRow)]
+pub struct SynthesisRun {
+    pub id: i64,
+    pub result_id: String,
+    pub device: String,
+    pub tool: String,
+    pub tool_version: String,
+    pub strategy: String,
+    /// target clock period in nanoseconds
+    pub target_clock_period: Option<f64>,
+    pub settings: Option<Value>,
+    pub results: sqlx::types::Json<SynthesisResults>,
+    pub submitter: String,
+    #[serde(with = "time::serde::rfc3339")]
+    pub timestamp: time::OffsetDateTime,
+}
+
+/// A synthesis run as submitted by a client. Device, tool and tool version default to the
+/// ones found in `results`.
+#[derive(Debug, Deserialize)]
+pub struct NewSynthesisRun {
+    #[serde(default)]
+    pub device: Option<String>,
+    #[serde(default)]
+    pub tool: Option<Tool>,
+    #[serde(default)]
+    pub tool_version: Option<String>,
+    #[serde(default)]
+    pub strategy: Option<String>,
+    #[serde(default)]
+    pub target_clock_period: Option<f64>,
+    #[serde(default)]
+    pub settings: Option<Value>,
+    pub results: SynthesisResults,
+}
+
+/// How the "best" run of a result is chosen.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BestRunRule {
+    /// highest `Fmax`
+    #[default]
+    MaxFmax,
+    /// fewest LUTs, ties broken by the highest `Fmax`
+    MinLut,
+    /// smallest LUTs / `Fmax`, i.e. area times clock period
+    MinAreaTime,
+}
+
+impl std::str::FromStr for BestRunRule {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        serde_json::from_value(Value::from(s)).map_err(|_| format!("unknown best run rule: {s}"))
+    }
+}
+
+impl BestRunRule {
+    /// The value to minimize, or `None` if the run lacks the metrics needed by the rule.
+    fn cost(&self, results: &SynthesisResults) -> Option<(f64, f64)> {
+        let fmax = results.fmax.filter(|f| *f > 0.0);
+        match self {
+            BestRunRule::MaxFmax => Some((-fmax?, 0.0)),
+            BestRunRule::MinLut => Some((results.lut? as f64, -fmax.unwrap_or(0.0))),
+            BestRunRule::MinAreaTime => Some((results.lut? as f64 / fmax?, 0.0)),
+        }
+    }
+
+    pub fn select<'a>(&self, runs: &'a [SynthesisRun]) -> Option<&'a SynthesisRun> {
+        runs.iter()
+            .filter_map(|run| Some((self.cost(&run.results)?, run)))
+            .min_by(|(a, _), (b, _)| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal))
+            .map(|(_, run)| run)
+    }
+}
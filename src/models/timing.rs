@@ -0,0 +1,66 @@
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+/// A single timing measurement in its normalized form.
+///
+/// Sizes are in bytes and the latency is in clock cycles. Columns without a canonical
+/// counterpart are kept in `extra`.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct TimingRow {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub msg_id: Option<i64>,
+    pub operation: String,
+    #[serde(default)]
+    pub ad_len: i64,
+    pub msg_len: i64,
+    pub cycles: i64,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+/// Map the operation names used by different testbenches to `enc`, `dec` or `hash`.
+pub fn normalize_operation(op: &str) -> String {
+    let op = op.trim().to_lowercase();
+    match op.as_str() {
+        "enc" | "encrypt" | "encryption" | "aead_enc" => "enc".to_owned(),
+        "dec" | "decrypt" | "decryption" | "aead_dec" => "dec".to_owned(),
+        "hash" | "digest" => "hash".to_owned(),
+        _ => op,
+    }
+}
+
+/// A row of the `timing_measurements` table together with the result it belongs to.
+#[derive(Debug, Serialize, sqlx::FromRow)]
+pub struct TimingMeasurement {
+    pub result_id: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[sqlx(default)]
+    pub name: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[sqlx(default)]
+    pub category: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub msg_id: Option<i64>,
+    pub operation: String,
+    pub ad_len: i64,
+    pub msg_len: i64,
+    pub cycles: i64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub extra: Option<Value>,
+}
+
+/// Statistics of the measurements of a category for one operation and pair of lengths.
+#[derive(Debug, Serialize, sqlx::FromRow)]
+pub struct TimingAggregate {
+    pub category: Option<String>,
+    pub operation: String,
+    pub ad_len: i64,
+    pub msg_len: i64,
+    /// number of measurements
+    pub results: i64,
+    pub min_cycles: i64,
+    pub max_cycles: i64,
+    pub avg_cycles: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cycles_per_byte: Option<f64>,
+}
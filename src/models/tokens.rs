@@ -0,0 +1,183 @@
+use std::{fmt, str::FromStr};
+
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use time::OffsetDateTime;
+use uuid::Uuid;
+
+use super::roles::Permission;
+
+/// API tokens start with this prefix, which tells them apart from JWTs.
+pub const TOKEN_PREFIX: &str = "cdb_";
+
+/// What an API token may be used for, on top of the roles of its owner.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(try_from = "String", into = "String")]
+pub enum Scope {
+    /// `read`: retrieve and analyze results
+    Read,
+    /// `submit:<pattern>`: submit results whose category matches the pattern, e.g. `submit:HW:LWC:*`
+    Submit(String),
+}
+
+impl Scope {
+    pub fn permission(&self) -> Permission {
+        match self {
+            Scope::Read => Permission::ReadResults,
+            Scope::Submit(_) => Permission::SubmitResults,
+        }
+    }
+
+    /// Whether the scope allows submitting results of `category`.
+    pub fn allows_category(&self, category: Option<&str>) -> bool {
+        match self {
+            Scope::Read => false,
+            Scope::Submit(pattern) => glob_matches(pattern, category.unwrap_or_default()),
+        }
+    }
+}
+
+impl fmt::Display for Scope {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Scope::Read => f.write_str("read"),
+            Scope::Submit(pattern) => write!(f, "submit:{pattern}"),
+        }
+    }
+}
+
+impl FromStr for Scope {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().split_once(':') {
+            None if s.trim() == "read" => Ok(Scope::Read),
+            None if s.trim() == "submit" => Ok(Scope::Submit("*".to_owned())),
+            Some(("submit", pattern)) if !pattern.is_empty() => {
+                Ok(Scope::Submit(pattern.to_owned()))
+            }
+            _ => Err(format!(
+                "unknown scope: {s} (expected `read` or `submit:<category pattern>`)"
+            )),
+        }
+    }
+}
+
+impl TryFrom<String> for Scope {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl From<Scope> for String {
+    fn from(scope: Scope) -> Self {
+        scope.to_string()
+    }
+}
+
+/// Match `text` against `pattern`, in which `*` stands for any sequence of characters.
+fn glob_matches(pattern: &str, text: &str) -> bool {
+    let mut parts = pattern.split('*');
+    let first = parts.next().unwrap_or_default();
+    let Some(mut rest) = text.strip_prefix(first) else {
+        return false;
+    };
+    let parts: Vec<&str> = parts.collect();
+    let Some((last, middle)) = parts.split_last() else {
+        // no `*` in the pattern
+        return rest.is_empty();
+    };
+    for part in middle {
+        match rest.find(part) {
+            Some(i) => rest = &rest[i + part.len()..],
+            None => return false,
+        }
+    }
+    rest.len() >= last.len() && rest.ends_with(last)
+}
+
+/// An API token as listed to its owner; the token itself is only shown once, when created.
+#[derive(Debug, sqlx::FromRow, Serialize)]
+pub struct ApiToken {
+    pub id: i32,
+    pub name: String,
+    pub user_id: Uuid,
+    pub scopes: Vec<String>,
+    #[serde(with = "time::serde::rfc3339")]
+    pub created: OffsetDateTime,
+    #[serde(with = "time::serde::rfc3339")]
+    pub expires: OffsetDateTime,
+    #[serde(with = "time::serde::rfc3339::option")]
+    pub last_used: Option<OffsetDateTime>,
+}
+
+/// A client certificate identity mapped to a user, who is authenticated by certificates with
+/// that subject or subject alternative name.
+#[derive(Debug, sqlx::FromRow, Serialize)]
+pub struct CertificateMapping {
+    pub id: i32,
+    pub identity: String,
+    pub user_id: Uuid,
+    pub scopes: Vec<String>,
+    #[serde(with = "time::serde::rfc3339")]
+    pub created: OffsetDateTime,
+    #[serde(with = "time::serde::rfc3339::option")]
+    pub last_used: Option<OffsetDateTime>,
+}
+
+/// `n` random bytes, hex encoded.
+pub fn random_hex(n: usize) -> String {
+    let mut bytes = vec![0u8; n];
+    rand::thread_rng().fill_bytes(&mut bytes);
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+/// A new random API token.
+pub fn generate() -> String {
+    format!("{TOKEN_PREFIX}{}", random_hex(32))
+}
+
+/// The stored form of a token. Tokens are random, so an unsalted hash is enough.
+pub fn hash(token: &str) -> String {
+    format!("{:x}", Sha256::digest(token.as_bytes()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matches_globs() {
+        assert!(glob_matches("*", ""));
+        assert!(glob_matches("*", "SW:AVR"));
+        assert!(glob_matches("SW:AVR", "SW:AVR"));
+        assert!(!glob_matches("SW:AVR", "SW:AVR8"));
+        assert!(glob_matches("HW:*", "HW:FPGA"));
+        assert!(glob_matches("HW:*", "HW:"));
+        assert!(!glob_matches("HW:*", "SW:AVR"));
+        assert!(glob_matches("*:FPGA", "HW:FPGA"));
+        assert!(glob_matches("HW:*:Xilinx*", "HW:FPGA:Xilinx:Artix7"));
+        assert!(!glob_matches("HW:*:Xilinx*", "HW:FPGA:Lattice"));
+    }
+
+    #[test]
+    fn does_not_overlap_prefix_and_suffix() {
+        assert!(!glob_matches("ab*bc", "abc"));
+        assert!(glob_matches("ab*bc", "abbc"));
+        assert!(!glob_matches("a*a", "a"));
+    }
+
+    #[test]
+    fn parses_scopes() {
+        assert_eq!("read".parse(), Ok(Scope::Read));
+        assert_eq!("submit".parse(), Ok(Scope::Submit("*".to_owned())));
+        assert_eq!("submit:HW:*".parse(), Ok(Scope::Submit("HW:*".to_owned())));
+        assert!("submit:".parse::<Scope>().is_err());
+        assert!("write".parse::<Scope>().is_err());
+        assert!(Scope::Submit("HW:*".to_owned()).allows_category(Some("HW:FPGA")));
+        assert!(!Scope::Read.allows_category(Some("HW:FPGA")));
+    }
+}
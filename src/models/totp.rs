@@ -0,0 +1,203 @@
+//! Time-based one-time passwords (RFC 6238) with HMAC-SHA1, 6 digits and 30 second steps, as
+//! supported by common authenticator apps.
+
+use hmac::{Hmac, Mac};
+use rand::RngCore;
+use sha1::Sha1;
+
+use super::tokens::random_hex;
+
+pub const ISSUER: &str = "cergdb";
+const DIGITS: u32 = 6;
+const PERIOD: u64 = 30;
+/// codes of this many steps before or after the current one are accepted as well
+const SKEW: i64 = 1;
+const SECRET_BYTES: usize = 20;
+pub const RECOVERY_CODES: usize = 10;
+
+/// A new random secret, hex encoded for storage.
+pub fn generate_secret() -> String {
+    let mut bytes = [0u8; SECRET_BYTES];
+    rand::thread_rng().fill_bytes(&mut bytes);
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+fn decode_hex(hex: &str) -> Option<Vec<u8>> {
+    if !hex.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+/// RFC 4648 base32 without padding, the encoding of secrets in provisioning URIs.
+fn base32(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 32] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ234567";
+    let mut encoded = String::new();
+    let mut buffer: u32 = 0;
+    let mut bits = 0;
+    for byte in bytes {
+        buffer = (buffer << 8) | u32::from(*byte);
+        bits += 8;
+        while bits >= 5 {
+            bits -= 5;
+            encoded.push(ALPHABET[((buffer >> bits) & 31) as usize] as char);
+        }
+    }
+    if bits > 0 {
+        encoded.push(ALPHABET[((buffer << (5 - bits)) & 31) as usize] as char);
+    }
+    encoded
+}
+
+fn percent_encode(s: &str) -> String {
+    s.bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                (b as char).to_string()
+            }
+            _ => format!("%{b:02X}"),
+        })
+        .collect()
+}
+
+/// The secret as entered manually in an authenticator app.
+pub fn display_secret(secret: &str) -> Option<String> {
+    Some(base32(&decode_hex(secret)?))
+}
+
+/// The `otpauth://` URI to show as a QR code for enrolling `account` in an authenticator app.
+pub fn provisioning_uri(account: &str, secret: &str) -> Option<String> {
+    let secret = display_secret(secret)?;
+    Some(format!(
+        "otpauth://totp/{issuer}:{account}?secret={secret}&issuer={issuer}&algorithm=SHA1&digits={DIGITS}&period={PERIOD}",
+        issuer = percent_encode(ISSUER),
+        account = percent_encode(account),
+    ))
+}
+
+/// The HOTP value (RFC 4226) of `secret` for the counter `step`.
+fn code_at(secret: &[u8], step: u64) -> u32 {
+    let mut mac = Hmac::<Sha1>::new_from_slice(secret).expect("HMAC accepts keys of any length");
+    mac.update(&step.to_be_bytes());
+    let digest = mac.finalize().into_bytes();
+    let offset = (digest[digest.len() - 1] & 0x0f) as usize;
+    let binary = u32::from_be_bytes([
+        digest[offset] & 0x7f,
+        digest[offset + 1],
+        digest[offset + 2],
+        digest[offset + 3],
+    ]);
+    binary % 10u32.pow(DIGITS)
+}
+
+/// Check `code` against the steps around `unix_time`.
+///
+/// Returns the matched step, which has to be stored and passed as `last_step` next time, so that
+/// a code cannot be used twice.
+pub fn verify(secret: &str, code: &str, unix_time: u64, last_step: Option<i64>) -> Option<i64> {
+    let secret = decode_hex(secret)?;
+    let code = code.trim().replace(' ', "");
+    if code.len() != DIGITS as usize {
+        return None;
+    }
+    let code: u32 = code.parse().ok()?;
+    let current = (unix_time / PERIOD) as i64;
+    (current - SKEW..=current + SKEW)
+        .filter(|step| *step >= 0 && !matches!(last_step, Some(last) if *step <= last))
+        .find(|step| code_at(&secret, *step as u64) == code)
+}
+
+/// New single-use recovery codes, e.g. `3f9a-01c2-77de-b4e0`.
+pub fn recovery_codes() -> Vec<String> {
+    (0..RECOVERY_CODES)
+        .map(|_| {
+            let hex = random_hex(8);
+            [&hex[0..4], &hex[4..8], &hex[8..12], &hex[12..16]].join("-")
+        })
+        .collect()
+}
+
+/// Recovery codes are compared without separators and case.
+pub fn normalize_recovery_code(code: &str) -> String {
+    code.chars()
+        .filter(char::is_ascii_alphanumeric)
+        .collect::<String>()
+        .to_lowercase()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// the ASCII secret `12345678901234567890` of the RFC 6238 test vectors
+    const SECRET: &str = "3132333435363738393031323334353637383930";
+
+    #[test]
+    fn accepts_rfc_6238_vectors() {
+        // the last six digits of the SHA1 vectors of RFC 6238, appendix B
+        let vectors = [
+            (59, "287082"),
+            (1111111109, "081804"),
+            (1111111111, "050471"),
+            (1234567890, "005924"),
+            (2000000000, "279037"),
+            (20000000000, "353130"),
+        ];
+        for (time, code) in vectors {
+            assert_eq!(
+                verify(SECRET, code, time, None),
+                Some((time / PERIOD) as i64),
+                "{time}"
+            );
+        }
+    }
+
+    #[test]
+    fn accepts_adjacent_steps_only() {
+        assert_eq!(
+            verify(SECRET, "081804", 1111111109 + 30, None),
+            Some(37037036)
+        );
+        assert_eq!(
+            verify(SECRET, "081804", 1111111109 - 30, None),
+            Some(37037036)
+        );
+        assert_eq!(verify(SECRET, "081804", 1111111109 + 60, None), None);
+    }
+
+    #[test]
+    fn rejects_used_and_malformed_codes() {
+        assert_eq!(verify(SECRET, "287082", 59, Some(1)), None);
+        assert_eq!(verify(SECRET, " 287 082 ", 59, Some(0)), Some(1));
+        assert_eq!(verify(SECRET, "28708", 59, None), None);
+        assert_eq!(verify(SECRET, "94287082", 59, None), None);
+        assert_eq!(verify(SECRET, "287083", 59, None), None);
+        assert_eq!(verify("313", "287082", 59, None), None);
+    }
+
+    #[test]
+    fn encodes_provisioning_uri() {
+        assert_eq!(
+            display_secret(SECRET).as_deref(),
+            Some("GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ")
+        );
+        let uri = provisioning_uri("alice@example.com", SECRET).unwrap();
+        assert!(uri.starts_with(
+            "otpauth://totp/cergdb:alice%40example.com?secret=GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ&"
+        ));
+    }
+
+    #[test]
+    fn normalizes_recovery_codes() {
+        assert_eq!(
+            normalize_recovery_code(" 3F9A-01c2-77de-B4E0 "),
+            "3f9a01c277deb4e0"
+        );
+        let codes = recovery_codes();
+        assert_eq!(codes.len(), RECOVERY_CODES);
+        assert!(codes.iter().all(|code| code.len() == 19));
+    }
+}
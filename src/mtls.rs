@@ -0,0 +1,142 @@
+use std::{fs::File, io, io::BufReader, path::Path, sync::Arc};
+
+use axum::{middleware::AddExtension, Extension};
+use axum_server::{
+    accept::Accept,
+    tls_rustls::{RustlsAcceptor, RustlsConfig},
+};
+use futures_util::future::BoxFuture;
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio_rustls::{
+    rustls::{
+        server::{AllowAnyAnonymousOrAuthenticatedClient, AllowAnyAuthenticatedClient},
+        Certificate, PrivateKey, RootCertStore, ServerConfig,
+    },
+    server::TlsStream,
+};
+use tower::Layer;
+use x509_parser::prelude::{FromDer, GeneralName, X509Certificate};
+
+/// The verified client certificate of a TLS connection, added to the extensions of its
+/// requests.
+#[derive(Debug, Clone, Default)]
+pub struct ClientCertificate {
+    /// the subject, e.g. `C=US, O=CERG, CN=lab-1`, followed by the DNS names, email addresses
+    /// and URIs of the subject alternative names
+    pub identities: Vec<String>,
+}
+
+impl ClientCertificate {
+    fn from_der(der: &[u8]) -> Self {
+        let Ok((_, cert)) = X509Certificate::from_der(der) else {
+            return Self::default();
+        };
+        let mut identities = vec![cert.subject().to_string()];
+        if let Ok(Some(san)) = cert.subject_alternative_name() {
+            for name in &san.value.general_names {
+                match name {
+                    GeneralName::DNSName(name)
+                    | GeneralName::RFC822Name(name)
+                    | GeneralName::URI(name) => identities.push(name.to_string()),
+                    _ => {}
+                }
+            }
+        }
+        Self { identities }
+    }
+}
+
+fn open(path: &Path) -> Result<BufReader<File>, String> {
+    File::open(path)
+        .map(BufReader::new)
+        .map_err(|e| format!("could not open {}: {e}", path.display()))
+}
+
+fn read_certs(path: &Path) -> Result<Vec<Certificate>, String> {
+    let certs = rustls_pemfile::certs(&mut open(path)?)
+        .map_err(|e| format!("invalid certificates in {}: {e}", path.display()))?;
+    if certs.is_empty() {
+        return Err(format!("no certificates in {}", path.display()));
+    }
+    Ok(certs.into_iter().map(Certificate).collect())
+}
+
+fn read_key(path: &Path) -> Result<PrivateKey, String> {
+    let items = rustls_pemfile::read_all(&mut open(path)?)
+        .map_err(|e| format!("invalid private key in {}: {e}", path.display()))?;
+    items
+        .into_iter()
+        .find_map(|item| match item {
+            rustls_pemfile::Item::RSAKey(key)
+            | rustls_pemfile::Item::PKCS8Key(key)
+            | rustls_pemfile::Item::ECKey(key) => Some(PrivateKey(key)),
+            _ => None,
+        })
+        .ok_or_else(|| format!("no private key in {}", path.display()))
+}
+
+/// A TLS configuration that asks clients for a certificate signed by one of the CAs in
+/// `client_ca`. With `required`, connections without one are refused; otherwise they can still
+/// authenticate with tokens.
+pub fn server_config(
+    cert: &Path,
+    key: &Path,
+    client_ca: &Path,
+    required: bool,
+) -> Result<RustlsConfig, String> {
+    let mut roots = RootCertStore::empty();
+    for ca in read_certs(client_ca)? {
+        roots
+            .add(&ca)
+            .map_err(|e| format!("invalid CA certificate in {}: {e}", client_ca.display()))?;
+    }
+    let verifier = if required {
+        AllowAnyAuthenticatedClient::new(roots)
+    } else {
+        AllowAnyAnonymousOrAuthenticatedClient::new(roots)
+    };
+    let mut config = ServerConfig::builder()
+        .with_safe_defaults()
+        .with_client_cert_verifier(verifier)
+        .with_single_cert(read_certs(cert)?, read_key(key)?)
+        .map_err(|e| format!("invalid server certificate or key: {e}"))?;
+    config.alpn_protocols = vec![b"h2".to_vec(), b"http/1.1".to_vec()];
+    Ok(RustlsConfig::from_config(Arc::new(config)))
+}
+
+/// Accepts TLS connections and passes the client certificate on to the requests.
+#[derive(Debug, Clone)]
+pub struct ClientCertAcceptor {
+    inner: RustlsAcceptor,
+}
+
+impl ClientCertAcceptor {
+    pub fn new(config: RustlsConfig) -> Self {
+        Self {
+            inner: RustlsAcceptor::new(config),
+        }
+    }
+}
+
+impl<I, S> Accept<I, S> for ClientCertAcceptor
+where
+    I: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+    S: Send + 'static,
+{
+    type Stream = TlsStream<I>;
+    type Service = AddExtension<S, ClientCertificate>;
+    type Future = BoxFuture<'static, io::Result<(Self::Stream, Self::Service)>>;
+
+    fn accept(&self, stream: I, service: S) -> Self::Future {
+        let acceptor = self.inner.clone();
+        Box::pin(async move {
+            let (stream, service) = acceptor.accept(stream, service).await?;
+            // the certificate was verified during the handshake, the first one is the client's
+            let cert = match stream.get_ref().1.peer_certificates() {
+                Some([cert, ..]) => ClientCertificate::from_der(&cert.0),
+                _ => ClientCertificate::default(),
+            };
+            Ok((stream, Extension(cert).layer(service)))
+        })
+    }
+}
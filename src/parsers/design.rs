@@ -0,0 +1,12 @@
+use super::LineError;
+use crate::models::design::Design;
+
+/// Parse the text of an LWC `design.toml`.
+pub fn parse_design_toml(text: &str) -> Result<Design, Vec<LineError>> {
+    toml::from_str(text).map_err(|e| {
+        let line = e.span().map_or(0, |span| {
+            text[..span.start].matches('\n').count() as u64 + 1
+        });
+        vec![LineError::new(line, e.message())]
+    })
+}
@@ -0,0 +1,77 @@
+use serde::Serialize;
+
+use crate::models::synthesis::{SynthesisResults, Tool};
+
+pub mod design;
+pub mod nextpnr;
+pub mod quartus;
+pub mod timing;
+pub mod vivado;
+pub mod yosys;
+
+/// A problem found on a specific line of an uploaded file.
+#[derive(Debug, Clone, Serialize)]
+pub struct LineError {
+    pub line: u64,
+    pub message: String,
+}
+
+impl LineError {
+    pub fn new(line: u64, message: impl Into<String>) -> Self {
+        Self {
+            line,
+            message: message.into(),
+        }
+    }
+}
+
+impl std::fmt::Display for LineError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+/// Parse the raw reports of a synthesis/implementation run into canonical results.
+///
+/// `report` returns the text of the report with the given name (see [`Tool::report_names`]).
+pub fn parse_synthesis_reports<'a>(
+    tool: Tool,
+    report: impl Fn(&str) -> Option<&'a str>,
+) -> Result<SynthesisResults, Vec<LineError>> {
+    match tool {
+        Tool::Vivado => vivado::parse_reports(
+            report("utilization"),
+            report("hierarchical_utilization"),
+            report("timing_summary"),
+        ),
+        Tool::Yosys | Tool::Nextpnr => {
+            let mut results = SynthesisResults::default();
+            // post place-and-route numbers take precedence over the synthesis statistics
+            if let Some(text) = report("nextpnr") {
+                results.merge(nextpnr::parse_report(text)?);
+            }
+            if let Some(text) = report("stat") {
+                let mut stat = yosys::parse_stat(text)?;
+                if results.tool == Some(Tool::Nextpnr) {
+                    if let Some(version) = stat.tool_version.take() {
+                        results
+                            .extra
+                            .insert("yosys_version".to_owned(), version.into());
+                    }
+                }
+                results.merge(stat);
+            }
+            Ok(results)
+        }
+        Tool::Quartus => {
+            let mut results = SynthesisResults::default();
+            if let Some(text) = report("fit_summary") {
+                results.merge(quartus::parse_fit_summary(text)?);
+            }
+            if let Some(text) = report("sta") {
+                results.merge(quartus::parse_sta(text)?);
+            }
+            Ok(results)
+        }
+    }
+}
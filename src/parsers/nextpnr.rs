@@ -0,0 +1,192 @@
+use serde_json::{Map, Value};
+
+use super::LineError;
+use crate::models::synthesis::{SynthesisResults, Tool};
+
+/// Map the used resources of the nextpnr utilization report (iCE40 and ECP5 bels) to the
+/// canonical fields.
+fn apply_utilization(results: &mut SynthesisResults, used: &Map<String, Value>) {
+    let get = |bel: &str| used.get(bel).and_then(Value::as_i64);
+    results.lut = get("TRELLIS_COMB").or(get("ICESTORM_LC"));
+    results.ff = get("TRELLIS_FF");
+    results.slice = get("TRELLIS_SLICE");
+    results.dsp = get("MULT18X18D").or(get("ICESTORM_DSP"));
+    results.bram = get("DP16KD").or(get("ICESTORM_RAM"));
+    results.io = get("TRELLIS_IO").or(get("SB_IO"));
+}
+
+/// Parse the JSON report written by `nextpnr --report`, or the utilization and maximum
+/// frequency lines of a nextpnr log.
+///
+/// `Fmax` is the lowest frequency achieved by any clock. Logs report the frequencies after
+/// placement and again after routing, only the last block is used.
+pub fn parse_report(text: &str) -> Result<SynthesisResults, Vec<LineError>> {
+    let mut results = SynthesisResults {
+        tool: Some(Tool::Nextpnr),
+        ..Default::default()
+    };
+    let mut used = Map::new();
+    let mut utilization = Map::new();
+    let mut fmax: Option<f64> = None;
+    let mut constraint: Option<f64> = None;
+
+    if text.trim_start().starts_with('{') {
+        let report: Value = serde_json::from_str(text)
+            .map_err(|e| vec![LineError::new(e.line() as u64, e.to_string())])?;
+        for (bel, usage) in report["utilization"].as_object().into_iter().flatten() {
+            used.insert(bel.clone(), usage["used"].clone());
+            utilization.insert(bel.clone(), usage.clone());
+        }
+        for clock in report["fmax"]
+            .as_object()
+            .into_iter()
+            .flatten()
+            .map(|(_, c)| c)
+        {
+            if let Some(achieved) = clock["achieved"].as_f64() {
+                fmax = Some(fmax.map_or(achieved, |f| f.min(achieved)));
+            }
+            if let Some(target) = clock["constraint"].as_f64() {
+                constraint = Some(constraint.map_or(target, |c| c.max(target)));
+            }
+        }
+    } else {
+        let mut in_utilization = false;
+        let mut in_fmax = false;
+        for line in text.lines() {
+            let line = line.trim_start_matches("Info:").trim();
+            if line.starts_with("Device utilisation") {
+                in_utilization = true;
+                continue;
+            }
+            // Info: 	         ICESTORM_LC:  1234/ 7680    16%
+            if in_utilization {
+                let parsed = line.split_once(':').and_then(|(bel, usage)| {
+                    let (n, available) = usage.split_once('/')?;
+                    let available = available.split_whitespace().next()?;
+                    Some((
+                        bel.trim(),
+                        n.trim().parse::<i64>().ok()?,
+                        available.parse::<i64>().ok()?,
+                    ))
+                });
+                match parsed {
+                    Some((bel, n, available)) => {
+                        used.insert(bel.to_owned(), Value::from(n));
+                        utilization.insert(
+                            bel.to_owned(),
+                            serde_json::json!({"used": n, "available": available}),
+                        );
+                        continue;
+                    }
+                    None => in_utilization = false,
+                }
+            }
+            // Info: Max frequency for clock 'clk': 123.45 MHz (PASS at 12.00 MHz)
+            if let Some(rest) = line.strip_prefix("Max frequency for clock") {
+                if !in_fmax {
+                    // a later block replaces the estimates of an earlier one
+                    fmax = None;
+                    constraint = None;
+                    in_fmax = true;
+                }
+                let mut numbers = rest
+                    .split_whitespace()
+                    .filter_map(|w| w.trim_start_matches('(').parse::<f64>().ok());
+                if let Some(achieved) = numbers.next() {
+                    fmax = Some(fmax.map_or(achieved, |f| f.min(achieved)));
+                }
+                if let Some(target) = numbers.next() {
+                    constraint = Some(constraint.map_or(target, |c| c.max(target)));
+                }
+            } else if !line.is_empty() {
+                in_fmax = false;
+            }
+        }
+    }
+
+    if used.is_empty() && fmax.is_none() {
+        return Err(vec![LineError::new(
+            0,
+            "no utilization or maximum frequency found",
+        )]);
+    }
+    apply_utilization(&mut results, &used);
+    results.fmax = fmax;
+    results.clock_period = constraint.filter(|c| *c > 0.0).map(|c| 1000.0 / c);
+    results
+        .extra
+        .insert("utilization".to_owned(), Value::Object(utilization));
+
+    Ok(results)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LOG: &str = "\
+Info: Device utilisation:
+Info: 	         ICESTORM_LC:  1234/ 7680    16%
+Info: 	        ICESTORM_RAM:     4/   32    12%
+Info: 	               SB_IO:    24/  256     9%
+Info: 	        ICESTORM_DSP:     0/    0     0%
+
+Info: Placed 1262 cells based on constraints.
+Info: Max frequency for clock 'clk': 80.00 MHz (PASS at 12.00 MHz)
+Info: Max frequency for clock 'spi_clk': 150.00 MHz (PASS at 20.00 MHz)
+
+Info: Routing..
+Info: Max frequency for clock 'clk': 62.50 MHz (PASS at 12.00 MHz)
+Info: Max frequency for clock 'spi_clk': 140.00 MHz (PASS at 20.00 MHz)
+
+Info: Program finished normally.
+";
+
+    #[test]
+    fn parses_log() {
+        let results = parse_report(LOG).unwrap();
+        assert_eq!(results.tool, Some(Tool::Nextpnr));
+        assert_eq!(results.lut, Some(1234));
+        assert_eq!(results.bram, Some(4));
+        assert_eq!(results.io, Some(24));
+        assert_eq!(results.dsp, Some(0));
+        assert_eq!(
+            results.extra["utilization"]["ICESTORM_LC"],
+            serde_json::json!({"used": 1234, "available": 7680})
+        );
+        assert_eq!(results.clock_period, Some(50.0));
+    }
+
+    #[test]
+    fn uses_post_route_fmax() {
+        // the post-placement estimates of 80 MHz are superseded by the routed design
+        assert_eq!(parse_report(LOG).unwrap().fmax, Some(62.5));
+    }
+
+    #[test]
+    fn parses_json_report() {
+        let report = r#"{
+            "utilization": {
+                "TRELLIS_COMB": {"used": 2000, "available": 24288},
+                "TRELLIS_FF": {"used": 800, "available": 24288},
+                "DP16KD": {"used": 3, "available": 56}
+            },
+            "fmax": {
+                "clk": {"achieved": 95.2, "constraint": 50.0},
+                "clk2": {"achieved": 120.0, "constraint": 100.0}
+            }
+        }"#;
+        let results = parse_report(report).unwrap();
+        assert_eq!(results.lut, Some(2000));
+        assert_eq!(results.ff, Some(800));
+        assert_eq!(results.bram, Some(3));
+        assert_eq!(results.fmax, Some(95.2));
+        assert_eq!(results.clock_period, Some(10.0));
+    }
+
+    #[test]
+    fn rejects_logs_without_results() {
+        assert!(parse_report("Info: Program finished normally.\n").is_err());
+    }
+}
@@ -0,0 +1,184 @@
+use serde_json::Value;
+
+use super::LineError;
+use crate::models::synthesis::{SynthesisResults, Tool};
+
+/// `1234` from `1,234 / 32,070 ( 4 % )` or `1,234`
+fn used_count(value: &str) -> Option<i64> {
+    value
+        .split('/')
+        .next()?
+        .trim()
+        .replace(',', "")
+        .parse()
+        .ok()
+}
+
+/// Parse a Quartus fitter summary (`<revision>.fit.summary`).
+///
+/// Logic elements (Cyclone IV, MAX 10) are reported as `lut`; ALM based families report
+/// `alm` instead.
+pub fn parse_fit_summary(text: &str) -> Result<SynthesisResults, Vec<LineError>> {
+    let mut results = SynthesisResults {
+        tool: Some(Tool::Quartus),
+        ..Default::default()
+    };
+    let mut found = false;
+    for (i, line) in text.lines().enumerate() {
+        let Some((key, value)) = line.split_once(" : ") else {
+            continue;
+        };
+        let (key, value) = (key.trim(), value.trim());
+        let count = used_count(value);
+        match key {
+            "Quartus Prime Version" | "Quartus II Version" | "Quartus II 64-Bit Version" => {
+                results.tool_version = value.split_whitespace().next().map(str::to_owned)
+            }
+            "Device" => results.device = Some(value.to_owned()),
+            "Family" => {
+                results
+                    .extra
+                    .insert("family".to_owned(), Value::from(value));
+            }
+            "Total logic elements" => results.lut = count,
+            "Logic utilization (in ALMs)" => {
+                if let Some(alm) = count {
+                    results.extra.insert("alm".to_owned(), Value::from(alm));
+                }
+            }
+            "Total registers" | "Dedicated logic registers" => {
+                results.ff = results.ff.or(count);
+            }
+            "Total pins" => results.io = count,
+            "Total DSP Blocks" => results.dsp = count,
+            "Embedded Multiplier 9-bit elements" => {
+                results.dsp = results.dsp.or(count);
+            }
+            "Total RAM Blocks" | "Total M9Ks" | "Total M10Ks" => results.bram = count,
+            "Total block memory bits" | "Total memory bits" => {
+                if let Some(bits) = count {
+                    results
+                        .extra
+                        .insert("memory_bits".to_owned(), Value::from(bits));
+                }
+            }
+            _ => continue,
+        }
+        if count.is_none() && !matches!(key, "Device" | "Family") && !key.contains("Version") {
+            return Err(vec![LineError::new(
+                i as u64 + 1,
+                format!("could not parse {key}: {value:?}"),
+            )]);
+        }
+        found = true;
+    }
+    if !found {
+        return Err(vec![LineError::new(0, "no fitter summary found")]);
+    }
+    Ok(results)
+}
+
+/// Parse the `Fmax Summary` tables of a Quartus timing analyzer report (`<revision>.sta.rpt`).
+///
+/// `Fmax` is the lowest restricted Fmax over all clocks and timing models.
+pub fn parse_sta(text: &str) -> Result<SynthesisResults, Vec<LineError>> {
+    let lines: Vec<&str> = text.lines().collect();
+    let mut fmax: Option<f64> = None;
+    let mut i = 0;
+    while i < lines.len() {
+        if !(lines[i].starts_with(';') && lines[i].contains("Restricted Fmax")) {
+            i += 1;
+            continue;
+        }
+        // ; Fmax ; Restricted Fmax ; Clock Name ; Note ;
+        i += 1;
+        while i < lines.len() && lines[i].starts_with('+') {
+            i += 1;
+        }
+        while i < lines.len() && lines[i].starts_with(';') {
+            let restricted = lines[i]
+                .split(';')
+                .nth(2)
+                .and_then(|cell| cell.split_whitespace().next())
+                .and_then(|mhz| mhz.parse::<f64>().ok())
+                .ok_or_else(|| vec![LineError::new(i as u64 + 1, "malformed Fmax summary")])?;
+            fmax = Some(fmax.map_or(restricted, |f| f.min(restricted)));
+            i += 1;
+        }
+    }
+    let Some(fmax) = fmax else {
+        return Err(vec![LineError::new(0, "no Fmax summary found")]);
+    };
+    Ok(SynthesisResults {
+        tool: Some(Tool::Quartus),
+        fmax: Some(fmax),
+        ..Default::default()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_fit_summary() {
+        let summary = "\
+Fitter Status : Successful - Mon Aug 21 10:00:00 2023
+Quartus Prime Version : 22.1std.0 Build 915 10/25/2022 SC Lite Edition
+Revision Name : LWC
+Top-level Entity Name : LWC
+Family : Cyclone IV E
+Device : EP4CE22F17C6
+Timing Models : Final
+Total logic elements : 1,234 / 22,320 ( 6 % )
+    Total combinational functions : 1,100 / 22,320 ( 5 % )
+    Dedicated logic registers : 567 / 22,320 ( 3 % )
+Total registers : 567
+Total pins : 24 / 154 ( 16 % )
+Total memory bits : 4,096 / 608,256 ( < 1 % )
+Embedded Multiplier 9-bit elements : 2 / 132 ( 2 % )
+";
+        let results = parse_fit_summary(summary).unwrap();
+        assert_eq!(results.tool, Some(Tool::Quartus));
+        assert_eq!(results.tool_version.as_deref(), Some("22.1std.0"));
+        assert_eq!(results.device.as_deref(), Some("EP4CE22F17C6"));
+        assert_eq!(results.lut, Some(1234));
+        assert_eq!(results.ff, Some(567));
+        assert_eq!(results.io, Some(24));
+        assert_eq!(results.dsp, Some(2));
+        assert_eq!(results.extra["family"], Value::from("Cyclone IV E"));
+        assert_eq!(results.extra["memory_bits"], Value::from(4096));
+    }
+
+    #[test]
+    fn reports_malformed_counts() {
+        let errors = parse_fit_summary("Device : EP4CE22F17C6\nTotal pins : many\n").unwrap_err();
+        assert_eq!(errors[0].line, 2);
+        assert!(parse_fit_summary("nothing to see").is_err());
+    }
+
+    #[test]
+    fn takes_lowest_restricted_fmax() {
+        let sta = "\
++-------------------------------------------------+
+; Slow 1200mV 85C Model Fmax Summary              ;
++------------+-----------------+------------+------+
+; Fmax       ; Restricted Fmax ; Clock Name ; Note ;
++------------+-----------------+------------+------+
+; 180.5 MHz  ; 180.5 MHz       ; clk        ;      ;
++------------+-----------------+------------+------+
+
++-------------------------------------------------+
+; Slow 1200mV 0C Model Fmax Summary               ;
++------------+-----------------+------------+------+
+; Fmax       ; Restricted Fmax ; Clock Name ; Note ;
++------------+-----------------+------------+------+
+; 195.2 MHz  ; 195.2 MHz       ; clk        ;      ;
+; 450.0 MHz  ; 250.0 MHz       ; spi_clk    ; limit due to minimum period restriction ;
++------------+-----------------+------------+------+
+";
+        let results = parse_sta(sta).unwrap();
+        assert_eq!(results.fmax, Some(180.5));
+        assert!(parse_sta("; Fmax Summary ;\n").is_err());
+    }
+}
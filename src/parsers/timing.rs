@@ -0,0 +1,295 @@
+use serde_json::{Map, Value};
+
+use super::LineError;
+use crate::models::timing::{normalize_operation, TimingRow};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Column {
+    MsgId,
+    Operation,
+    AdLen,
+    MsgLen,
+    Cycles,
+    Extra,
+}
+
+impl Column {
+    fn name(self) -> &'static str {
+        match self {
+            Column::MsgId => "msg_id",
+            Column::Operation => "operation",
+            Column::AdLen => "ad_len",
+            Column::MsgLen => "msg_len",
+            Column::Cycles => "cycles",
+            Column::Extra => "",
+        }
+    }
+}
+
+/// Lower-case a header and collapse everything that is not alphanumeric into `_`,
+/// e.g. `"Msg Size (bytes)"` becomes `"msg_size_bytes"`.
+pub fn normalize_header(header: &str) -> String {
+    header
+        .to_lowercase()
+        .split(|c: char| !c.is_ascii_alphanumeric())
+        .filter(|s| !s.is_empty())
+        .collect::<Vec<_>>()
+        .join("_")
+}
+
+fn classify(header: &str) -> Column {
+    match header {
+        "msg_id" | "msgid" | "message_id" | "id" => Column::MsgId,
+        "operation" | "op" | "mode" => Column::Operation,
+        "ad_len" | "ad_size" | "ad_bytes" | "adbytes" | "ad_length" | "ad" | "ad_size_bytes" => {
+            Column::AdLen
+        }
+        "msg_len" | "msg_size" | "msg_bytes" | "msgbytes" | "msg_length" | "msg"
+        | "message_length" | "pt_len" | "ct_len" | "data_len" | "msg_size_bytes" => Column::MsgLen,
+        "cycles"
+        | "latency"
+        | "exec_cycles"
+        | "execution_cycles"
+        | "clock_cycles"
+        | "total_cycles"
+        | "ccs"
+        | "actual_execution_ccs" => Column::Cycles,
+        _ => Column::Extra,
+    }
+}
+
+pub(crate) fn parse_integer(value: &str) -> Result<i64, String> {
+    value
+        .trim()
+        .replace('_', "")
+        .parse::<i64>()
+        .map_err(|_| format!("{value:?} is not an integer"))
+}
+
+/// Give a cell of an unknown column the most specific JSON type it fits.
+pub(crate) fn typed_value(value: &str) -> Value {
+    let value = value.trim();
+    if value.is_empty() {
+        Value::Null
+    } else if let Ok(i) = value.parse::<i64>() {
+        Value::from(i)
+    } else if let Some(f) = value.parse::<f64>().ok().filter(|f| f.is_finite()) {
+        Value::from(f)
+    } else {
+        Value::from(value)
+    }
+}
+
+/// Parse a timing CSV as produced by the LWC testbench into normalized rows.
+///
+/// All malformed lines are reported at once, with 1-based line numbers.
+pub fn parse_timing_csv(input: &str) -> Result<Vec<TimingRow>, Vec<LineError>> {
+    let mut reader = csv::ReaderBuilder::new()
+        .trim(csv::Trim::All)
+        .comment(Some(b'#'))
+        .from_reader(input.as_bytes());
+
+    let headers = reader
+        .headers()
+        .map_err(|e| vec![LineError::new(1, e.to_string())])?
+        .clone();
+    let header_line = headers.position().map_or(1, |p| p.line());
+
+    let names: Vec<String> = headers.iter().map(normalize_header).collect();
+    let columns: Vec<Column> = names.iter().map(|h| classify(h)).collect();
+
+    let mut errors = Vec::new();
+    for (i, column) in columns.iter().enumerate() {
+        if *column != Column::Extra && columns[..i].contains(column) {
+            errors.push(LineError::new(
+                header_line,
+                format!(
+                    "duplicate column for {:?}: {:?}",
+                    column.name(),
+                    &headers[i]
+                ),
+            ));
+        }
+    }
+    for required in [Column::Operation, Column::MsgLen, Column::Cycles] {
+        if !columns.contains(&required) {
+            errors.push(LineError::new(
+                header_line,
+                format!("missing required column {:?}", required.name()),
+            ));
+        }
+    }
+    if !errors.is_empty() {
+        return Err(errors);
+    }
+
+    let mut rows = Vec::new();
+    for record in reader.records() {
+        let record = match record {
+            Ok(record) => record,
+            Err(e) => {
+                let line = e.position().map_or(0, |p| p.line());
+                errors.push(LineError::new(line, e.to_string()));
+                continue;
+            }
+        };
+        let line = record.position().map_or(0, |p| p.line());
+
+        let mut row = TimingRow {
+            msg_id: None,
+            operation: String::new(),
+            ad_len: 0,
+            msg_len: 0,
+            cycles: 0,
+            extra: Map::new(),
+        };
+        for ((value, column), name) in record.iter().zip(&columns).zip(&names) {
+            let parsed = match column {
+                Column::Operation if value.is_empty() => Err("empty operation".to_owned()),
+                Column::Operation => {
+                    row.operation = normalize_operation(value);
+                    Ok(())
+                }
+                Column::MsgId => parse_integer(value).map(|v| row.msg_id = Some(v)),
+                Column::AdLen => parse_integer(value).map(|v| row.ad_len = v),
+                Column::MsgLen => parse_integer(value).map(|v| row.msg_len = v),
+                Column::Cycles => parse_integer(value).map(|v| row.cycles = v),
+                Column::Extra => {
+                    row.extra.insert(name.clone(), typed_value(value));
+                    Ok(())
+                }
+            };
+            if let Err(msg) = parsed {
+                errors.push(LineError::new(line, format!("{}: {msg}", column.name())));
+            }
+        }
+        if row.ad_len < 0 || row.msg_len < 0 || row.cycles < 0 {
+            errors.push(LineError::new(
+                line,
+                "sizes and cycles must not be negative",
+            ));
+        }
+        rows.push(row);
+    }
+
+    if errors.is_empty() {
+        Ok(rows)
+    } else {
+        Err(errors)
+    }
+}
+
+/// Best-effort conversion of a `timing` JSON array into normalized rows.
+///
+/// Accepts both normalized rows and the all-string rows of older clients that read the CSV
+/// with `csv.DictReader`. Rows lacking an operation, message size or cycle count are skipped.
+pub fn rows_from_json(timing: &Value) -> Vec<TimingRow> {
+    let Some(rows) = timing.as_array() else {
+        return Vec::new();
+    };
+    rows.iter()
+        .filter_map(Value::as_object)
+        .filter_map(|object| {
+            let mut operation = None;
+            let mut msg_id = None;
+            let mut ad_len = Some(0);
+            let mut msg_len = None;
+            let mut cycles = None;
+            let mut extra = Map::new();
+            for (key, value) in object {
+                let name = normalize_header(key);
+                let column = classify(&name);
+                let integer = match value {
+                    Value::Number(n) => n.as_i64(),
+                    Value::String(s) => parse_integer(s).ok(),
+                    _ => None,
+                };
+                match column {
+                    Column::Operation => operation = value.as_str().map(normalize_operation),
+                    Column::MsgId => msg_id = integer,
+                    Column::AdLen => ad_len = integer,
+                    Column::MsgLen => msg_len = integer,
+                    Column::Cycles => cycles = integer,
+                    Column::Extra => {
+                        let value = match value {
+                            Value::String(s) => typed_value(s),
+                            _ => value.clone(),
+                        };
+                        extra.insert(name, value);
+                    }
+                }
+            }
+            Some(TimingRow {
+                msg_id,
+                operation: operation.filter(|op| !op.is_empty())?,
+                ad_len: ad_len?,
+                msg_len: msg_len?,
+                cycles: cycles?,
+                extra,
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn parses_testbench_csv() {
+        let csv = "\
+# generated by the LWC testbench
+Msg ID, Operation, AD Size (bytes), Msg Size (bytes), Actual Execution CCs, Notes
+1, Encryption, 0, 16, 45, ok
+2, decrypt, 16, 1_536, 1200,
+";
+        let rows = parse_timing_csv(csv).unwrap();
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[0].msg_id, Some(1));
+        assert_eq!(rows[0].operation, "enc");
+        assert_eq!(
+            (rows[0].ad_len, rows[0].msg_len, rows[0].cycles),
+            (0, 16, 45)
+        );
+        assert_eq!(rows[0].extra["notes"], json!("ok"));
+        assert_eq!(rows[1].operation, "dec");
+        assert_eq!(
+            (rows[1].ad_len, rows[1].msg_len, rows[1].cycles),
+            (16, 1536, 1200)
+        );
+        assert_eq!(rows[1].extra["notes"], Value::Null);
+    }
+
+    #[test]
+    fn reports_missing_and_duplicate_columns() {
+        let errors = parse_timing_csv("operation,msg_len,msg_size\nenc,16,16\n").unwrap_err();
+        let messages: Vec<&str> = errors.iter().map(|e| e.message.as_str()).collect();
+        assert!(messages[0].starts_with("duplicate column for \"msg_len\""));
+        assert_eq!(messages[1], "missing required column \"cycles\"");
+        assert!(errors.iter().all(|e| e.line == 1));
+    }
+
+    #[test]
+    fn reports_all_malformed_lines() {
+        let csv = "operation,msg_len,cycles\nenc,16,x\nenc,16,10\n,-1,10\n";
+        let errors = parse_timing_csv(csv).unwrap_err();
+        let lines: Vec<u64> = errors.iter().map(|e| e.line).collect();
+        assert_eq!(lines, [2, 4, 4]);
+        assert_eq!(errors[0].message, "cycles: \"x\" is not an integer");
+    }
+
+    #[test]
+    fn reads_string_rows_of_older_clients() {
+        let timing = json!([
+            {"operation": "Enc", "msg_len": "64", "ad_len": "0", "cycles": "100", "power": "1.5"},
+            {"operation": "enc", "msg_len": 16},
+        ]);
+        let rows = rows_from_json(&timing);
+        assert_eq!(rows.len(), 1);
+        assert_eq!(rows[0].operation, "enc");
+        assert_eq!((rows[0].msg_len, rows[0].cycles), (64, 100));
+        assert_eq!(rows[0].extra["power"], json!(1.5));
+    }
+}
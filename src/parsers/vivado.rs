@@ -0,0 +1,437 @@
+use serde_json::{Map, Value};
+
+use super::{timing::typed_value, LineError};
+use crate::models::synthesis::{SynthesisResults, Tool};
+
+/// A `+---+` delimited table of a Vivado report.
+struct Table<'a> {
+    header: Vec<String>,
+    /// cells of each row, untrimmed, with the 1-based line number of the row
+    rows: Vec<(u64, Vec<&'a str>)>,
+}
+
+fn split_row(line: &str) -> Option<Vec<&str>> {
+    let inner = line.trim().strip_prefix('|')?.strip_suffix('|')?;
+    Some(inner.split('|').collect())
+}
+
+fn is_separator(line: &str) -> bool {
+    let line = line.trim();
+    line.starts_with('+') && line.chars().all(|c| c == '+' || c == '-')
+}
+
+fn tables(text: &str) -> Vec<Table<'_>> {
+    let lines: Vec<&str> = text.lines().collect();
+    let mut tables = Vec::new();
+    let mut i = 0;
+    while i + 2 < lines.len() {
+        let header = match split_row(lines[i + 1]) {
+            Some(header) if is_separator(lines[i]) && is_separator(lines[i + 2]) => header,
+            _ => {
+                i += 1;
+                continue;
+            }
+        };
+        let mut table = Table {
+            header: header.iter().map(|h| h.trim().to_owned()).collect(),
+            rows: Vec::new(),
+        };
+        i += 3;
+        while i < lines.len() && !is_separator(lines[i]) {
+            if let Some(row) = split_row(lines[i]) {
+                table.rows.push((i as u64 + 1, row));
+            }
+            i += 1;
+        }
+        tables.push(table);
+    }
+    tables
+}
+
+/// Value of a `| Key : Value` line of the report banner.
+fn banner_field(text: &str, key: &str) -> Option<String> {
+    text.lines().find_map(|line| {
+        let (k, v) = line.trim().strip_prefix('|')?.split_once(':')?;
+        (k.trim() == key).then(|| v.trim().to_owned())
+    })
+}
+
+/// `2022.1` from `Vivado v.2022.1 (lin64) Build 3526262 ...`
+fn tool_version(text: &str) -> Option<String> {
+    let version = banner_field(text, "Tool Version")?;
+    let version = version.split_whitespace().nth(1)?;
+    Some(version.trim_start_matches("v.").to_owned())
+}
+
+fn parse_number(cell: &str, line: u64) -> Result<f64, LineError> {
+    cell.trim()
+        .parse()
+        .map_err(|_| LineError::new(line, format!("{:?} is not a number", cell.trim())))
+}
+
+/// Parse the output of `report_utilization`.
+pub fn parse_utilization(text: &str) -> Result<SynthesisResults, Vec<LineError>> {
+    let mut results = SynthesisResults {
+        tool_version: tool_version(text),
+        device: banner_field(text, "Device"),
+        ..Default::default()
+    };
+    let mut errors = Vec::new();
+    let mut found = false;
+
+    for table in tables(text) {
+        if table.header.first().map(String::as_str) != Some("Site Type") {
+            continue;
+        }
+        let Some(used) = table.header.iter().position(|h| h == "Used") else {
+            continue;
+        };
+        found = true;
+        for (line, row) in &table.rows {
+            let site_type = row[0].trim().trim_end_matches('*').trim();
+            let Some(cell) = row.get(used) else {
+                errors.push(LineError::new(*line, "missing Used column"));
+                continue;
+            };
+            let value = match parse_number(cell, *line) {
+                Ok(value) => value,
+                Err(e) => {
+                    errors.push(e);
+                    continue;
+                }
+            };
+            let count = value.round() as i64;
+            match site_type {
+                "Slice LUTs" | "CLB LUTs" => results.lut = Some(count),
+                "Slice Registers" | "CLB Registers" => {
+                    results.ff.get_or_insert(count);
+                }
+                "Register as Flip Flop" => results.ff = Some(count),
+                "Register as Latch" => results.latch = Some(count),
+                "Slice" | "CLB" => results.slice = Some(count),
+                "LUT as Memory" => results.lutram = Some(count),
+                "LUT as Shift Register" => results.srl = Some(count),
+                "Block RAM Tile" => results.bram_tile = Some(value),
+                "DSPs" | "DSP48E1 only" | "DSP48E2 only" => {
+                    results.dsp.get_or_insert(count);
+                }
+                "Bonded IOB" => results.io = Some(count),
+                _ => {}
+            }
+        }
+    }
+
+    if !found {
+        errors.push(LineError::new(0, "no utilization table found"));
+    }
+    if errors.is_empty() {
+        Ok(results)
+    } else {
+        Err(errors)
+    }
+}
+
+/// Parse the output of `report_utilization -hierarchical` into nested instances, with the
+/// sub-instances of each instance under `@children`.
+pub fn parse_hierarchical_utilization(text: &str) -> Result<Value, Vec<LineError>> {
+    let Some(table) = tables(text)
+        .into_iter()
+        .find(|t| t.header.first().map(String::as_str) == Some("Instance"))
+    else {
+        return Err(vec![LineError::new(
+            0,
+            "no hierarchical utilization table found",
+        )]);
+    };
+
+    // stack of (depth, instance name, instance) of the current path through the hierarchy
+    let mut stack: Vec<(usize, String, Map<String, Value>)> = Vec::new();
+    let mut roots = Map::new();
+
+    fn attach(
+        stack: &mut Vec<(usize, String, Map<String, Value>)>,
+        roots: &mut Map<String, Value>,
+        depth: usize,
+    ) {
+        while matches!(stack.last(), Some((d, _, _)) if *d >= depth) {
+            let (_, name, instance) = stack.pop().unwrap();
+            let siblings = match stack.last_mut() {
+                Some((_, _, parent)) => parent
+                    .entry("@children")
+                    .or_insert_with(|| Value::Object(Map::new()))
+                    .as_object_mut()
+                    .unwrap(),
+                None => &mut *roots,
+            };
+            siblings.insert(name, Value::Object(instance));
+        }
+    }
+
+    for (line, row) in &table.rows {
+        if row.len() != table.header.len() {
+            return Err(vec![LineError::new(
+                *line,
+                format!(
+                    "expected {} columns, found {}",
+                    table.header.len(),
+                    row.len()
+                ),
+            )]);
+        }
+        let name_cell = row[0].strip_prefix(' ').unwrap_or(row[0]);
+        let depth = name_cell.len() - name_cell.trim_start().len();
+        let name = name_cell.trim().to_owned();
+
+        let mut instance = Map::new();
+        for (header, cell) in table.header.iter().zip(row).skip(1) {
+            instance.insert(header.clone(), typed_value(cell));
+        }
+
+        attach(&mut stack, &mut roots, depth);
+        stack.push((depth, name, instance));
+    }
+    attach(&mut stack, &mut roots, 0);
+
+    Ok(Value::Object(roots))
+}
+
+/// Parse the output of `report_timing_summary`.
+///
+/// `Fmax` is derived from the period of the first clock and the worst negative slack.
+pub fn parse_timing_summary(text: &str) -> Result<SynthesisResults, Vec<LineError>> {
+    let lines: Vec<&str> = text.lines().collect();
+    let mut results = SynthesisResults {
+        tool_version: tool_version(text),
+        device: banner_field(text, "Device"),
+        ..Default::default()
+    };
+
+    // the WNS table is a whitespace separated header, a dashed underline and the values
+    let wns_line = lines
+        .iter()
+        .position(|line| line.trim_start().starts_with("WNS(ns)"))
+        .map(|i| i + 2)
+        .filter(|i| *i < lines.len());
+    let Some(wns_line) = wns_line else {
+        return Err(vec![LineError::new(0, "no design timing summary found")]);
+    };
+    let wns = lines[wns_line]
+        .split_whitespace()
+        .next()
+        .unwrap_or_default();
+    // `inf` when there are no constrained paths
+    if wns != "inf" {
+        results.wns = Some(parse_number(wns, wns_line as u64 + 1).map_err(|e| vec![e])?);
+    }
+
+    let clock_line = lines.iter().position(|line| {
+        let line = line.trim_start();
+        line.starts_with("Clock") && line.contains("Period(ns)")
+    });
+    let Some(clock_line) = clock_line else {
+        return Err(vec![LineError::new(0, "no clock summary found")]);
+    };
+    let mut clocks = Map::new();
+    let mut period_line = clock_line;
+    for (i, line) in lines.iter().enumerate().skip(clock_line + 2) {
+        if line.trim().is_empty() || line.starts_with('-') {
+            break;
+        }
+        // Clock  Waveform(ns)  Period(ns)  Frequency(MHz), the waveform is `{rise fall}`
+        let Some((name, rest)) = line.trim().split_once(char::is_whitespace) else {
+            return Err(vec![LineError::new(
+                i as u64 + 1,
+                "malformed clock summary",
+            )]);
+        };
+        let period = rest
+            .rsplit_once('}')
+            .and_then(|(_, rest)| rest.split_whitespace().next())
+            .ok_or_else(|| vec![LineError::new(i as u64 + 1, "malformed clock summary")])?;
+        let period = parse_number(period, i as u64 + 1).map_err(|e| vec![e])?;
+        if results.clock_period.is_none() {
+            results.clock_period = Some(period);
+            period_line = i;
+        }
+        clocks.insert(name.to_owned(), Value::from(period));
+    }
+    let Some(period) = results.clock_period else {
+        return Err(vec![LineError::new(
+            clock_line as u64 + 1,
+            "no clocks found",
+        )]);
+    };
+    let wns = results.wns.unwrap_or(0.0);
+    let achieved = period - wns;
+    // a slack as large as the period would mean an infinite or negative Fmax
+    if achieved.is_nan() || achieved <= 0.0 {
+        return Err(vec![LineError::new(
+            period_line as u64 + 1,
+            format!("clock period of {period} ns minus a WNS of {wns} ns is not positive"),
+        )]);
+    }
+    results.fmax = Some(1000.0 / achieved);
+    results
+        .extra
+        .insert("clocks".to_owned(), Value::Object(clocks));
+
+    Ok(results)
+}
+
+/// Combine the Vivado reports of a run into canonical synthesis results.
+pub fn parse_reports(
+    utilization: Option<&str>,
+    hierarchical_utilization: Option<&str>,
+    timing_summary: Option<&str>,
+) -> Result<SynthesisResults, Vec<LineError>> {
+    let mut results = SynthesisResults {
+        tool: Some(Tool::Vivado),
+        ..Default::default()
+    };
+    if let Some(text) = utilization {
+        results.merge(parse_utilization(text)?);
+    }
+    if let Some(text) = timing_summary {
+        results.merge(parse_timing_summary(text)?);
+    }
+    if let Some(text) = hierarchical_utilization {
+        results.hierarchical_utilization = Some(parse_hierarchical_utilization(text)?);
+    }
+    Ok(results)
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    const UTILIZATION: &str = "\
+| Tool Version : Vivado v.2022.1 (lin64) Build 3526262 Mon Apr 18 15:47:01 MDT 2022
+| Device       : 7a12tcsg325-3
+
+1. Slice Logic
+--------------
+
++-------------------------+------+-------+------------+-----------+-------+
+|        Site Type        | Used | Fixed | Prohibited | Available | Util% |
++-------------------------+------+-------+------------+-----------+-------+
+| Slice LUTs*             | 1234 |     0 |          0 |      8000 | 15.43 |
+|   LUT as Logic          | 1200 |     0 |          0 |      8000 | 15.00 |
+|   LUT as Memory         |   34 |     0 |          0 |      5000 |  0.68 |
+| Slice Registers         |  567 |     0 |          0 |     16000 |  3.54 |
+|   Register as Flip Flop |  560 |     0 |          0 |     16000 |  3.50 |
+|   Register as Latch     |    7 |     0 |          0 |     16000 |  0.04 |
++-------------------------+------+-------+------------+-----------+-------+
+
+3. Memory
+---------
+
++----------------+------+-------+------------+-----------+-------+
+|    Site Type   | Used | Fixed | Prohibited | Available | Util% |
++----------------+------+-------+------------+-----------+-------+
+| Block RAM Tile |  1.5 |     0 |          0 |        20 |  7.50 |
++----------------+------+-------+------------+-----------+-------+
+";
+
+    const TIMING_SUMMARY: &str = "\
+| Tool Version : Vivado v.2022.1 (lin64) Build 3526262 Mon Apr 18 15:47:01 MDT 2022
+
+------------------------------------------------------------------------------------------------
+| Design Timing Summary
+| ---------------------
+------------------------------------------------------------------------------------------------
+
+    WNS(ns)      TNS(ns)  TNS Failing Endpoints  TNS Total Endpoints
+    -------      -------  ---------------------  -------------------
+      1.000        0.000                      0                 2345
+
+
+------------------------------------------------------------------------------------------------
+| Clock Summary
+| -------------
+------------------------------------------------------------------------------------------------
+
+Clock  Waveform(ns)       Period(ns)      Frequency(MHz)
+-----  ------------       ----------      --------------
+clk    {0.000 2.500}      5.000           200.000
+";
+
+    const HIERARCHICAL: &str = "\
++----------------------+--------------+------------+
+|       Instance       |    Module    | Total LUTs |
++----------------------+--------------+------------+
+| LWC                  |        (top) |       1234 |
+|   Inst_Cipher        |   CryptoCore |       1000 |
+|     Inst_Perm        |  Permutation |        800 |
+|   Inst_PreProcessor  | PreProcessor |        200 |
++----------------------+--------------+------------+
+";
+
+    #[test]
+    fn parses_utilization() {
+        let results = parse_utilization(UTILIZATION).unwrap();
+        assert_eq!(results.tool_version.as_deref(), Some("2022.1"));
+        assert_eq!(results.device.as_deref(), Some("7a12tcsg325-3"));
+        assert_eq!(results.lut, Some(1234));
+        assert_eq!(results.lutram, Some(34));
+        assert_eq!(results.ff, Some(560));
+        assert_eq!(results.latch, Some(7));
+        assert_eq!(results.bram_tile, Some(1.5));
+    }
+
+    #[test]
+    fn reports_malformed_utilization() {
+        let text = UTILIZATION.replace("|  567 |", "|  abc |");
+        let errors = parse_utilization(&text).unwrap_err();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].line, 13);
+        assert!(parse_utilization("no tables here").is_err());
+    }
+
+    #[test]
+    fn parses_timing_summary() {
+        let results = parse_timing_summary(TIMING_SUMMARY).unwrap();
+        assert_eq!(results.wns, Some(1.0));
+        assert_eq!(results.clock_period, Some(5.0));
+        // the 5 ns clock met timing with 1 ns to spare
+        assert_eq!(results.fmax, Some(250.0));
+        assert_eq!(results.extra["clocks"], json!({"clk": 5.0}));
+    }
+
+    #[test]
+    fn rejects_slack_of_a_whole_period() {
+        for wns in ["5.000", "7.500", "nan"] {
+            let text = TIMING_SUMMARY.replace("1.000        0.000", &format!("{wns}        0.000"));
+            let errors = parse_timing_summary(&text).unwrap_err();
+            assert_eq!(errors[0].line, 20);
+        }
+    }
+
+    #[test]
+    fn nests_hierarchical_utilization() {
+        let hierarchy = parse_hierarchical_utilization(HIERARCHICAL).unwrap();
+        let top = &hierarchy["LWC"];
+        assert_eq!(top["Total LUTs"], json!(1234));
+        assert_eq!(
+            top["@children"]["Inst_Cipher"]["Module"],
+            json!("CryptoCore")
+        );
+        assert_eq!(
+            top["@children"]["Inst_Cipher"]["@children"]["Inst_Perm"]["Total LUTs"],
+            json!(800)
+        );
+        assert_eq!(
+            top["@children"]["Inst_PreProcessor"]["Total LUTs"],
+            json!(200)
+        );
+    }
+
+    #[test]
+    fn combines_reports() {
+        let results = parse_reports(Some(UTILIZATION), None, Some(TIMING_SUMMARY)).unwrap();
+        assert_eq!(results.tool, Some(Tool::Vivado));
+        assert_eq!(results.lut, Some(1234));
+        assert_eq!(results.fmax, Some(250.0));
+    }
+}
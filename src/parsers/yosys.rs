@@ -0,0 +1,145 @@
+use serde_json::{Map, Value};
+
+use super::LineError;
+use crate::models::synthesis::{SynthesisResults, Tool};
+
+fn json_error(e: serde_json::Error) -> Vec<LineError> {
+    vec![LineError::new(e.line() as u64, e.to_string())]
+}
+
+/// Sum the cells of all types matching `f`.
+fn count_cells(cells: &Map<String, Value>, f: impl Fn(&str) -> bool) -> Option<i64> {
+    let mut matched = cells
+        .iter()
+        .filter(|(cell_type, _)| f(cell_type.trim_start_matches('\\')))
+        .filter_map(|(_, n)| n.as_i64())
+        .peekable();
+    matched.peek().is_some().then(|| matched.sum())
+}
+
+/// Parse the output of Yosys `stat -json`.
+///
+/// Cells are counted over the whole design when Yosys reports it, or over all modules
+/// otherwise. LUT, flip-flop, DSP and block RAM primitives of the iCE40, ECP5, Xilinx and
+/// generic techmap libraries are recognized.
+pub fn parse_stat(text: &str) -> Result<SynthesisResults, Vec<LineError>> {
+    let stat: Value = serde_json::from_str(text).map_err(json_error)?;
+
+    let tool_version = stat["creator"]
+        .as_str()
+        .and_then(|creator| creator.split_whitespace().nth(1))
+        .map(str::to_owned);
+
+    let mut cells = Map::new();
+    let mut num_cells = 0;
+    let scopes: Vec<&Value> = match stat.get("design") {
+        Some(design) => vec![design],
+        None => stat["modules"]
+            .as_object()
+            .map(|modules| modules.values().collect())
+            .unwrap_or_default(),
+    };
+    if scopes.is_empty() {
+        return Err(vec![LineError::new(
+            0,
+            "no design or modules in stat output",
+        )]);
+    }
+    for scope in scopes {
+        num_cells += scope["num_cells"].as_i64().unwrap_or(0);
+        for (cell_type, n) in scope["num_cells_by_type"].as_object().into_iter().flatten() {
+            let count = cells
+                .entry(cell_type.clone())
+                .or_insert_with(|| Value::from(0));
+            *count = Value::from(count.as_i64().unwrap_or(0) + n.as_i64().unwrap_or(0));
+        }
+    }
+
+    let mut results = SynthesisResults {
+        tool: Some(Tool::Yosys),
+        tool_version,
+        lut: count_cells(&cells, |t| {
+            t == "$lut"
+                || t.ends_with("LUT4")
+                || (t.starts_with("LUT") && t[3..].parse::<u8>().is_ok())
+        }),
+        ff: count_cells(&cells, |t| {
+            t.contains("DFF") || t == "TRELLIS_FF" || (t.starts_with("FD") && t.len() == 4)
+        }),
+        latch: count_cells(&cells, |t| t.contains("DLATCH") || t.starts_with("LD")),
+        dsp: count_cells(&cells, |t| {
+            t == "SB_MAC16" || t.starts_with("MULT18X18") || t.starts_with("DSP48")
+        }),
+        bram: count_cells(&cells, |t| {
+            t.starts_with("SB_RAM40") || t == "DP16KD" || t == "PDPW16KD"
+        }),
+        ..Default::default()
+    };
+    let ramb36 = count_cells(&cells, |t| t.starts_with("RAMB36"));
+    let ramb18 = count_cells(&cells, |t| t.starts_with("RAMB18"));
+    if ramb36.is_some() || ramb18.is_some() {
+        results.bram_tile = Some(ramb36.unwrap_or(0) as f64 + 0.5 * ramb18.unwrap_or(0) as f64);
+    }
+    results
+        .extra
+        .insert("cells".to_owned(), Value::from(num_cells));
+    results
+        .extra
+        .insert("cells_by_type".to_owned(), Value::Object(cells));
+
+    Ok(results)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn counts_design_cells() {
+        let stat = r#"{
+            "creator": "Yosys 0.33 (git sha1 2584903a060)",
+            "design": {
+                "num_cells": 1830,
+                "num_cells_by_type": {
+                    "SB_LUT4": 1200,
+                    "SB_DFF": 300,
+                    "SB_DFFE": 200,
+                    "SB_CARRY": 100,
+                    "SB_RAM40_4K": 4,
+                    "SB_MAC16": 2,
+                    "SB_IO": 24
+                }
+            }
+        }"#;
+        let results = parse_stat(stat).unwrap();
+        assert_eq!(results.tool, Some(Tool::Yosys));
+        assert_eq!(results.tool_version.as_deref(), Some("0.33"));
+        assert_eq!(results.lut, Some(1200));
+        assert_eq!(results.ff, Some(500));
+        assert_eq!(results.bram, Some(4));
+        assert_eq!(results.dsp, Some(2));
+        assert_eq!(results.latch, None);
+        assert_eq!(results.extra["cells"], Value::from(1830));
+    }
+
+    #[test]
+    fn sums_modules_without_design() {
+        let stat = r#"{
+            "modules": {
+                "\\top": {"num_cells": 3, "num_cells_by_type": {"LUT6": 2, "FDRE": 1}},
+                "\\core": {"num_cells": 4, "num_cells_by_type": {"LUT3": 1, "RAMB18E1": 1, "RAMB36E1": 2}}
+            }
+        }"#;
+        let results = parse_stat(stat).unwrap();
+        assert_eq!(results.lut, Some(3));
+        assert_eq!(results.ff, Some(1));
+        assert_eq!(results.bram_tile, Some(2.5));
+        assert_eq!(results.extra["cells"], Value::from(7));
+    }
+
+    #[test]
+    fn rejects_other_json() {
+        assert!(parse_stat("{}").is_err());
+        assert_eq!(parse_stat("{\n\"design\":").unwrap_err()[0].line, 2);
+    }
+}
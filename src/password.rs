@@ -0,0 +1,306 @@
+use std::env;
+
+use argon2::{Config, Variant, Version};
+use rand::RngCore;
+use secrecy::{ExposeSecret, Secret};
+
+use crate::{error::AppError, utils::env_or};
+
+/// Bytes of the random salt of every password.
+const SALT_LEN: usize = 16;
+
+/// Argon2 parameters of new password hashes.
+#[derive(Debug)]
+pub struct PasswordConfig {
+    pub variant: Variant,
+    /// memory in KiB
+    pub memory: u32,
+    pub iterations: u32,
+    pub parallelism: u32,
+    /// server-wide secret mixed into every hash, kept out of the database
+    pub pepper: Option<Secret<String>>,
+}
+
+impl Default for PasswordConfig {
+    fn default() -> Self {
+        Self {
+            variant: Variant::Argon2id,
+            memory: 19 * 1024,
+            iterations: 2,
+            parallelism: 1,
+            pepper: None,
+        }
+    }
+}
+
+fn parse_variant(name: &str) -> Result<Variant, String> {
+    match name.trim().to_lowercase().as_str() {
+        "argon2d" => Ok(Variant::Argon2d),
+        "argon2i" => Ok(Variant::Argon2i),
+        "argon2id" => Ok(Variant::Argon2id),
+        _ => Err(format!(
+            "invalid value of ARGON2_VARIANT: {name}, expected argon2id, argon2i or argon2d"
+        )),
+    }
+}
+
+impl PasswordConfig {
+    /// Read the parameters from `ARGON2_VARIANT`, `ARGON2_MEMORY_KIB`, `ARGON2_ITERATIONS`,
+    /// `ARGON2_PARALLELISM` and `PASSWORD_PEPPER`, falling back to the defaults.
+    pub fn from_env() -> Result<Self, String> {
+        let default = Self::default();
+        Ok(Self {
+            variant: match env::var("ARGON2_VARIANT") {
+                Ok(name) => parse_variant(&name)?,
+                Err(_) => default.variant,
+            },
+            memory: env_or("ARGON2_MEMORY_KIB", default.memory)?,
+            iterations: env_or("ARGON2_ITERATIONS", default.iterations)?,
+            parallelism: env_or("ARGON2_PARALLELISM", default.parallelism)?,
+            pepper: env::var("PASSWORD_PEPPER")
+                .ok()
+                .filter(|pepper| !pepper.is_empty())
+                .map(Secret::new),
+        })
+    }
+}
+
+/// The outcome of checking a password against its stored hash.
+#[derive(Debug, PartialEq, Eq)]
+pub enum PasswordMatch {
+    Wrong,
+    Correct,
+    /// correct, but the hash should be replaced with one of the current parameters
+    Outdated,
+}
+
+/// The parameters of an encoded hash, e.g. `$argon2id$v=19$m=19456,t=2,p=1$<salt>$<hash>`.
+#[derive(Debug)]
+struct HashParams<'a> {
+    variant: &'a str,
+    version: u32,
+    memory: u32,
+    iterations: u32,
+    parallelism: u32,
+    salt: &'a str,
+}
+
+fn parse_hash(encoded: &str) -> Option<HashParams<'_>> {
+    let mut parts = encoded.strip_prefix('$')?.split('$');
+    let variant = parts.next()?;
+    let mut params = parts.next()?;
+    // hashes of version 0x10 have no version field
+    let version = match params.strip_prefix("v=") {
+        Some(version) => {
+            params = parts.next()?;
+            version.parse().ok()?
+        }
+        None => 0x10,
+    };
+    let (mut memory, mut iterations, mut parallelism) = (None, None, None);
+    for param in params.split(',') {
+        let (name, value) = param.split_once('=')?;
+        let value = value.parse().ok();
+        match name {
+            "m" => memory = value,
+            "t" => iterations = value,
+            "p" => parallelism = value,
+            _ => {}
+        }
+    }
+    Some(HashParams {
+        variant,
+        version,
+        memory: memory?,
+        iterations: iterations?,
+        parallelism: parallelism?,
+        salt: parts.next()?,
+    })
+}
+
+/// Hashes and verifies passwords with Argon2.
+#[derive(Debug, Default)]
+pub struct PasswordHasher {
+    pub config: PasswordConfig,
+}
+
+impl PasswordHasher {
+    pub fn new(config: PasswordConfig) -> Self {
+        Self { config }
+    }
+
+    fn pepper(&self) -> &[u8] {
+        self.config
+            .pepper
+            .as_ref()
+            .map_or(&[][..], |pepper| pepper.expose_secret().as_bytes())
+    }
+
+    /// Hash `password` with a new random salt.
+    pub fn hash(&self, password: &str) -> Result<String, AppError> {
+        let mut salt = [0u8; SALT_LEN];
+        rand::thread_rng().fill_bytes(&mut salt);
+        let config = Config {
+            variant: self.config.variant,
+            version: Version::Version13,
+            mem_cost: self.config.memory,
+            time_cost: self.config.iterations,
+            lanes: self.config.parallelism,
+            secret: self.pepper(),
+            ..Config::default()
+        };
+        argon2::hash_encoded(password.as_bytes(), &salt, &config).map_err(|e| {
+            tracing::error!("hashing a password failed: {e}");
+            AppError::InternalServerError
+        })
+    }
+
+    /// Whether `encoded` was made with other parameters than the current ones, or with the
+    /// server secret as the salt of old versions.
+    fn outdated(&self, encoded: &str) -> bool {
+        let Some(params) = parse_hash(encoded) else {
+            return true;
+        };
+        // the salt is base64 encoded without padding
+        let salt_len = (SALT_LEN * 4).div_ceil(3);
+        params.variant != self.config.variant.as_lowercase_str()
+            || params.version != Version::Version13 as u32
+            || params.memory != self.config.memory
+            || params.iterations != self.config.iterations
+            || params.parallelism != self.config.parallelism
+            || params.salt.len() != salt_len
+    }
+
+    /// Check `password` against the stored hash `encoded`.
+    ///
+    /// Hashes made before the pepper was configured are still accepted and reported as
+    /// outdated. A malformed hash is an error.
+    pub fn verify(&self, encoded: &str, password: &str) -> Result<PasswordMatch, AppError> {
+        let verify = |pepper: &[u8]| {
+            argon2::verify_encoded_ext(encoded, password.as_bytes(), pepper, &[]).map_err(|e| {
+                tracing::error!("cannot verify a malformed password hash: {e}");
+                AppError::InternalServerError
+            })
+        };
+        if verify(self.pepper())? {
+            return Ok(if self.outdated(encoded) {
+                PasswordMatch::Outdated
+            } else {
+                PasswordMatch::Correct
+            });
+        }
+        if self.config.pepper.is_some() && verify(&[])? {
+            return Ok(PasswordMatch::Outdated);
+        }
+        Ok(PasswordMatch::Wrong)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// cheap parameters to keep the tests fast
+    fn config(iterations: u32, pepper: Option<&str>) -> PasswordConfig {
+        PasswordConfig {
+            memory: 64,
+            iterations,
+            pepper: pepper.map(|pepper| Secret::new(pepper.to_owned())),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn verifies_own_hashes() {
+        let hasher = PasswordHasher::new(config(1, Some("pepper")));
+        let hash = hasher.hash("hunter2").unwrap();
+        assert!(hash.starts_with("$argon2id$v=19$m=64,t=1,p=1$"));
+        assert_ne!(hash, hasher.hash("hunter2").unwrap());
+        assert_eq!(
+            hasher.verify(&hash, "hunter2").unwrap(),
+            PasswordMatch::Correct
+        );
+        assert_eq!(
+            hasher.verify(&hash, "hunter3").unwrap(),
+            PasswordMatch::Wrong
+        );
+    }
+
+    #[test]
+    fn reports_other_parameters_as_outdated() {
+        let hash = PasswordHasher::new(config(1, None))
+            .hash("hunter2")
+            .unwrap();
+        let hasher = PasswordHasher::new(config(2, None));
+        assert_eq!(
+            hasher.verify(&hash, "hunter2").unwrap(),
+            PasswordMatch::Outdated
+        );
+        assert_eq!(
+            hasher.verify(&hash, "hunter3").unwrap(),
+            PasswordMatch::Wrong
+        );
+    }
+
+    #[test]
+    fn reports_hashes_without_pepper_as_outdated() {
+        let hash = PasswordHasher::new(config(1, None))
+            .hash("hunter2")
+            .unwrap();
+        let peppered = PasswordHasher::new(config(1, Some("pepper")));
+        assert_eq!(
+            peppered.verify(&hash, "hunter2").unwrap(),
+            PasswordMatch::Outdated
+        );
+
+        // a hash with the pepper cannot be verified without it
+        let hash = peppered.hash("hunter2").unwrap();
+        let hasher = PasswordHasher::new(config(1, None));
+        assert_eq!(
+            hasher.verify(&hash, "hunter2").unwrap(),
+            PasswordMatch::Wrong
+        );
+    }
+
+    #[test]
+    fn reports_server_secret_salts_as_outdated() {
+        let hasher = PasswordHasher::new(config(1, None));
+        let argon2 = Config {
+            variant: Variant::Argon2id,
+            version: Version::Version13,
+            mem_cost: 64,
+            time_cost: 1,
+            ..Config::default()
+        };
+        let hash = argon2::hash_encoded(b"hunter2", b"the server secret", &argon2).unwrap();
+        assert_eq!(
+            hasher.verify(&hash, "hunter2").unwrap(),
+            PasswordMatch::Outdated
+        );
+    }
+
+    #[test]
+    fn rejects_malformed_hashes() {
+        let hasher = PasswordHasher::new(config(1, None));
+        assert!(hasher.verify("not a hash", "hunter2").is_err());
+        assert!(hasher.outdated("$argon2id$v=19$m=64$c2FsdA"));
+    }
+
+    #[test]
+    fn parses_hashes_without_version() {
+        let params = parse_hash("$argon2i$m=4096,t=3,p=1$c2FsdHNhbHQ$aGFzaA").unwrap();
+        assert_eq!(params.variant, "argon2i");
+        assert_eq!(params.version, 0x10);
+        assert_eq!(
+            (params.memory, params.iterations, params.parallelism),
+            (4096, 3, 1)
+        );
+        assert_eq!(params.salt, "c2FsdHNhbHQ");
+    }
+
+    #[test]
+    fn reads_variants() {
+        assert_eq!(parse_variant(" Argon2i ").unwrap(), Variant::Argon2i);
+        assert!(parse_variant("bcrypt").is_err());
+    }
+}
@@ -0,0 +1,262 @@
+use std::{
+    collections::HashMap,
+    net::IpAddr,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use axum::{
+    extract::{ConnectInfo, State},
+    headers::{authorization::Bearer, Authorization},
+    http::Request,
+    middleware::Next,
+    response::Response,
+    RequestPartsExt, TypedHeader,
+};
+
+use time::OffsetDateTime;
+
+use crate::{
+    error::AppError,
+    models::{auth::Claims, tokens},
+    utils::env_or,
+    AppState,
+};
+
+/// Entries are pruned once a table grows beyond this many keys.
+const PRUNE_THRESHOLD: usize = 10_000;
+
+/// Thresholds of the login throttling and the request rate limiter.
+#[derive(Debug, Clone)]
+pub struct RateLimitConfig {
+    /// failed logins of an account before it is locked
+    pub account_max_failures: u32,
+    /// failed logins from an IP address before it is locked
+    pub ip_max_failures: u32,
+    /// wait after the first failed login of an account, doubled with every further failure
+    pub backoff_base: Duration,
+    pub lockout: Duration,
+    /// failures older than this are forgotten
+    pub failure_window: Duration,
+    /// requests per minute of each token (or IP address without a token), `0` to disable
+    pub requests_per_minute: u32,
+}
+
+impl Default for RateLimitConfig {
+    fn default() -> Self {
+        Self {
+            account_max_failures: 5,
+            ip_max_failures: 20,
+            backoff_base: Duration::from_secs(1),
+            lockout: Duration::from_secs(15 * 60),
+            failure_window: Duration::from_secs(15 * 60),
+            requests_per_minute: 300,
+        }
+    }
+}
+
+impl RateLimitConfig {
+    /// Read the thresholds from `LOGIN_MAX_FAILURES`, `LOGIN_IP_MAX_FAILURES`,
+    /// `LOGIN_BACKOFF_SECS`, `LOGIN_LOCKOUT_SECS`, `LOGIN_FAILURE_WINDOW_SECS` and
+    /// `RATE_LIMIT_PER_MINUTE`, falling back to the defaults.
+    pub fn from_env() -> Result<Self, String> {
+        let default = Self::default();
+        Ok(Self {
+            account_max_failures: env_or("LOGIN_MAX_FAILURES", default.account_max_failures)?,
+            ip_max_failures: env_or("LOGIN_IP_MAX_FAILURES", default.ip_max_failures)?,
+            backoff_base: Duration::from_secs(env_or(
+                "LOGIN_BACKOFF_SECS",
+                default.backoff_base.as_secs(),
+            )?),
+            lockout: Duration::from_secs(env_or("LOGIN_LOCKOUT_SECS", default.lockout.as_secs())?),
+            failure_window: Duration::from_secs(env_or(
+                "LOGIN_FAILURE_WINDOW_SECS",
+                default.failure_window.as_secs(),
+            )?),
+            requests_per_minute: env_or("RATE_LIMIT_PER_MINUTE", default.requests_per_minute)?,
+        })
+    }
+}
+
+#[derive(Debug)]
+struct Failures {
+    count: u32,
+    last: Instant,
+    locked_until: Option<Instant>,
+}
+
+#[derive(Debug)]
+struct Bucket {
+    tokens: f64,
+    refilled: Instant,
+}
+
+/// In-memory login throttling and request rate limiting.
+#[derive(Debug, Default)]
+pub struct RateLimiter {
+    pub config: RateLimitConfig,
+    failures: Mutex<HashMap<String, Failures>>,
+    buckets: Mutex<HashMap<String, Bucket>>,
+}
+
+fn account_key(email: &str) -> String {
+    format!("account:{}", email.trim().to_lowercase())
+}
+
+fn ip_key(ip: IpAddr) -> String {
+    format!("ip:{ip}")
+}
+
+impl RateLimiter {
+    pub fn new(config: RateLimitConfig) -> Self {
+        Self {
+            config,
+            ..Default::default()
+        }
+    }
+
+    /// How long `key` has to wait before its next login attempt, if at all.
+    fn wait(&self, failures: &HashMap<String, Failures>, key: &str, backoff: bool) -> Duration {
+        let now = Instant::now();
+        let Some(record) = failures.get(key) else {
+            return Duration::ZERO;
+        };
+        if let Some(until) = record.locked_until {
+            return until.saturating_duration_since(now);
+        }
+        if !backoff || record.count == 0 {
+            return Duration::ZERO;
+        }
+        let delay = self
+            .config
+            .backoff_base
+            .saturating_mul(2u32.saturating_pow(record.count - 1))
+            .min(self.config.lockout);
+        (record.last + delay).saturating_duration_since(now)
+    }
+
+    /// Reject a login attempt while the account or the IP address is backing off or locked.
+    pub fn check_login(&self, email: &str, ip: IpAddr) -> Result<(), AppError> {
+        let failures = self.failures.lock().expect("rate limiter lock poisoned");
+        let wait = self
+            .wait(&failures, &account_key(email), true)
+            .max(self.wait(&failures, &ip_key(ip), false));
+        if wait > Duration::ZERO {
+            return Err(AppError::TooManyRequests(wait));
+        }
+        Ok(())
+    }
+
+    /// Count a failed login of `email` from `ip`, locking either once it reaches its threshold.
+    pub fn login_failed(&self, email: &str, ip: IpAddr) {
+        let now = Instant::now();
+        let mut failures = self.failures.lock().expect("rate limiter lock poisoned");
+        if failures.len() > PRUNE_THRESHOLD {
+            let window = self.config.failure_window.max(self.config.lockout);
+            failures.retain(|_, record| now.duration_since(record.last) < window);
+        }
+        for (key, max_failures) in [
+            (account_key(email), self.config.account_max_failures),
+            (ip_key(ip), self.config.ip_max_failures),
+        ] {
+            let record = failures.entry(key.clone()).or_insert(Failures {
+                count: 0,
+                last: now,
+                locked_until: None,
+            });
+            if now.duration_since(record.last) > self.config.failure_window
+                || matches!(record.locked_until, Some(until) if until <= now)
+            {
+                record.count = 0;
+                record.locked_until = None;
+            }
+            record.count += 1;
+            record.last = now;
+            if max_failures > 0 && record.count >= max_failures && record.locked_until.is_none() {
+                record.locked_until = Some(now + self.config.lockout);
+                tracing::warn!(
+                    "locking out {} for {}s after {} failed logins (last from {})",
+                    key,
+                    self.config.lockout.as_secs(),
+                    record.count,
+                    ip
+                );
+            }
+        }
+    }
+
+    /// Forget the failed logins of an account after a successful login.
+    pub fn login_succeeded(&self, email: &str) {
+        let mut failures = self.failures.lock().expect("rate limiter lock poisoned");
+        failures.remove(&account_key(email));
+    }
+
+    /// Take a request of `key` from its token bucket, which holds up to a minute of requests.
+    fn take(&self, key: String) -> Result<(), AppError> {
+        let per_minute = self.config.requests_per_minute;
+        if per_minute == 0 {
+            return Ok(());
+        }
+        let capacity = per_minute as f64;
+        let per_second = capacity / 60.0;
+        let now = Instant::now();
+        let mut buckets = self.buckets.lock().expect("rate limiter lock poisoned");
+        if buckets.len() > PRUNE_THRESHOLD {
+            buckets.retain(|_, bucket| now.duration_since(bucket.refilled).as_secs() < 60);
+        }
+        let bucket = buckets.entry(key).or_insert(Bucket {
+            tokens: capacity,
+            refilled: now,
+        });
+        let elapsed = now.duration_since(bucket.refilled).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * per_second).min(capacity);
+        bucket.refilled = now;
+        if bucket.tokens < 1.0 {
+            let wait = Duration::from_secs_f64((1.0 - bucket.tokens) / per_second);
+            return Err(AppError::TooManyRequests(wait));
+        }
+        bucket.tokens -= 1.0;
+        Ok(())
+    }
+}
+
+/// Whether a bearer token belongs to a user, i.e. a login token with a valid signature or an
+/// unexpired API token. Revocation is left to the `Claims` extractor.
+async fn is_known_token(state: &AppState, token: &str) -> Result<bool, AppError> {
+    if !token.starts_with(tokens::TOKEN_PREFIX) {
+        return Ok(state.jwt_keys.decode::<Claims>(token).is_ok());
+    }
+    Ok(sqlx::query_scalar(
+        r#"SELECT EXISTS(SELECT 1 FROM api_tokens WHERE token_hash = $1 AND expires > $2)"#,
+    )
+    .bind(tokens::hash(token))
+    .bind(OffsetDateTime::now_utc())
+    .fetch_one(&state.pool)
+    .await?)
+}
+
+/// Limit the request rate of every bearer token, or of the client's IP address for requests
+/// without a valid one, so that made-up tokens cannot evade the limit.
+pub async fn limit_requests<B>(
+    State(state): State<Arc<AppState>>,
+    request: Request<B>,
+    next: Next<B>,
+) -> Result<Response, AppError> {
+    let (mut parts, body) = request.into_parts();
+    let bearer = match parts.extract::<TypedHeader<Authorization<Bearer>>>().await {
+        Ok(TypedHeader(Authorization(bearer))) => Some(bearer),
+        Err(_) => None,
+    };
+    let key = match bearer {
+        // only the hash of the token is kept in memory
+        Some(bearer) if is_known_token(&state, bearer.token()).await? => {
+            format!("token:{}", tokens::hash(bearer.token()))
+        }
+        _ => match parts.extensions.get::<ConnectInfo<std::net::SocketAddr>>() {
+            Some(ConnectInfo(addr)) => ip_key(addr.ip()),
+            None => "anonymous".to_owned(),
+        },
+    };
+    state.rate_limiter.take(key)?;
+    Ok(next.run(Request::from_parts(parts, body)).await)
+}
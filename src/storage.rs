@@ -0,0 +1,68 @@
+use std::{
+    io,
+    path::PathBuf,
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+use sha2::{Digest, Sha256};
+use tokio::fs;
+
+static TMP_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+/// Content-addressed blob store for result artifacts on local disk.
+///
+/// Each blob is stored once under `<root>/<first 2 hex digits>/<sha256>`, no matter how many
+/// artifacts refer to it.
+#[derive(Debug, Clone)]
+pub struct ArtifactStore {
+    root: PathBuf,
+}
+
+impl ArtifactStore {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into() }
+    }
+
+    pub async fn init(&self) -> io::Result<()> {
+        fs::create_dir_all(&self.root).await
+    }
+
+    fn blob_path(&self, sha256: &str) -> PathBuf {
+        self.root.join(&sha256[..2]).join(sha256)
+    }
+
+    /// The hex-encoded SHA-256 checksum `data` is stored under.
+    pub fn checksum(data: &[u8]) -> String {
+        format!("{:x}", Sha256::digest(data))
+    }
+
+    /// Store `data` and return its hex-encoded SHA-256 checksum.
+    pub async fn put(&self, data: &[u8]) -> io::Result<String> {
+        let sha256 = Self::checksum(data);
+        let path = self.blob_path(&sha256);
+        if fs::try_exists(&path).await? {
+            return Ok(sha256);
+        }
+        fs::create_dir_all(path.parent().unwrap()).await?;
+        // write to a temporary file first so a partially written blob is never visible
+        let tmp_path = path.with_extension(format!(
+            "{}.{}.tmp",
+            std::process::id(),
+            TMP_COUNTER.fetch_add(1, Ordering::Relaxed)
+        ));
+        fs::write(&tmp_path, data).await?;
+        fs::rename(&tmp_path, &path).await?;
+        Ok(sha256)
+    }
+
+    pub async fn get(&self, sha256: &str) -> io::Result<Vec<u8>> {
+        fs::read(self.blob_path(sha256)).await
+    }
+
+    pub async fn remove(&self, sha256: &str) -> io::Result<()> {
+        match fs::remove_file(self.blob_path(sha256)).await {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+            _ => Ok(()),
+        }
+    }
+}
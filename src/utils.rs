@@ -1,9 +1,23 @@
-use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use std::{
+    env,
+    str::FromStr,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
 
-pub fn get_timestamp_8_hours_from_now() -> u64 {
+/// Seconds since the epoch at `lifetime` from now, for the `exp` claim of tokens.
+pub fn get_timestamp_from_now(lifetime: Duration) -> u64 {
     let now = SystemTime::now();
     let since_the_epoch = now.duration_since(UNIX_EPOCH).expect("Time went backwards");
-    // expires after 8 hours
-    let eighthoursfromnow = since_the_epoch + Duration::from_secs(8 * 3600);
-    eighthoursfromnow.as_secs()
+    (since_the_epoch + lifetime).as_secs()
+}
+
+/// The value of the environment variable `name`, or `default` when it is not set.
+pub fn env_or<T: FromStr>(name: &str, default: T) -> Result<T, String> {
+    match env::var(name) {
+        Ok(value) => value
+            .trim()
+            .parse()
+            .map_err(|_| format!("invalid value of {name}: {value}")),
+        Err(_) => Ok(default),
+    }
 }